@@ -4,7 +4,8 @@
 
 /// The size of the shared memory map used for AFL-style coverage tracking.
 /// This value (2^16) is standard for AFL.
-/// The actual memory map size is AFL_COVERAGE_MAP_SIZE * history_size
+/// The default memory map size is AFL_COVERAGE_MAP_SIZE * history_size, unless a map size is
+/// configured with `CanisterBuilder::with_map_size`.
 pub const AFL_COVERAGE_MAP_SIZE: i32 = 65536;
 
 /// The module name for the IC System API.
@@ -19,3 +20,28 @@ pub const COVERAGE_FN_EXPORT_NAME: &str = "__export_coverage_for_afl";
 /// The fuzzer orchestrator calls this function to retrieve the instruction count
 /// from the last canister_update/canister_query execution.
 pub const INSTRUCTION_COUNT_FN_EXPORT_NAME: &str = "__export_instruction_count_for_afl";
+
+/// The size of a Wasm memory page in bytes.
+pub const WASM_PAGE_SIZE: i64 = 65536;
+
+/// The name of the function exported by a canister instrumented for CmpLog to expose the
+/// operands of its traced comparisons. The fuzzer orchestrator calls this function after a
+/// tracing execution; the buffer is cleared by every call.
+pub const CMPLOG_FN_EXPORT_NAME: &str = "__export_cmplog_for_afl";
+
+/// The number of entries of the CmpLog buffer. Comparison sites are numbered in order and
+/// wrap around, so sites `CMPLOG_MAP_ENTRIES` apart share an entry.
+pub const CMPLOG_MAP_ENTRIES: i32 = 2048;
+
+/// The size in bytes of the CmpLog buffer: the two operands of every entry as little-endian
+/// `u64`s, followed by one byte per entry holding the operand width (0 if not hit, 4 or 8).
+pub const CMPLOG_MAP_SIZE: i32 = CMPLOG_MAP_ENTRIES * 17;
+
+/// The name of the function exported by a canister instrumented for value profiling to expose
+/// its value-profile map. The fuzzer orchestrator calls this function after every execution,
+/// alongside [`COVERAGE_FN_EXPORT_NAME`]; the map is cleared by every call.
+pub const VALUE_PROFILE_FN_EXPORT_NAME: &str = "__export_value_profile_for_afl";
+
+/// The size in bytes of the value-profile map, one byte per comparison site. Sites are
+/// numbered in order and wrap around, so sites `VALUE_PROFILE_MAP_SIZE` apart share a byte.
+pub const VALUE_PROFILE_MAP_SIZE: i32 = 16384;
@@ -0,0 +1,297 @@
+//! JSON reports saved next to the crashing inputs.
+//!
+//! [`CrashReportFeedback`] names every saved crash, timeout or OOM input after its exit kind
+//! (e.g. `crash_<hash>`) and writes a [`CrashReport`] to `<name>.json` in the same directory,
+//! so a crashes directory can be reviewed without replaying the inputs. If an identical input
+//! was already saved, e.g. when crashes are not deduplicated, a numeric suffix keeps the
+//! earlier report (`crash_<hash>_1`). It also counts the saved inputs by exit kind in the
+//! [`ObjectiveCounts`] metadata of the state.
+//!
+//! The method and the reject of the last canister call of an execution are recorded with
+//! [`record_method`] and [`record_reject`] for the reports and the crash signatures. They are
+//! kept per thread, as a fuzzer runs its executions on a single thread, so fuzzers or tests
+//! running on other threads of the process do not overwrite them.
+
+use crate::custom::feedback::crash_triage::CrashSignature;
+use crate::custom::observer::instruction_count::INSTRUCTION_MAP;
+use crate::libafl::corpus::Testcase;
+use crate::libafl::executors::ExitKind;
+use crate::libafl::feedbacks::{Feedback, StateInitializer};
+use crate::libafl::inputs::Input;
+use crate::libafl::{Error, HasMetadata};
+use chrono::Local;
+use pocket_ic::{ErrorCode, RejectResponse};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use crate::libafl_bolts::Named;
+
+/// Name used by `CrashReportFeedback`
+pub const CRASH_REPORT_FEEDBACK_NAME: &str = "CrashReportFeedback";
+
+/// The content of the JSON file saved next to a crashing input.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CrashReport {
+    /// The exit kind of the execution, e.g. `Crash` or `Timeout`.
+    pub exit_kind: String,
+    /// The error code of the failing call, if a reject was recorded.
+    pub error_code: Option<ErrorCode>,
+    /// The full reject message of the failing call, if a reject was recorded.
+    pub reject_message: Option<String>,
+    /// The instruction count of the execution, if instruction counting is enabled.
+    pub instruction_count: Option<u64>,
+    /// The method of the last call, if known.
+    pub method: Option<String>,
+    /// The signature of the crash bucket, see [`CrashSignature`].
+    pub signature: Option<String>,
+    /// The local time at which the input was saved, in RFC 3339 format.
+    pub timestamp: String,
+    /// The RNG seed of the fuzzer that found the input.
+    pub rng_seed: u64,
+}
+
+/// The number of saved crashing inputs by exit kind, stored as metadata of the `libafl` state.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ObjectiveCounts {
+    /// Inputs saved with `ExitKind::Crash`.
+    pub crashes: u64,
+    /// Inputs saved with `ExitKind::Timeout`.
+    pub timeouts: u64,
+    /// Inputs saved with `ExitKind::Oom`.
+    pub ooms: u64,
+}
+
+libafl_bolts::impl_serdeany!(ObjectiveCounts);
+
+impl ObjectiveCounts {
+    /// Counts an input saved with `exit_kind`.
+    pub fn record(&mut self, exit_kind: &ExitKind) {
+        match exit_kind {
+            ExitKind::Timeout => self.timeouts += 1,
+            ExitKind::Oom => self.ooms += 1,
+            _ => self.crashes += 1,
+        }
+    }
+}
+
+/// What is known about the last canister call of the current execution.
+#[derive(Debug, Clone, Default)]
+pub struct LastCall {
+    /// The method of the call, if recorded with [`record_method`].
+    pub method: Option<String>,
+    /// The reject of the call, if it failed with a trap, an OOM or a timeout.
+    pub reject: Option<RejectResponse>,
+}
+
+thread_local! {
+    static LAST_CALL: RefCell<LastCall> = RefCell::default();
+}
+
+/// Records the method of a canister call before it is made, for the crash reports.
+pub fn record_method(method: &str) {
+    LAST_CALL.with_borrow_mut(|last_call| {
+        last_call.method = Some(method.to_string());
+        last_call.reject = None;
+    });
+}
+
+/// Records the reject of a failing canister call, to be used in the signature of the crash.
+pub fn record_reject(reject: &RejectResponse) {
+    LAST_CALL.with_borrow_mut(|last_call| last_call.reject = Some(reject.clone()));
+}
+
+/// Forgets the recorded call. Called before each execution.
+pub fn clear_last_call() {
+    LAST_CALL.take();
+}
+
+/// Returns the recorded call of the current execution on this thread.
+pub fn last_call() -> LastCall {
+    LAST_CALL.with_borrow(LastCall::clone)
+}
+
+/// A libafl feedback that writes a [`CrashReport`] for every saved crashing input.
+///
+/// It is meant to be the last feedback of the objective in a `feedback_and_fast!` chain, so it
+/// only runs for inputs that are saved as solutions. It always considers the input interesting.
+#[derive(Debug)]
+pub struct CrashReportFeedback {
+    crashes_dir: PathBuf,
+    rng_seed: u64,
+    default_method: Option<String>,
+    instruction_count_enabled: bool,
+    report: Option<CrashReport>,
+    exit_kind: Option<ExitKind>,
+}
+
+impl CrashReportFeedback {
+    /// Creates a new `CrashReportFeedback` writing the reports to `crashes_dir`.
+    ///
+    /// `default_method` is reported when no method was recorded with
+    /// [`record_method`] during the
+    /// execution. The instruction count is only reported if `instruction_count_enabled` is set.
+    #[must_use]
+    pub fn new(
+        crashes_dir: PathBuf,
+        rng_seed: u64,
+        default_method: Option<String>,
+        instruction_count_enabled: bool,
+    ) -> Self {
+        Self {
+            crashes_dir,
+            rng_seed,
+            default_method,
+            instruction_count_enabled,
+            report: None,
+            exit_kind: None,
+        }
+    }
+}
+
+impl Named for CrashReportFeedback {
+    #[inline]
+    fn name(&self) -> &Cow<'static, str> {
+        &Cow::Borrowed(CRASH_REPORT_FEEDBACK_NAME)
+    }
+}
+
+impl<S> StateInitializer<S> for CrashReportFeedback {
+    fn init_state(&mut self, _state: &mut S) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl<EM, I, OT, S> Feedback<EM, I, OT, S> for CrashReportFeedback
+where
+    I: Input,
+    S: HasMetadata,
+{
+    #[allow(clippy::wrong_self_convention, static_mut_refs)]
+    fn is_interesting(
+        &mut self,
+        _state: &mut S,
+        _manager: &mut EM,
+        _input: &I,
+        _observers: &OT,
+        exit_kind: &ExitKind,
+    ) -> Result<bool, Error> {
+        let last_call = last_call();
+        let instruction_count = self
+            .instruction_count_enabled
+            .then(|| unsafe { INSTRUCTION_MAP.borrow().current_instructions });
+        self.report = Some(CrashReport {
+            exit_kind: format!("{exit_kind:?}"),
+            error_code: last_call.reject.as_ref().map(|r| r.error_code),
+            reject_message: last_call.reject.map(|r| r.reject_message),
+            instruction_count,
+            method: last_call.method.or_else(|| self.default_method.clone()),
+            signature: None,
+            timestamp: Local::now().to_rfc3339(),
+            rng_seed: self.rng_seed,
+        });
+        self.exit_kind = Some(*exit_kind);
+        Ok(true)
+    }
+
+    fn append_metadata(
+        &mut self,
+        state: &mut S,
+        _manager: &mut EM,
+        _observers: &OT,
+        testcase: &mut Testcase<I>,
+    ) -> Result<(), Error> {
+        let Some(mut report) = self.report.take() else {
+            return Ok(());
+        };
+        if let Some(exit_kind) = self.exit_kind.take() {
+            state
+                .metadata_or_insert_with(ObjectiveCounts::default)
+                .record(&exit_kind);
+        }
+        report.signature = testcase
+            .metadata::<CrashSignature>()
+            .ok()
+            .map(ToString::to_string);
+
+        let input = testcase
+            .input()
+            .as_ref()
+            .ok_or_else(|| Error::empty("The crashing testcase has no input"))?;
+        let name = format!(
+            "{}_{}",
+            report.exit_kind.to_lowercase(),
+            input.generate_name(None)
+        );
+
+        let json = serde_json::to_vec_pretty(&report)
+            .map_err(|e| Error::serialize(format!("Failed to serialize the crash report: {e}")))?;
+        let (file_name, mut file) = create_report_file(&self.crashes_dir, &name)?;
+        file.write_all(&json)?;
+        *testcase.filename_mut() = Some(file_name);
+        Ok(())
+    }
+}
+
+/// Creates the report file `<name>.json` in `dir`. If it already exists, the first free
+/// `<name>_<n>.json` is created instead, so reports of identical inputs are not overwritten,
+/// even by another worker sharing the directory.
+///
+/// Returns the chosen name, without the extension, and the created file.
+fn create_report_file(dir: &Path, name: &str) -> Result<(String, File), Error> {
+    let mut n = 0;
+    loop {
+        let file_name = if n == 0 {
+            name.to_string()
+        } else {
+            format!("{name}_{n}")
+        };
+        match OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(dir.join(format!("{file_name}.json")))
+        {
+            Ok(file) => return Ok((file_name, file)),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => n += 1,
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report_file_names_are_unique() {
+        let dir = std::env::temp_dir().join(format!("canfuzz_crash_report_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let names: Vec<String> = (0..3)
+            .map(|_| create_report_file(&dir, "crash_0123").unwrap().0)
+            .collect();
+        let other = create_report_file(&dir, "timeout_0123").unwrap().0;
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(names, ["crash_0123", "crash_0123_1", "crash_0123_2"]);
+        assert_eq!(other, "timeout_0123");
+    }
+
+    #[test]
+    fn test_last_call_is_per_thread() {
+        record_method("deposit");
+        std::thread::spawn(|| {
+            assert_eq!(last_call().method, None);
+            record_method("withdraw");
+        })
+        .join()
+        .unwrap();
+        assert_eq!(last_call().method.as_deref(), Some("deposit"));
+
+        clear_last_call();
+        assert_eq!(last_call().method, None);
+    }
+}
@@ -0,0 +1,421 @@
+//! Crash triage: grouping crashing inputs into buckets.
+//!
+//! A long campaign usually finds the same bug over and over. [`CrashTriageFeedback`] computes
+//! a [`CrashSignature`] for every input that satisfies the crash, timeout or OOM objectives and
+//! groups the inputs by signature. Only the first input of each bucket is kept as a solution,
+//! the others are counted. The buckets are stored in the [`CrashBuckets`] state metadata and
+//! their counts are reported to the monitor as user stats: `crash_buckets` is the number of
+//! buckets and `bucket_<id>` the count of the bucket whose signature has the
+//! [`id`](CrashSignature::id) `<id>`.
+//!
+//! With parallel workers, each worker keeps its own buckets. The id of a signature is the same
+//! in every worker, so the monitor sums the `bucket_<id>` counts of all workers. `crash_buckets`
+//! is the largest number of buckets of a single worker, a lower bound of the number of distinct
+//! buckets; the terminal UI counts the distinct `bucket_<id>` stats instead.
+//!
+//! The signature is made of the reject code and error code of the failing call, its message
+//! normalized by [`normalize_reject_message`] and, if enabled in [`CrashTriageConfig`], a hash
+//! of the covered edges. The failing call is recorded by
+//! [`parse_canister_result_for_trap`](crate::util::parse_canister_result_for_trap).
+
+use crate::custom::feedback::crash_report::last_call;
+use crate::libafl::corpus::Testcase;
+use crate::libafl::events::{Event, EventFirer, EventWithStats};
+use crate::libafl::executors::ExitKind;
+use crate::libafl::feedbacks::{ExitKindLogic, Feedback, StateInitializer};
+use crate::libafl::monitors::stats::{AggregatorOps, UserStats, UserStatsValue};
+use crate::libafl::observers::MapObserver;
+use crate::libafl::state::HasExecutions;
+use crate::libafl::{Error, HasMetadata};
+use candid::Principal;
+use pocket_ic::{ErrorCode, RejectCode, RejectResponse};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::fmt;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::marker::PhantomData;
+
+use crate::libafl_bolts::Named;
+use crate::libafl_bolts::tuples::{Handle, Handled, MatchNameRef};
+
+/// Name used by `CrashTriageFeedback`
+pub const CRASH_TRIAGE_FEEDBACK_NAME: &str = "CrashTriageFeedback";
+
+/// Configuration of the crash triage.
+///
+/// Returned by [`FuzzerOrchestrator::crash_triage_config`](crate::orchestrator::FuzzerOrchestrator::crash_triage_config).
+#[derive(Debug, Clone)]
+pub struct CrashTriageConfig {
+    /// Keep only one input per bucket. If `false`, every crashing input is saved, but the
+    /// buckets are still counted.
+    pub deduplicate: bool,
+    /// Include a hash of the covered edges in the signature. Crashes reached through
+    /// different paths then end up in different buckets.
+    pub include_coverage: bool,
+}
+
+impl Default for CrashTriageConfig {
+    fn default() -> Self {
+        Self {
+            deduplicate: true,
+            include_coverage: false,
+        }
+    }
+}
+
+/// The signature by which crashing inputs are grouped.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CrashSignature {
+    /// The exit kind of the execution, e.g. `Crash`.
+    pub exit_kind: String,
+    /// The reject code of the failing call, if a reject was recorded.
+    pub reject_code: Option<RejectCode>,
+    /// The error code of the failing call, if a reject was recorded.
+    pub error_code: Option<ErrorCode>,
+    /// The normalized reject message, see [`normalize_reject_message`].
+    pub message: String,
+    /// A hash of the covered edges, if enabled in [`CrashTriageConfig::include_coverage`].
+    pub coverage_hash: Option<u64>,
+}
+
+libafl_bolts::impl_serdeany!(CrashSignature);
+
+impl CrashSignature {
+    /// Creates the signature of an execution that ended with `exit_kind` after `reject`.
+    pub fn new(
+        exit_kind: &ExitKind,
+        reject: Option<&RejectResponse>,
+        coverage_hash: Option<u64>,
+    ) -> Self {
+        Self {
+            exit_kind: format!("{exit_kind:?}"),
+            reject_code: reject.map(|r| r.reject_code),
+            error_code: reject.map(|r| r.error_code),
+            message: reject
+                .map(|r| normalize_reject_message(&r.reject_message))
+                .unwrap_or_default(),
+            coverage_hash,
+        }
+    }
+
+    /// A short hash of the signature that names its bucket in the user stats. Unlike the
+    /// index of the bucket, it is the same in every worker.
+    #[must_use]
+    pub fn id(&self) -> String {
+        let mut hasher = DefaultHasher::new();
+        self.to_string().hash(&mut hasher);
+        format!("{:08x}", hasher.finish() >> 32)
+    }
+}
+
+impl fmt::Display for CrashSignature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.exit_kind)?;
+        if let (Some(reject_code), Some(error_code)) = (self.reject_code, self.error_code) {
+            write!(f, " | {reject_code:?}/{error_code:?}")?;
+        }
+        if !self.message.is_empty() {
+            write!(f, " | {}", self.message)?;
+        }
+        if let Some(hash) = self.coverage_hash {
+            write!(f, " | edges {hash:016x}")?;
+        }
+        Ok(())
+    }
+}
+
+/// A group of crashing inputs with the same [`CrashSignature`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CrashBucket {
+    pub signature: CrashSignature,
+    /// The number of crashing inputs with this signature.
+    pub count: u64,
+}
+
+/// The crash buckets found so far, in the order they were found. Stored in the state metadata.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CrashBuckets {
+    pub buckets: Vec<CrashBucket>,
+}
+
+libafl_bolts::impl_serdeany!(CrashBuckets);
+
+impl CrashBuckets {
+    /// Adds a crash with the given signature. Returns the index of its bucket and whether
+    /// the bucket is new.
+    pub fn record(&mut self, signature: CrashSignature) -> (usize, bool) {
+        if let Some(index) = self.buckets.iter().position(|b| b.signature == signature) {
+            self.buckets[index].count += 1;
+            return (index, false);
+        }
+        self.buckets.push(CrashBucket {
+            signature,
+            count: 1,
+        });
+        (self.buckets.len() - 1, true)
+    }
+}
+
+/// Normalizes a reject message so that crashes caused by the same bug get the same message.
+///
+/// If the message contains a source location, e.g. `src/lib.rs:42:5` from a Rust panic or
+/// `main.mo:12.3-12.20` from a Motoko trap, only the location is kept: the rest of a panic
+/// message often contains values that depend on the input. Otherwise, principals are
+/// replaced by `<principal>` and numbers by `N`.
+pub fn normalize_reject_message(message: &str) -> String {
+    if let Some(location) = message.split_whitespace().find_map(source_location) {
+        return location.to_string();
+    }
+    let words: Vec<String> = message
+        .split_whitespace()
+        .map(|word| {
+            let trimmed = trim_punctuation(word);
+            if trimmed.contains('-') && Principal::from_text(trimmed).is_ok() {
+                word.replace(trimmed, "<principal>")
+            } else {
+                replace_numbers(word)
+            }
+        })
+        .collect();
+    words.join(" ")
+}
+
+fn trim_punctuation(word: &str) -> &str {
+    word.trim_start_matches(['\'', '"', '`', '(', '['])
+        .trim_end_matches(['\'', '"', '`', ')', ']', ',', ';', ':', '.'])
+}
+
+/// Returns the word if it is a source location: a path followed by a line and column,
+/// separated by `:`, `.` or `-`.
+fn source_location(word: &str) -> Option<&str> {
+    let word = trim_punctuation(word);
+    let (path, position) = word.split_once(':')?;
+    let is_position = position.starts_with(|c: char| c.is_ascii_digit())
+        && position
+            .chars()
+            .all(|c| c.is_ascii_digit() || matches!(c, ':' | '.' | '-'));
+    (!path.is_empty() && path.contains('.') && is_position).then_some(word)
+}
+
+fn replace_numbers(word: &str) -> String {
+    let mut result = String::with_capacity(word.len());
+    let mut in_number = false;
+    for c in word.chars() {
+        if c.is_ascii_digit() {
+            if !in_number {
+                result.push('N');
+            }
+            in_number = true;
+        } else {
+            result.push(c);
+            in_number = false;
+        }
+    }
+    result
+}
+
+/// Logic which finds all [`ExitKind::Ok`] exits interesting.
+///
+/// Used to keep crashing inputs out of the corpus when they are not saved as solutions
+/// because their bucket already has a representative.
+#[derive(Debug, Copy, Clone)]
+pub struct OkLogic;
+
+impl ExitKindLogic for OkLogic {
+    const NAME: Cow<'static, str> = Cow::Borrowed("OkFeedback");
+
+    fn check_exit_kind(kind: &ExitKind) -> Result<bool, Error> {
+        Ok(matches!(kind, ExitKind::Ok))
+    }
+}
+
+/// A libafl feedback that buckets crashing inputs by [`CrashSignature`].
+///
+/// It is meant to be combined with the crash objectives using `feedback_and_fast!`, so it
+/// only runs on crashing inputs. It considers an input interesting if it opens a new bucket,
+/// or always if [`CrashTriageConfig::deduplicate`] is `false`.
+///
+/// `C` is the type of the coverage map observer, used to hash the covered edges.
+#[derive(Debug)]
+pub struct CrashTriageFeedback<C, O> {
+    map_handle: Handle<C>,
+    config: CrashTriageConfig,
+    signature: Option<CrashSignature>,
+    phantom: PhantomData<O>,
+}
+
+impl<C, O> CrashTriageFeedback<C, O>
+where
+    C: Named + AsRef<O>,
+{
+    #[must_use]
+    pub fn new(map_observer: &C, config: CrashTriageConfig) -> Self {
+        Self {
+            map_handle: map_observer.handle(),
+            config,
+            signature: None,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<C, O> Named for CrashTriageFeedback<C, O> {
+    #[inline]
+    fn name(&self) -> &Cow<'static, str> {
+        &Cow::Borrowed(CRASH_TRIAGE_FEEDBACK_NAME)
+    }
+}
+
+impl<C, O, S> StateInitializer<S> for CrashTriageFeedback<C, O> {
+    fn init_state(&mut self, _state: &mut S) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl<C, EM, I, O, OT, S> Feedback<EM, I, OT, S> for CrashTriageFeedback<C, O>
+where
+    C: AsRef<O>,
+    O: MapObserver<Entry = u8>,
+    EM: EventFirer<I, S>,
+    OT: MatchNameRef,
+    S: HasMetadata + HasExecutions,
+{
+    #[allow(clippy::wrong_self_convention)]
+    fn is_interesting(
+        &mut self,
+        state: &mut S,
+        manager: &mut EM,
+        _input: &I,
+        observers: &OT,
+        exit_kind: &ExitKind,
+    ) -> Result<bool, Error> {
+        let coverage_hash = if self.config.include_coverage {
+            let map = observers
+                .get(&self.map_handle)
+                .ok_or_else(|| Error::key_not_found("The coverage map observer is missing"))?
+                .as_ref();
+            let mut hasher = DefaultHasher::new();
+            (0..map.usable_count())
+                .filter(|&i| map.get(i) != 0)
+                .for_each(|i| i.hash(&mut hasher));
+            Some(hasher.finish())
+        } else {
+            None
+        };
+        let signature = CrashSignature::new(exit_kind, last_call().reject.as_ref(), coverage_hash);
+
+        let buckets = state.metadata_or_insert_with(CrashBuckets::default);
+        let (index, is_new) = buckets.record(signature.clone());
+        let count = buckets.buckets[index].count;
+        let num_buckets = buckets.buckets.len() as u64;
+        if is_new {
+            println!("[triage] NEW BUCKET {} | {signature}", signature.id());
+        }
+
+        let executions = *state.executions();
+        for (name, value, aggregator) in [
+            (
+                Cow::Borrowed("crash_buckets"),
+                num_buckets,
+                AggregatorOps::Max,
+            ),
+            (
+                Cow::Owned(format!("bucket_{}", signature.id())),
+                count,
+                AggregatorOps::Sum,
+            ),
+        ] {
+            manager.fire(
+                state,
+                EventWithStats::with_current_time(
+                    Event::UpdateUserStats {
+                        name,
+                        value: UserStats::new(UserStatsValue::Number(value), aggregator),
+                        phantom: PhantomData,
+                    },
+                    executions,
+                ),
+            )?;
+        }
+
+        self.signature = Some(signature);
+        Ok(is_new || !self.config.deduplicate)
+    }
+
+    fn append_metadata(
+        &mut self,
+        _state: &mut S,
+        _manager: &mut EM,
+        _observers: &OT,
+        testcase: &mut Testcase<I>,
+    ) -> Result<(), Error> {
+        if let Some(signature) = self.signature.take() {
+            testcase.add_metadata(signature);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rust_panic_keeps_location() {
+        let message = "Error from Canister bkyz2-fmaaa-aaaaa-qaaaq-cai: Canister called `ic0.trap` with message: 'Panicked at 'index out of bounds: the len is 3 but the index is 7', src/lib.rs:42:5'.";
+        assert_eq!(normalize_reject_message(message), "src/lib.rs:42:5");
+    }
+
+    #[test]
+    fn test_motoko_trap_keeps_location() {
+        let message = "Canister bkyz2-fmaaa-aaaaa-qaaaq-cai trapped explicitly: assertion failed at main.mo:12.3-12.20";
+        assert_eq!(normalize_reject_message(message), "main.mo:12.3-12.20");
+    }
+
+    #[test]
+    fn test_message_without_location_is_normalized() {
+        let message =
+            "Canister bkyz2-fmaaa-aaaaa-qaaaq-cai trapped: heap out of bounds at offset 123456";
+        assert_eq!(
+            normalize_reject_message(message),
+            "Canister <principal> trapped: heap out of bounds at offset N"
+        );
+    }
+
+    #[test]
+    fn test_buckets_group_same_signature() {
+        let reject = |message: &str| RejectResponse {
+            reject_code: RejectCode::CanisterError,
+            reject_message: message.to_string(),
+            error_code: ErrorCode::CanisterCalledTrap,
+            certified: true,
+        };
+        let signature =
+            |message: &str| CrashSignature::new(&ExitKind::Crash, Some(&reject(message)), None);
+
+        let mut buckets = CrashBuckets::default();
+        assert_eq!(
+            buckets.record(signature("Panicked at 'a: 1', src/lib.rs:1:1")),
+            (0, true)
+        );
+        assert_eq!(
+            buckets.record(signature("Panicked at 'a: 2', src/lib.rs:1:1")),
+            (0, false)
+        );
+        assert_eq!(
+            buckets.record(signature("Panicked at 'b', src/lib.rs:9:1")),
+            (1, true)
+        );
+        assert_eq!(buckets.buckets[0].count, 2);
+        assert_eq!(buckets.buckets[1].count, 1);
+        assert_eq!(
+            signature("Panicked at 'a: 1', src/lib.rs:1:1").id(),
+            signature("Panicked at 'a: 2', src/lib.rs:1:1").id()
+        );
+        assert_ne!(
+            buckets.buckets[0].signature.id(),
+            buckets.buckets[1].signature.id()
+        );
+        assert_eq!(buckets.buckets[0].signature.id().len(), 8);
+    }
+}
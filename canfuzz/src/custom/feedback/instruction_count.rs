@@ -2,17 +2,22 @@
 //!
 //! [`InstructionCountFeedback`] marks an input as "interesting" when it increases the
 //! maximum observed instruction count, guiding the fuzzer toward inputs that consume
-//! more IC instructions.
+//! more IC instructions. Every new maximum is reported to the monitor as the
+//! `max_instructions` user stat.
 
 use crate::custom::observer::instruction_count::{
     INSTRUCTION_COUNT_OBSERVER_NAME, InstructionCountObserver,
 };
+use crate::libafl::events::{Event, EventFirer, EventWithStats};
 use crate::libafl::executors::ExitKind;
 use crate::libafl::feedbacks::{Feedback, StateInitializer};
+use crate::libafl::monitors::stats::{AggregatorOps, UserStats, UserStatsValue};
 use crate::libafl::state::HasExecutions;
 use crate::libafl::{Error, HasNamedMetadata};
+use crate::monitor::MAX_INSTRUCTIONS_STAT_NAME;
 use serde::Serialize;
 use std::borrow::Cow;
+use std::marker::PhantomData;
 
 use crate::libafl_bolts::Named;
 use crate::libafl_bolts::tuples::MatchNameRef;
@@ -55,20 +60,39 @@ impl<S> StateInitializer<S> for InstructionCountFeedback<'_> {
 
 impl<EM, I, OT, S> Feedback<EM, I, OT, S> for InstructionCountFeedback<'_>
 where
+    EM: EventFirer<I, S>,
     S: HasNamedMetadata + HasExecutions,
     OT: MatchName,
 {
     #[allow(clippy::wrong_self_convention)]
     fn is_interesting(
         &mut self,
-        _state: &mut S,
-        _manager: &mut EM,
+        state: &mut S,
+        manager: &mut EM,
         _input: &I,
         observers: &OT,
         _exit_kind: &ExitKind,
     ) -> Result<bool, Error> {
         let observer: &InstructionCountObserver = observers.get(&self.handle).unwrap();
-        Ok(observer.get_ref().increased)
+        let map = *observer.get_ref();
+        if map.increased {
+            let executions = *state.executions();
+            manager.fire(
+                state,
+                EventWithStats::with_current_time(
+                    Event::UpdateUserStats {
+                        name: Cow::Borrowed(MAX_INSTRUCTIONS_STAT_NAME),
+                        value: UserStats::new(
+                            UserStatsValue::Number(map.max_instructions),
+                            AggregatorOps::Max,
+                        ),
+                        phantom: PhantomData,
+                    },
+                    executions,
+                ),
+            )?;
+        }
+        Ok(map.increased)
     }
 
     fn append_metadata(
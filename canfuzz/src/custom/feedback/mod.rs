@@ -1,2 +1,4 @@
+pub mod crash_report;
+pub mod crash_triage;
 pub mod instruction_count;
 pub mod oom_exit_kind;
@@ -0,0 +1,169 @@
+//! A structured input describing a sequence of canister calls.
+//!
+//! Many canister bugs only show up after several calls to different methods, e.g. a
+//! deposit followed by a withdrawal from another caller. [`CallSequenceInput`] models such a
+//! scenario as a list of [`CallStep`]s, each with its own method, caller, Candid-encoded
+//! arguments and an optional advance of the IC time. It is mutated by the
+//! [`CallSequenceMutator`](crate::custom::mutator::call_sequence::CallSequenceMutator).
+//!
+//! To fuzz with it, implement `FuzzerOrchestrator<CallSequenceInput>` and call
+//! [`CallSequenceInput::execute`] from `execute`. The input is serialized with `postcard`,
+//! both in the seed corpus directory and in the corpus written by the fuzzer.
+
+use candid::Principal;
+use ic_management_canister_types::CanisterId;
+use pocket_ic::PocketIc;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+use super::FuzzerInput;
+use crate::custom::feedback::crash_report::record_method;
+use crate::custom::mutator::call_sequence::{CallSequenceArgs, CallSequenceMutator};
+use crate::custom::mutator::candid::CandidTypeDefArgs;
+use crate::libafl::executors::ExitKind;
+use crate::libafl::inputs::Input;
+use crate::libafl_bolts::HasLen;
+use crate::util::parse_canister_result_for_trap;
+
+/// The maximum number of steps of the default [`CallSequenceMutator`].
+const DEFAULT_MAX_STEPS: usize = 16;
+
+/// A single canister call in a [`CallSequenceInput`].
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct CallStep {
+    /// The name of the canister method to call.
+    pub method: String,
+    /// The principal the call is made from.
+    pub caller: Principal,
+    /// The Candid-encoded arguments of the call.
+    pub args: Vec<u8>,
+    /// If set, the IC time is advanced by this duration after the call.
+    pub advance_time: Option<Duration>,
+}
+
+/// A `libafl` input made of a sequence of calls to the methods of a canister.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct CallSequenceInput {
+    /// The calls, executed in order.
+    pub steps: Vec<CallStep>,
+}
+
+impl Input for CallSequenceInput {}
+
+impl HasLen for CallSequenceInput {
+    fn len(&self) -> usize {
+        self.steps.len()
+    }
+}
+
+impl FuzzerInput for CallSequenceInput {
+    type Mutator = CallSequenceMutator;
+
+    /// Calls all methods of the service defined in `candid_args.definition`, anonymously and
+    /// with up to 16 steps. Override
+    /// [`FuzzerOrchestrator::mutator`](crate::orchestrator::FuzzerOrchestrator::mutator)
+    /// to restrict the methods or set the callers.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `candid_args` is `None`.
+    fn default_mutator(candid_args: Option<CandidTypeDefArgs>) -> Self::Mutator {
+        let candid_args = candid_args.expect(
+            "CallSequenceInput requires get_candid_args() or an overridden mutator() to know the canister methods",
+        );
+        CallSequenceMutator::new(CallSequenceArgs {
+            definition: candid_args.definition,
+            methods: vec![],
+            callers: vec![],
+            max_steps: DEFAULT_MAX_STEPS,
+        })
+    }
+
+    /// A sequence with a single random call.
+    fn initial_input(mutator: &Self::Mutator, seed: u64) -> Self {
+        mutator.random_input(seed)
+    }
+
+    /// The Candid-encoded arguments of each step. Fixed-width Candid integers (`nat8` to
+    /// `nat64`, `int8` to `int64`) are encoded little-endian, so replacing such an operand with
+    /// one of the same width keeps the arguments valid. `nat` and `int` are LEB128-encoded, so
+    /// replacements inside them are lossy (see [`crate::custom::mutator::i2s`]).
+    fn bytes_mut(&mut self) -> Vec<&mut [u8]> {
+        self.steps
+            .iter_mut()
+            .map(|step| step.args.as_mut_slice())
+            .collect()
+    }
+}
+
+impl CallSequenceInput {
+    /// Creates a new input from the given steps.
+    pub fn new(steps: Vec<CallStep>) -> Self {
+        Self { steps }
+    }
+
+    /// Executes the steps as update calls to `canister_id`, in order.
+    ///
+    /// Each result is checked with [`parse_canister_result_for_trap`]. Execution stops at the
+    /// first step that does not return `ExitKind::Ok`, and its exit kind is returned.
+    pub fn execute(&self, pic: &PocketIc, canister_id: CanisterId) -> ExitKind {
+        for step in &self.steps {
+            record_method(&step.method);
+            let result = pic.update_call(canister_id, step.caller, &step.method, step.args.clone());
+            let exit_kind = parse_canister_result_for_trap(result);
+            if exit_kind != ExitKind::Ok {
+                return exit_kind;
+            }
+            if let Some(duration) = step.advance_time {
+                pic.advance_time(duration);
+            }
+        }
+        ExitKind::Ok
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_input() -> CallSequenceInput {
+        CallSequenceInput::new(vec![
+            CallStep {
+                method: "deposit".to_string(),
+                caller: Principal::anonymous(),
+                args: candid::encode_one(42u64).unwrap(),
+                advance_time: Some(Duration::from_secs(60)),
+            },
+            CallStep {
+                method: "withdraw".to_string(),
+                caller: Principal::management_canister(),
+                args: candid::encode_args(()).unwrap(),
+                advance_time: None,
+            },
+        ])
+    }
+
+    #[test]
+    fn test_seed_corpus_round_trip() {
+        let input = sample_input();
+        let bytes = input.to_bytes();
+        assert_eq!(CallSequenceInput::from_bytes(&bytes).unwrap(), input);
+        assert!(CallSequenceInput::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn test_on_disk_corpus_round_trip() {
+        // The on-disk corpus writes inputs with `Input::to_file`, which must match the
+        // seed corpus format so saved inputs can be fed back as seeds.
+        let path =
+            std::env::temp_dir().join(format!("canfuzz_call_sequence_{}", std::process::id()));
+        let input = sample_input();
+        input.to_file(&path).unwrap();
+        let written = std::fs::read(&path).unwrap();
+        let loaded = CallSequenceInput::from_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded, input);
+        assert_eq!(written, input.to_bytes());
+    }
+}
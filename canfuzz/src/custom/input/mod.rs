@@ -0,0 +1,100 @@
+//! Input types for canister fuzzing.
+//!
+//! The [`FuzzerOrchestrator`](crate::orchestrator::FuzzerOrchestrator) is generic over the
+//! input type through the [`FuzzerInput`] trait. An input type defines its default mutator
+//! and how it is read from and written to the corpus. `BytesInput` (the default) and
+//! [`CallSequenceInput`](call_sequence::CallSequenceInput) are supported out of the box.
+//! Other types, such as `arbitrary`-derived Rust structs or typed Candid values, only need
+//! to implement `libafl`'s `Input` and `HasLen` traits and [`FuzzerInput`].
+
+pub mod call_sequence;
+
+use libafl::Error;
+use libafl::inputs::{BytesInput, Input};
+use libafl::mutators::{HavocScheduledMutator, Mutator, havoc_mutations, tokens_mutations};
+use libafl_bolts::tuples::Merge;
+use libafl_bolts::{HasLen, Named};
+use rand::{RngCore, SeedableRng};
+
+use crate::custom::mutator::bytes::BytesMutator;
+use crate::custom::mutator::candid::{CandidParserMutator, CandidTypeDefArgs};
+use crate::orchestrator::FuzzerStdState;
+
+/// An input type that can be fuzzed by a [`FuzzerOrchestrator`](crate::orchestrator::FuzzerOrchestrator).
+///
+/// Inputs must implement `HasLen`, which the corpus scheduler uses to favor short inputs.
+pub trait FuzzerInput: Input + HasLen + 'static {
+    /// The mutator used by the power mutational stage of the fuzzing loop.
+    type Mutator: Mutator<Self, FuzzerStdState<Self>> + Named;
+
+    /// Creates the mutator used when the orchestrator does not override
+    /// [`FuzzerOrchestrator::mutator`](crate::orchestrator::FuzzerOrchestrator::mutator).
+    ///
+    /// `candid_args` is the result of
+    /// [`FuzzerOrchestrator::get_candid_args`](crate::orchestrator::FuzzerOrchestrator::get_candid_args).
+    fn default_mutator(candid_args: Option<CandidTypeDefArgs>) -> Self::Mutator;
+
+    /// Returns the input to start from when the seed corpus directory is empty.
+    ///
+    /// The input is added to the corpus even if it does not reach new coverage. It must be
+    /// derived from `seed` only, so that a campaign with a fixed RNG seed is reproducible.
+    fn initial_input(mutator: &Self::Mutator, seed: u64) -> Self;
+
+    /// Serializes the input. This is the format of the files in the seed corpus directory
+    /// and of the inputs saved by the fuzzer.
+    ///
+    /// The default implementation uses `postcard`, the format `libafl` uses for inputs
+    /// in the on-disk corpus.
+    fn to_bytes(&self) -> Vec<u8> {
+        postcard::to_allocvec(self).expect("Failed to serialize the input")
+    }
+
+    /// Deserializes an input serialized by [`FuzzerInput::to_bytes`].
+    fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        postcard::from_bytes(bytes).map_err(|e| Error::serialize(e.to_string()))
+    }
+
+    /// Returns the byte buffers of the input that the
+    /// [`InputToStateMutator`](crate::custom::mutator::i2s::InputToStateMutator) may patch
+    /// with the operands of traced comparisons.
+    ///
+    /// The default implementation returns no buffers, which disables the input-to-state stage
+    /// for the input type.
+    fn bytes_mut(&mut self) -> Vec<&mut [u8]> {
+        vec![]
+    }
+}
+
+impl FuzzerInput for BytesInput {
+    type Mutator = BytesMutator;
+
+    /// Havoc and token mutations, combined with the [`CandidParserMutator`] if `candid_args`
+    /// is set.
+    fn default_mutator(candid_args: Option<CandidTypeDefArgs>) -> Self::Mutator {
+        BytesMutator::new(
+            HavocScheduledMutator::new(havoc_mutations().merge(tokens_mutations())),
+            candid_args.map(|args| CandidParserMutator::new(Some(args))),
+        )
+    }
+
+    /// A random input of 1 to 1024 bytes.
+    fn initial_input(_mutator: &Self::Mutator, seed: u64) -> Self {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+        let len = (rng.next_u32() % 1024 + 1) as usize;
+        let mut buf = vec![0u8; len];
+        rng.fill_bytes(&mut buf);
+        BytesInput::new(buf)
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        self.as_ref().to_vec()
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        Ok(BytesInput::new(bytes.to_vec()))
+    }
+
+    fn bytes_mut(&mut self) -> Vec<&mut [u8]> {
+        vec![self.as_mut()]
+    }
+}
@@ -1,9 +1,11 @@
 //! Custom libafl components for canister fuzzing.
 //!
 //! - [`observer`]: Coverage map and instruction count observers.
-//! - [`feedback`]: Instruction count maximization and OOM detection feedback.
-//! - [`mutator`]: Candid-aware input mutation.
+//! - [`feedback`]: Instruction count maximization, OOM detection and crash triage feedback.
+//! - [`mutator`]: Candid-aware input mutation and call sequence mutation.
+//! - [`input`]: Structured inputs, such as sequences of canister calls.
 
 pub mod feedback;
+pub mod input;
 pub mod mutator;
 pub mod observer;
@@ -0,0 +1,78 @@
+//! The default mutator for `BytesInput`.
+//!
+//! [`BytesMutator`] applies `libafl`'s havoc and token mutations and, if a Candid definition is
+//! configured, alternates them with the structure-aware [`CandidParserMutator`]. The token
+//! mutations use the dictionary extracted from the coverage canisters.
+
+use libafl::{
+    Error,
+    corpus::CorpusId,
+    inputs::BytesInput,
+    mutators::{
+        HavocMutationsType, HavocScheduledMutator, MutationResult, Mutator, TokenInsert,
+        TokenReplace,
+    },
+    state::HasRand,
+};
+use libafl_bolts::rands::Rand;
+use libafl_bolts::tuples::tuple_list_type;
+use libafl_bolts::{Named, merge_tuple_list_type};
+use std::borrow::Cow;
+
+use super::candid::CandidParserMutator;
+use crate::orchestrator::FuzzerStdState;
+
+type State = FuzzerStdState<BytesInput>;
+
+/// The havoc mutations followed by the token mutations, as returned by
+/// `havoc_mutations().merge(tokens_mutations())`.
+pub type BytesMutationsType = merge_tuple_list_type!(
+    HavocMutationsType,
+    tuple_list_type!(TokenInsert, TokenReplace)
+);
+
+/// A mutator for `BytesInput` that picks havoc or Candid-aware mutation with equal chance.
+pub struct BytesMutator {
+    havoc: HavocScheduledMutator<BytesMutationsType>,
+    candid: Option<CandidParserMutator<State>>,
+}
+
+impl BytesMutator {
+    /// Creates a new `BytesMutator`. If `candid` is `None`, only havoc mutations are applied.
+    pub fn new(
+        havoc: HavocScheduledMutator<BytesMutationsType>,
+        candid: Option<CandidParserMutator<State>>,
+    ) -> Self {
+        Self { havoc, candid }
+    }
+}
+
+impl Named for BytesMutator {
+    fn name(&self) -> &Cow<'static, str> {
+        &Cow::Borrowed("BytesMutator")
+    }
+}
+
+impl Mutator<BytesInput, State> for BytesMutator {
+    fn mutate(
+        &mut self,
+        state: &mut State,
+        input: &mut BytesInput,
+    ) -> Result<MutationResult, Error> {
+        match self.candid.as_mut() {
+            Some(candid) if state.rand_mut().coinflip(0.5) => candid.mutate(state, input),
+            _ => self.havoc.mutate(state, input),
+        }
+    }
+
+    fn post_exec(
+        &mut self,
+        state: &mut State,
+        new_corpus_id: Option<CorpusId>,
+    ) -> Result<(), Error> {
+        if let Some(candid) = self.candid.as_mut() {
+            candid.post_exec(state, new_corpus_id)?;
+        }
+        self.havoc.post_exec(state, new_corpus_id)
+    }
+}
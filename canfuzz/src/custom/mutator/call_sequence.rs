@@ -0,0 +1,461 @@
+//! A `libAFL` mutator for [`CallSequenceInput`]s.
+//!
+//! The mutator works on two levels:
+//! 1.  **Sequence mutations**: Inserts, duplicates, deletes, reorders and splices whole
+//!     [`CallStep`]s, changing which methods are called and in which order.
+//! 2.  **Step mutations**: Mutates the Candid arguments of one step with the same `.did`-aware
+//!     value mutation as the [`CandidParserMutator`](super::candid::CandidParserMutator),
+//!     including the dictionary tokens, or changes its caller or the time advanced after it.
+
+use candid::types::Type;
+use candid::{Principal, TypeEnv};
+use candid_parser::typing::pretty_check_file;
+use libafl::{
+    Error, HasMetadata,
+    corpus::Corpus,
+    mutators::{MutationResult, Mutator},
+    random_corpus_id_with_disabled,
+    state::{HasCorpus, HasRand},
+};
+use libafl_bolts::rands::Rand;
+use libafl_bolts::{HasLen, Named};
+use rand::{Rng, SeedableRng};
+use std::borrow::Cow;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use super::candid::{dictionary, mutate_args, random_args};
+use crate::custom::input::call_sequence::{CallSequenceInput, CallStep};
+
+/// The time advances a mutated step can get, from none to one day.
+const TIME_ADVANCES: [Option<Duration>; 5] = [
+    None,
+    Some(Duration::from_secs(1)),
+    Some(Duration::from_secs(60)),
+    Some(Duration::from_secs(60 * 60)),
+    Some(Duration::from_secs(24 * 60 * 60)),
+];
+
+/// Configuration for the `CallSequenceMutator`.
+pub struct CallSequenceArgs {
+    /// The path to the Candid definition (`.did`) file of the target canister.
+    pub definition: PathBuf,
+    /// The methods that can be called. If empty, all methods of the service are used.
+    pub methods: Vec<String>,
+    /// The principals the calls can be made from. If empty, all calls are anonymous.
+    pub callers: Vec<Principal>,
+    /// The maximum number of steps in a sequence.
+    pub max_steps: usize,
+}
+
+/// A callable method of the target canister with its argument types.
+struct CallMethod {
+    name: String,
+    arg_types: Vec<Type>,
+}
+
+/// A `libAFL` mutator that mutates a [`CallSequenceInput`] based on a `.did` definition.
+pub struct CallSequenceMutator {
+    env: TypeEnv,
+    methods: Vec<CallMethod>,
+    callers: Vec<Principal>,
+    max_steps: usize,
+}
+
+impl CallSequenceMutator {
+    /// Creates a new `CallSequenceMutator`.
+    ///
+    /// It parses the `.did` file and collects the argument types of the configured methods.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the `.did` file cannot be parsed, has no service, or does not define one
+    /// of the configured methods.
+    pub fn new(args: CallSequenceArgs) -> Self {
+        let (env, actor, _) =
+            pretty_check_file(&args.definition).expect("Unable to parse did file");
+        let actor = actor.expect("The did file does not define a service");
+        Self::from_service(env, &actor, args)
+    }
+
+    fn from_service(env: TypeEnv, actor: &Type, args: CallSequenceArgs) -> Self {
+        let names: Vec<String> = if args.methods.is_empty() {
+            env.as_service(actor)
+                .expect("Unable to read the service methods")
+                .iter()
+                .map(|(name, _)| name.clone())
+                .collect()
+        } else {
+            args.methods
+        };
+        assert!(!names.is_empty(), "At least one method is required");
+        let methods = names
+            .into_iter()
+            .map(|name| {
+                let func = env
+                    .get_method(actor, &name)
+                    .unwrap_or_else(|_| panic!("Method {name} not found in the did file"));
+                CallMethod {
+                    arg_types: func.args.to_vec(),
+                    name,
+                }
+            })
+            .collect();
+        let callers = if args.callers.is_empty() {
+            vec![Principal::anonymous()]
+        } else {
+            args.callers
+        };
+
+        Self {
+            env,
+            methods,
+            callers,
+            max_steps: args.max_steps.max(1),
+        }
+    }
+
+    /// Generates a sequence with a single random step, derived from `seed`, to start fuzzing
+    /// from when there is no seed corpus.
+    pub fn random_input(&self, seed: u64) -> CallSequenceInput {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+        CallSequenceInput::new(self.random_step(&mut rng).into_iter().collect())
+    }
+
+    /// Generates a new step calling a random method with random arguments.
+    fn random_step<R: Rng>(&self, rng: &mut R) -> Option<CallStep> {
+        let method = &self.methods[rng.random_range(0..self.methods.len())];
+        let args = random_args(&self.env, &method.arg_types, Some(&method.name), rng)?;
+        Some(CallStep {
+            method: method.name.clone(),
+            caller: self.callers[rng.random_range(0..self.callers.len())],
+            args,
+            advance_time: TIME_ADVANCES[rng.random_range(0..TIME_ADVANCES.len())],
+        })
+    }
+
+    /// Inserts a new random step at a random position.
+    fn insert_step<R: Rng>(&self, input: &mut CallSequenceInput, rng: &mut R) -> MutationResult {
+        if input.len() >= self.max_steps {
+            return MutationResult::Skipped;
+        }
+        let Some(step) = self.random_step(rng) else {
+            return MutationResult::Skipped;
+        };
+        let index = rng.random_range(0..=input.len());
+        input.steps.insert(index, step);
+        MutationResult::Mutated
+    }
+
+    /// Duplicates a random step, inserting the copy right after it.
+    fn duplicate_step<R: Rng>(&self, input: &mut CallSequenceInput, rng: &mut R) -> MutationResult {
+        if input.is_empty() || input.len() >= self.max_steps {
+            return MutationResult::Skipped;
+        }
+        let index = rng.random_range(0..input.len());
+        input.steps.insert(index + 1, input.steps[index].clone());
+        MutationResult::Mutated
+    }
+
+    /// Removes a random step, keeping at least one.
+    fn delete_step<R: Rng>(&self, input: &mut CallSequenceInput, rng: &mut R) -> MutationResult {
+        if input.len() < 2 {
+            return MutationResult::Skipped;
+        }
+        let index = rng.random_range(0..input.len());
+        input.steps.remove(index);
+        MutationResult::Mutated
+    }
+
+    /// Swaps two random steps.
+    fn swap_steps<R: Rng>(&self, input: &mut CallSequenceInput, rng: &mut R) -> MutationResult {
+        if input.len() < 2 {
+            return MutationResult::Skipped;
+        }
+        let first = rng.random_range(0..input.len());
+        let second = rng.random_range(0..input.len());
+        if first == second {
+            return MutationResult::Skipped;
+        }
+        input.steps.swap(first, second);
+        MutationResult::Mutated
+    }
+
+    /// Replaces the tail of `input` with the tail of `other`, at random split points.
+    fn splice<R: Rng>(
+        &self,
+        input: &mut CallSequenceInput,
+        other: &CallSequenceInput,
+        rng: &mut R,
+    ) -> MutationResult {
+        if other.is_empty() {
+            return MutationResult::Skipped;
+        }
+        let split = rng.random_range(0..=input.len());
+        let other_split = rng.random_range(0..other.len());
+        input.steps.truncate(split);
+        input
+            .steps
+            .extend(other.steps[other_split..].iter().cloned());
+        input.steps.truncate(self.max_steps);
+        MutationResult::Mutated
+    }
+
+    /// Mutates the arguments of a random step with the dictionary `tokens`, or regenerates
+    /// them if they do not match the method's argument types.
+    fn mutate_step_args<R: Rng>(
+        &self,
+        input: &mut CallSequenceInput,
+        tokens: &[Vec<u8>],
+        rng: &mut R,
+    ) -> MutationResult {
+        if input.is_empty() {
+            return MutationResult::Skipped;
+        }
+        let index = rng.random_range(0..input.len());
+        let step = &mut input.steps[index];
+        let Some(method) = self.methods.iter().find(|m| m.name == step.method) else {
+            return MutationResult::Skipped;
+        };
+        let new_args = mutate_args(&step.args, &self.env, &method.arg_types, tokens, rng)
+            .or_else(|| random_args(&self.env, &method.arg_types, Some(&method.name), rng));
+        match new_args {
+            Some(args) => {
+                step.args = args;
+                MutationResult::Mutated
+            }
+            None => MutationResult::Skipped,
+        }
+    }
+
+    /// Changes the caller of a random step.
+    fn mutate_step_caller<R: Rng>(
+        &self,
+        input: &mut CallSequenceInput,
+        rng: &mut R,
+    ) -> MutationResult {
+        if input.is_empty() || self.callers.len() < 2 {
+            return MutationResult::Skipped;
+        }
+        let index = rng.random_range(0..input.len());
+        input.steps[index].caller = self.callers[rng.random_range(0..self.callers.len())];
+        MutationResult::Mutated
+    }
+
+    /// Changes the time advanced after a random step.
+    fn mutate_step_time<R: Rng>(
+        &self,
+        input: &mut CallSequenceInput,
+        rng: &mut R,
+    ) -> MutationResult {
+        if input.is_empty() {
+            return MutationResult::Skipped;
+        }
+        let index = rng.random_range(0..input.len());
+        input.steps[index].advance_time = TIME_ADVANCES[rng.random_range(0..TIME_ADVANCES.len())];
+        MutationResult::Mutated
+    }
+}
+
+impl Named for CallSequenceMutator {
+    fn name(&self) -> &Cow<'static, str> {
+        &Cow::Borrowed("CallSequenceMutator")
+    }
+}
+
+impl<S> Mutator<CallSequenceInput, S> for CallSequenceMutator
+where
+    S: HasRand + HasCorpus<CallSequenceInput> + HasMetadata,
+{
+    fn mutate(
+        &mut self,
+        state: &mut S,
+        input: &mut CallSequenceInput,
+    ) -> Result<MutationResult, Error> {
+        let state_u64 = state.rand_mut().next();
+        let mut rng = rand::rngs::StdRng::seed_from_u64(state_u64);
+
+        // An empty sequence can only grow.
+        if input.is_empty() {
+            return Ok(self.insert_step(input, &mut rng));
+        }
+
+        let result = match rng.random_range(0..10) {
+            0 => self.insert_step(input, &mut rng),
+            1 => self.duplicate_step(input, &mut rng),
+            2 => self.delete_step(input, &mut rng),
+            3 => self.swap_steps(input, &mut rng),
+            4 => {
+                if state.corpus().count_all() == 0 {
+                    return Ok(MutationResult::Skipped);
+                }
+                let id = random_corpus_id_with_disabled!(state.corpus(), state.rand_mut());
+                // We don't want to splice the testcase with itself.
+                if state.corpus().current().is_some_and(|cur| cur == id) {
+                    return Ok(MutationResult::Skipped);
+                }
+                let mut other_testcase = state.corpus().get_from_all(id)?.borrow_mut();
+                let other = other_testcase.load_input(state.corpus())?.clone();
+                self.splice(input, &other, &mut rng)
+            }
+            5 => self.mutate_step_caller(input, &mut rng),
+            6 => self.mutate_step_time(input, &mut rng),
+            _ => self.mutate_step_args(input, dictionary(state.metadata_map()), &mut rng),
+        };
+        Ok(result)
+    }
+
+    fn post_exec(
+        &mut self,
+        _state: &mut S,
+        _new_corpus_id: Option<libafl::corpus::CorpusId>,
+    ) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use candid::IDLArgs;
+    use candid_parser::utils::CandidSource;
+
+    const STATIC_SEED: u64 = 7355608;
+    const SERVICE: &str = r#"
+        type Account = record { owner : principal; amount : nat64 };
+        service : {
+            deposit : (Account) -> ();
+            withdraw : (nat64) -> (bool);
+            balance : () -> (nat64) query;
+        }
+    "#;
+
+    fn mutator(methods: Vec<String>, max_steps: usize) -> CallSequenceMutator {
+        let (env, actor) = CandidSource::Text(SERVICE).load().unwrap();
+        CallSequenceMutator::from_service(
+            env,
+            &actor.unwrap(),
+            CallSequenceArgs {
+                definition: PathBuf::new(),
+                methods,
+                callers: vec![],
+                max_steps,
+            },
+        )
+    }
+
+    fn step(method: &str) -> CallStep {
+        CallStep {
+            method: method.to_string(),
+            caller: Principal::anonymous(),
+            args: IDLArgs::new(&[]).to_bytes().unwrap(),
+            advance_time: None,
+        }
+    }
+
+    #[test]
+    fn test_all_methods_by_default() {
+        let mutator = mutator(vec![], 8);
+        let names: Vec<&str> = mutator.methods.iter().map(|m| m.name.as_str()).collect();
+        assert_eq!(names, vec!["balance", "deposit", "withdraw"]);
+        assert_eq!(mutator.callers, vec![Principal::anonymous()]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Method transfer not found in the did file")]
+    fn test_unknown_method() {
+        mutator(vec!["transfer".to_string()], 8);
+    }
+
+    #[test]
+    fn test_random_input_is_seeded() {
+        let mutator = mutator(vec![], 8);
+        let input = mutator.random_input(STATIC_SEED);
+        assert_eq!(input.steps.len(), 1);
+        assert_eq!(input, mutator.random_input(STATIC_SEED));
+    }
+
+    #[test]
+    fn test_insert_step() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(STATIC_SEED);
+        let mutator = mutator(vec!["withdraw".to_string()], 2);
+        let mut input = CallSequenceInput::default();
+
+        assert_eq!(
+            mutator.insert_step(&mut input, &mut rng),
+            MutationResult::Mutated
+        );
+        assert_eq!(
+            mutator.insert_step(&mut input, &mut rng),
+            MutationResult::Mutated
+        );
+        // The sequence is already at `max_steps`.
+        assert_eq!(
+            mutator.insert_step(&mut input, &mut rng),
+            MutationResult::Skipped
+        );
+
+        for step in &input.steps {
+            assert_eq!(step.method, "withdraw");
+            let args = IDLArgs::from_bytes(&step.args).unwrap();
+            assert_eq!(args.args.len(), 1);
+        }
+    }
+
+    #[test]
+    fn test_delete_and_swap_steps() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(STATIC_SEED);
+        let mutator = mutator(vec![], 8);
+        let mut input = CallSequenceInput::new(vec![step("deposit"), step("withdraw")]);
+
+        while mutator.swap_steps(&mut input, &mut rng) == MutationResult::Skipped {}
+        assert_eq!(input.steps, vec![step("withdraw"), step("deposit")]);
+
+        assert_eq!(
+            mutator.delete_step(&mut input, &mut rng),
+            MutationResult::Mutated
+        );
+        assert_eq!(input.len(), 1);
+        // The last step is never removed.
+        assert_eq!(
+            mutator.delete_step(&mut input, &mut rng),
+            MutationResult::Skipped
+        );
+    }
+
+    #[test]
+    fn test_splice_respects_max_steps() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(STATIC_SEED);
+        let mutator = mutator(vec![], 3);
+        let mut input = CallSequenceInput::new(vec![step("deposit"); 3]);
+        let other = CallSequenceInput::new(vec![step("withdraw"); 3]);
+
+        assert_eq!(
+            mutator.splice(&mut input, &other, &mut rng),
+            MutationResult::Mutated
+        );
+        assert!(input.len() <= 3);
+        let split = input
+            .steps
+            .iter()
+            .position(|s| s.method == "withdraw")
+            .unwrap_or(input.len());
+        assert!(input.steps[..split].iter().all(|s| s.method == "deposit"));
+        assert!(input.steps[split..].iter().all(|s| s.method == "withdraw"));
+    }
+
+    #[test]
+    fn test_mutate_step_args_regenerates_invalid_args() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(STATIC_SEED);
+        let mutator = mutator(vec![], 8);
+        // `deposit` expects a record, but the step has no arguments.
+        let mut input = CallSequenceInput::new(vec![step("deposit")]);
+
+        assert_eq!(
+            mutator.mutate_step_args(&mut input, &[], &mut rng),
+            MutationResult::Mutated
+        );
+        let args = IDLArgs::from_bytes(&input.steps[0].args).unwrap();
+        assert_eq!(args.args.len(), 1);
+    }
+}
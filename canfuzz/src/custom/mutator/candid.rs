@@ -6,6 +6,9 @@
 //! 1.  **Random Generation**: Creates entirely new, valid Candid arguments from scratch based on the `.did` file definition.
 //! 2.  **Structure-Aware Mutation**: Decodes existing Candid data, intelligently mutates one of the values within the structure
 //!     (e.g., changing a number, modifying a string, altering a vector), and then re-encodes it.
+//!
+//! Text and blob values are also mutated with the tokens of the `Tokens` metadata of the state,
+//! i.e. the dictionary extracted from the coverage canisters.
 
 use candid::types::{Type, TypeInner};
 use candid::{IDLArgs, IDLValue, Int, Nat, Principal, TypeEnv};
@@ -15,13 +18,14 @@ use core::marker::PhantomData;
 use libafl::inputs::HasMutatorBytes;
 use libafl::inputs::ResizableMutator;
 use libafl::{
-    Error,
+    Error, HasMetadata,
     inputs::Input,
-    mutators::{MutationResult, Mutator},
+    mutators::{MutationResult, Mutator, Tokens},
     state::{HasCorpus, HasRand},
 };
 use libafl_bolts::Named;
 use libafl_bolts::rands::Rand;
+use libafl_bolts::serdeany::SerdeAnyMap;
 use num_bigint::{BigInt, BigUint};
 use num_traits::{PrimInt, WrappingAdd, WrappingMul, WrappingSub};
 use rand::distr::{Distribution, StandardUniform};
@@ -67,16 +71,12 @@ impl<S> CandidParserMutator<S> {
             };
         }
         let candid_args = candid_args.unwrap();
-        let (env, actor, _) =
-            pretty_check_file(&candid_args.definition).expect("Unable to parse did file");
-        let actor = actor.unwrap();
-        let func = env.get_method(&actor, &candid_args.method).unwrap();
-        let types = &func.args;
+        let (env, arg_types) = method_arg_types(&candid_args);
 
         Self {
             is_enabled: true,
-            env: env.clone(),
-            arg_types: types.to_vec(),
+            env,
+            arg_types,
             method_name: Some(candid_args.method.to_string()),
             phantom: PhantomData,
         }
@@ -84,8 +84,6 @@ impl<S> CandidParserMutator<S> {
 
     /// Generates a completely new, valid Candid `IDLArgs` value from scratch based on the
     /// method's type definition and replaces the input with its byte representation.
-    ///
-    /// This uses the `candid-parser`'s random generation capabilities.
     fn mutate_random_generation<I, R>(
         &self,
         input: &mut I,
@@ -95,24 +93,10 @@ impl<S> CandidParserMutator<S> {
         I: Input + HasMutatorBytes + ResizableMutator<u8>,
         R: Rng,
     {
-        let config = Configs::from_str("").unwrap();
-
-        let scope = self.method_name.as_ref().map(|method| Scope {
-            position: Some(ScopePos::Arg),
-            method,
-        });
-
-        let seed = rng.random::<u64>().to_le_bytes().to_vec();
-
-        let new_args =
-            match candid_parser::random::any(&seed, config, &self.env, &self.arg_types, &scope) {
-                Ok(args) => args,
-                Err(_) => return Ok(MutationResult::Skipped), // Schema mismatch or gen failure
-            };
-
-        let new_bytes = match new_args.to_bytes() {
-            Ok(b) => b,
-            Err(_) => return Ok(MutationResult::Skipped),
+        let Some(new_bytes) =
+            random_args(&self.env, &self.arg_types, self.method_name.as_deref(), rng)
+        else {
+            return Ok(MutationResult::Skipped);
         };
 
         input.resize(0, 0);
@@ -120,57 +104,25 @@ impl<S> CandidParserMutator<S> {
         Ok(MutationResult::Mutated)
     }
 
-    /// Mutates an existing Candid-encoded input.
-    ///
-    /// It first decodes the input bytes into `IDLArgs`. It then performs a subtyping
-    /// check to ensure the decoded types are compatible with the expected method argument types.
-    /// If they are, it recursively traverses the `IDLValue` structure and applies a
-    /// type-aware mutation to one of the values. Finally, it re-encodes the mutated `IDLArgs`.
+    /// Mutates an existing Candid-encoded input with [`mutate_args`].
     fn mutate_existing_bytes<I, R>(
         &self,
         input: &mut I,
+        tokens: &[Vec<u8>],
         rng: &mut R,
     ) -> Result<MutationResult, Error>
     where
         I: Input + HasMutatorBytes + ResizableMutator<u8>,
         R: Rng,
     {
-        let mut args = match IDLArgs::from_bytes(input.mutator_bytes()) {
-            Ok(args) => {
-                // Subtyping check
-                let mut gamma = std::collections::HashSet::new();
-                let decoded_types = args.get_types();
-                if self.arg_types.len() == decoded_types.len()
-                    && self
-                        .arg_types
-                        .iter()
-                        .zip(decoded_types.iter())
-                        .all(|(t1, t2)| {
-                            candid::types::subtype::subtype(&mut gamma, &self.env, t2, t1).is_ok()
-                        })
-                {
-                    args
-                } else {
-                    return Ok(MutationResult::Skipped);
-                }
-            }
-            Err(_) => return Ok(MutationResult::Skipped),
-        };
-
-        if !args.args.is_empty() {
-            let index = rng.random_range(0..args.args.len());
-            mutate_value(
-                &mut args.args[index],
-                &self.arg_types[index],
-                &self.env,
-                rng,
-                0,
-            );
-        }
-
-        let new_bytes = match args.to_bytes() {
-            Ok(bytes) => bytes,
-            Err(_) => return Ok(MutationResult::Skipped),
+        let Some(new_bytes) = mutate_args(
+            input.mutator_bytes(),
+            &self.env,
+            &self.arg_types,
+            tokens,
+            rng,
+        ) else {
+            return Ok(MutationResult::Skipped);
         };
 
         input.resize(0, 0);
@@ -179,15 +131,104 @@ impl<S> CandidParserMutator<S> {
     }
 }
 
+/// Parses the `.did` file of `candid_args` and returns its type environment and the
+/// argument types of the method.
+///
+/// # Panics
+///
+/// Panics if the file cannot be parsed, has no service or the service has no such method.
+pub(crate) fn method_arg_types(candid_args: &CandidTypeDefArgs) -> (TypeEnv, Vec<Type>) {
+    let (env, actor, _) =
+        pretty_check_file(&candid_args.definition).expect("Unable to parse did file");
+    let actor = actor.unwrap();
+    let func = env.get_method(&actor, &candid_args.method).unwrap();
+    let arg_types = func.args.to_vec();
+    (env, arg_types)
+}
+
+/// Generates new, valid Candid-encoded arguments from scratch for a method with
+/// the given argument types.
+///
+/// This uses the `candid-parser`'s random generation capabilities. Returns `None` if the
+/// generation or the encoding fails.
+pub(crate) fn random_args<R: Rng>(
+    env: &TypeEnv,
+    arg_types: &[Type],
+    method: Option<&str>,
+    rng: &mut R,
+) -> Option<Vec<u8>> {
+    let config = Configs::from_str("").unwrap();
+
+    let scope = method.map(|method| Scope {
+        position: Some(ScopePos::Arg),
+        method,
+    });
+
+    let seed = rng.random::<u64>().to_le_bytes().to_vec();
+
+    // Schema mismatch or gen failure
+    let new_args = candid_parser::random::any(&seed, config, env, arg_types, &scope).ok()?;
+    new_args.to_bytes().ok()
+}
+
+/// Mutates existing Candid-encoded arguments of a method with the given argument types.
+///
+/// It first decodes the bytes into `IDLArgs`. It then performs a subtyping check to
+/// ensure the decoded types are compatible with the expected method argument types.
+/// If they are, it recursively traverses the `IDLValue` structure and applies a
+/// type-aware mutation to one of the values, using the dictionary `tokens` for text and blob
+/// values. Finally, it re-encodes the mutated `IDLArgs`.
+/// Returns `None` if the bytes cannot be decoded or do not match the argument types.
+pub(crate) fn mutate_args<R: Rng>(
+    bytes: &[u8],
+    env: &TypeEnv,
+    arg_types: &[Type],
+    tokens: &[Vec<u8>],
+    rng: &mut R,
+) -> Option<Vec<u8>> {
+    let mut args = IDLArgs::from_bytes(bytes).ok()?;
+
+    // Subtyping check
+    let mut gamma = std::collections::HashSet::new();
+    let decoded_types = args.get_types();
+    if arg_types.len() != decoded_types.len()
+        || !arg_types
+            .iter()
+            .zip(decoded_types.iter())
+            .all(|(t1, t2)| candid::types::subtype::subtype(&mut gamma, env, t2, t1).is_ok())
+    {
+        return None;
+    }
+
+    if !args.args.is_empty() {
+        let index = rng.random_range(0..args.args.len());
+        mutate_value(
+            &mut args.args[index],
+            &arg_types[index],
+            env,
+            tokens,
+            rng,
+            0,
+        );
+    }
+
+    args.to_bytes().ok()
+}
+
 impl<S> Named for CandidParserMutator<S> {
     fn name(&self) -> &Cow<'static, str> {
         &Cow::Borrowed("CandidParserMutator")
     }
 }
 
+/// Returns the dictionary of the `Tokens` metadata of a state, or an empty slice if it has none.
+pub(crate) fn dictionary(metadata: &SerdeAnyMap) -> &[Vec<u8>] {
+    metadata.get::<Tokens>().map_or(&[], Tokens::tokens)
+}
+
 impl<S, I> Mutator<I, S> for CandidParserMutator<S>
 where
-    S: HasRand + HasCorpus<I>,
+    S: HasRand + HasCorpus<I> + HasMetadata,
     I: Input + HasMutatorBytes + ResizableMutator<u8>,
 {
     fn mutate(&mut self, state: &mut S, input: &mut I) -> Result<MutationResult, Error> {
@@ -206,7 +247,7 @@ where
             return self.mutate_random_generation(input, &mut rng);
         }
 
-        self.mutate_existing_bytes(input, &mut rng)
+        self.mutate_existing_bytes(input, dictionary(state.metadata_map()), &mut rng)
     }
 
     fn post_exec(
@@ -222,7 +263,14 @@ where
 /// and applies a type-specific mutation.
 ///
 /// A depth limit is used to prevent infinite recursion on recursive types.
-fn mutate_value<R: Rng>(val: &mut IDLValue, ty: &Type, env: &TypeEnv, rng: &mut R, depth: usize) {
+fn mutate_value<R: Rng>(
+    val: &mut IDLValue,
+    ty: &Type,
+    env: &TypeEnv,
+    tokens: &[Vec<u8>],
+    rng: &mut R,
+    depth: usize,
+) {
     if depth > 20 {
         return;
     }
@@ -233,7 +281,7 @@ fn mutate_value<R: Rng>(val: &mut IDLValue, ty: &Type, env: &TypeEnv, rng: &mut
         IDLValue::None => {}
         IDLValue::Reserved => {}
 
-        IDLValue::Text(s) => mutate_text(s, rng),
+        IDLValue::Text(s) => mutate_text_with_tokens(s, tokens, rng),
         IDLValue::Number(s) => mutate_text(s, rng),
 
         IDLValue::Int(i) => mutate_int(i, rng),
@@ -253,11 +301,11 @@ fn mutate_value<R: Rng>(val: &mut IDLValue, ty: &Type, env: &TypeEnv, rng: &mut
 
         IDLValue::Vec(v) => {
             if let Ok(TypeInner::Vec(item_ty)) = env.trace_type(ty).map(|t| t.as_ref().clone()) {
-                mutate_vec(v, &item_ty, env, rng, depth);
+                mutate_vec(v, &item_ty, env, tokens, rng, depth);
             }
         }
         IDLValue::Principal(p) => mutate_principal(p, rng),
-        IDLValue::Blob(items) => mutate_blob(items, rng),
+        IDLValue::Blob(items) => mutate_blob_with_tokens(items, tokens, rng),
 
         IDLValue::Record(fields) => {
             if let Ok(TypeInner::Record(field_types)) =
@@ -281,6 +329,7 @@ fn mutate_value<R: Rng>(val: &mut IDLValue, ty: &Type, env: &TypeEnv, rng: &mut
                             &mut fields[field_idx_to_mutate].val,
                             &field_ty.ty,
                             env,
+                            tokens,
                             rng,
                             depth + 1,
                         );
@@ -316,12 +365,12 @@ fn mutate_value<R: Rng>(val: &mut IDLValue, ty: &Type, env: &TypeEnv, rng: &mut
                     .iter()
                     .find(|f| f.id == Rc::new(v.0.id.clone()))
                 {
-                    mutate_value(&mut v.0.val, &field_ty.ty, env, rng, depth + 1);
+                    mutate_value(&mut v.0.val, &field_ty.ty, env, tokens, rng, depth + 1);
                 }
             }
         }
 
-        IDLValue::Opt(o) => mutate_opt(o, ty, env, rng, depth),
+        IDLValue::Opt(o) => mutate_opt(o, ty, env, tokens, rng, depth),
         IDLValue::Service(_principal) => {
             unimplemented!("Mutating service defintion is umimplemented!")
         }
@@ -341,10 +390,7 @@ fn mutate_text<R: Rng>(s: &mut String, rng: &mut R) {
 
     match rng.random_range(0..10) {
         0..5 => {
-            // Pick a random char-aligned index for insertion
-            let char_count = s.chars().count();
-            let char_idx = rng.random_range(0..=char_count);
-            let byte_idx = s.char_indices().nth(char_idx).map_or(s.len(), |(i, _)| i);
+            let byte_idx = random_char_boundary(s, rng);
             let naughty_index = rng.random_range(0..naughty_strings::BLNS.len());
             s.insert_str(byte_idx, naughty_strings::BLNS[naughty_index]);
         }
@@ -363,6 +409,31 @@ fn mutate_text<R: Rng>(s: &mut String, rng: &mut R) {
     }
 }
 
+/// Picks a random char-aligned index of `s` for insertion.
+fn random_char_boundary<R: Rng>(s: &str, rng: &mut R) -> usize {
+    let char_count = s.chars().count();
+    let char_idx = rng.random_range(0..=char_count);
+    s.char_indices().nth(char_idx).map_or(s.len(), |(i, _)| i)
+}
+
+/// Mutates a `String` with [`mutate_text`] or, one time in four, replaces it with a dictionary
+/// token or inserts one. Only tokens that are valid UTF-8 are used.
+fn mutate_text_with_tokens<R: Rng>(s: &mut String, tokens: &[Vec<u8>], rng: &mut R) {
+    if !tokens.is_empty() && rng.random_bool(0.25) {
+        let token = &tokens[rng.random_range(0..tokens.len())];
+        if let Ok(token) = std::str::from_utf8(token) {
+            if rng.random_bool(0.5) {
+                *s = token.to_string();
+            } else {
+                let byte_idx = random_char_boundary(s, rng);
+                s.insert_str(byte_idx, token);
+            }
+            return;
+        }
+    }
+    mutate_text(s, rng);
+}
+
 /// Mutates an `Int` (arbitrary-precision integer) using various arithmetic strategies.
 fn mutate_int<R: Rng>(i: &mut Int, rng: &mut R) {
     let val = &i.0;
@@ -442,6 +513,7 @@ fn mutate_vec<R: Rng>(
     vec: &mut Vec<IDLValue>,
     item_ty: &Type,
     env: &TypeEnv,
+    tokens: &[Vec<u8>],
     rng: &mut R,
     depth: usize,
 ) {
@@ -471,11 +543,11 @@ fn mutate_vec<R: Rng>(
             let idx = rng.random_range(0..length);
             let val = vec[idx].clone();
             vec.push(val);
-            mutate_value(&mut vec[length], item_ty, env, rng, depth + 1);
+            mutate_value(&mut vec[length], item_ty, env, tokens, rng, depth + 1);
         }
         2 => {
             let idx = rng.random_range(0..vec.len());
-            mutate_value(&mut vec[idx], item_ty, env, rng, depth + 1);
+            mutate_value(&mut vec[idx], item_ty, env, tokens, rng, depth + 1);
         }
         _ => {}
     }
@@ -510,6 +582,27 @@ fn mutate_blob<R: Rng>(b: &mut Vec<u8>, rng: &mut R) {
     rng.fill_bytes(b);
 }
 
+/// Mutates a `blob` with [`mutate_blob`] or, one time in four, replaces it with a dictionary
+/// token, inserts one or overwrites part of it with one.
+fn mutate_blob_with_tokens<R: Rng>(b: &mut Vec<u8>, tokens: &[Vec<u8>], rng: &mut R) {
+    if tokens.is_empty() || !rng.random_bool(0.25) {
+        return mutate_blob(b, rng);
+    }
+    let token = &tokens[rng.random_range(0..tokens.len())];
+    match rng.random_range(0..3) {
+        0 => *b = token.clone(),
+        1 => {
+            let idx = rng.random_range(0..=b.len());
+            b.splice(idx..idx, token.iter().copied());
+        }
+        _ => {
+            let idx = rng.random_range(0..=b.len());
+            let end = (idx + token.len()).min(b.len());
+            b.splice(idx..end, token.iter().copied());
+        }
+    }
+}
+
 /// A generic mutator for primitive integer types (`i8`, `u16`, etc.).
 fn mutate_primitive<T, R>(val: T, rng: &mut R) -> T
 where
@@ -534,7 +627,14 @@ where
 /// 1. Change `Some(v)` to `None`.
 /// 2. Change `None` to `Some(v)` with a newly generated value.
 /// 3. Mutate the inner value of `Some(v)`.
-fn mutate_opt<R: Rng>(val: &mut IDLValue, ty: &Type, env: &TypeEnv, rng: &mut R, depth: usize) {
+fn mutate_opt<R: Rng>(
+    val: &mut IDLValue,
+    ty: &Type,
+    env: &TypeEnv,
+    tokens: &[Vec<u8>],
+    rng: &mut R,
+    depth: usize,
+) {
     if let Ok(TypeInner::Opt(inner_ty)) = env.trace_type(ty).map(|t| t.as_ref().clone()) {
         match rng.random_range(0..10) {
             // Some -> None
@@ -558,7 +658,7 @@ fn mutate_opt<R: Rng>(val: &mut IDLValue, ty: &Type, env: &TypeEnv, rng: &mut R,
             // Mutate inner value
             _ => {
                 if let IDLValue::Opt(inner_val) = val {
-                    mutate_value(inner_val, &inner_ty, env, rng, depth);
+                    mutate_value(inner_val, &inner_ty, env, tokens, rng, depth);
                 }
             }
         }
@@ -649,13 +749,43 @@ mod tests {
         assert_eq!(b, Vec::<u8>::new());
     }
 
+    #[test]
+    fn test_mutate_text_with_tokens() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(STATIC_SEED);
+        let tokens = vec![b"transfer".to_vec(), vec![0xff, 0xfe]];
+        let mut with_token = 0;
+        for _ in 0..100 {
+            let mut s = "hello".to_string();
+            mutate_text_with_tokens(&mut s, &tokens, &mut rng);
+            if s.contains("transfer") {
+                with_token += 1;
+            }
+        }
+        assert!((5..50).contains(&with_token), "{with_token}");
+    }
+
+    #[test]
+    fn test_mutate_blob_with_tokens() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(STATIC_SEED);
+        let tokens = vec![vec![206, 12]];
+        let mut with_token = 0;
+        for _ in 0..100 {
+            let mut b = vec![1, 2, 3, 4, 5];
+            mutate_blob_with_tokens(&mut b, &tokens, &mut rng);
+            if b.windows(2).any(|w| w == [206, 12]) {
+                with_token += 1;
+            }
+        }
+        assert!((5..50).contains(&with_token), "{with_token}");
+    }
+
     #[test]
     fn test_mutate_vec() {
         let mut rng = rand::rngs::StdRng::seed_from_u64(STATIC_SEED);
         let mut v = vec![IDLValue::Nat8(10), IDLValue::Nat8(20)];
         let item_ty = TypeInner::Nat8.into();
         let env = TypeEnv::new();
-        mutate_vec(&mut v, &item_ty, &env, &mut rng, 0);
+        mutate_vec(&mut v, &item_ty, &env, &[], &mut rng, 0);
         assert_eq!(v, vec![IDLValue::Nat8(20)]);
     }
 
@@ -665,7 +795,7 @@ mod tests {
         let mut opt_val = Box::new(IDLValue::Nat8(10));
         let ty = TypeInner::Opt(TypeInner::Nat8.into()).into();
         let env = TypeEnv::new();
-        mutate_opt(&mut opt_val, &ty, &env, &mut rng, 0);
+        mutate_opt(&mut opt_val, &ty, &env, &[], &mut rng, 0);
         assert_eq!(*opt_val, IDLValue::None);
     }
 
@@ -695,7 +825,7 @@ mod tests {
         .into();
         let env = TypeEnv::new();
 
-        mutate_value(&mut val, &ty, &env, &mut rng, 0);
+        mutate_value(&mut val, &ty, &env, &[], &mut rng, 0);
 
         let expected = IDLValue::Record(vec![
             candid::types::value::IDLField {
@@ -733,7 +863,7 @@ mod tests {
         .into();
         let env = TypeEnv::new();
 
-        mutate_value(&mut val, &ty, &env, &mut rng, 0);
+        mutate_value(&mut val, &ty, &env, &[], &mut rng, 0);
 
         let expected = IDLValue::Variant(VariantValue(
             Box::new(candid::types::value::IDLField {
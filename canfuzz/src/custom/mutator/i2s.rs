@@ -0,0 +1,175 @@
+//! A `libAFL` input-to-state mutator for any [`FuzzerInput`].
+//!
+//! The mutator reads the comparisons traced by the CmpLog instrumentation from the
+//! `CmpValuesMetadata` of the state. For a random comparison, it looks for one operand in the
+//! byte buffers of the input and replaces it with the other, so a magic value the canister
+//! compares against is copied into the input at the position it was read from.
+//!
+//! Operands are matched little-endian, the byte order of wasm memory and of the fixed-width
+//! Candid integers (`nat8` to `nat64`, `int8` to `int64`). Candid's `nat` and `int` are
+//! LEB128-encoded instead: only their values below 128 have the same encoding, and a
+//! replacement inside them changes the value differently than intended or corrupts the
+//! encoding. Such mutations are lossy, but cheap: arguments that no longer decode are rejected
+//! by the canister, and the input is not kept unless it finds new coverage.
+
+use libafl::{
+    Error, HasMetadata,
+    mutators::{MutationResult, Mutator},
+    observers::cmp::{CmpValues, CmpValuesMetadata},
+    state::HasRand,
+};
+use libafl_bolts::rands::Rand;
+use libafl_bolts::{AsSlice, Named};
+use rand::{Rng, SeedableRng};
+use std::borrow::Cow;
+use std::marker::PhantomData;
+
+use crate::custom::input::FuzzerInput;
+
+/// A `libAFL` mutator that replaces comparison operands found in the input.
+///
+/// See the [module documentation](self) for details. The mutator is skipped when the state
+/// has no traced comparisons or the input has no byte buffers.
+pub struct InputToStateMutator<I> {
+    phantom: PhantomData<I>,
+}
+
+impl<I> InputToStateMutator<I> {
+    /// Creates a new `InputToStateMutator`.
+    pub fn new() -> Self {
+        Self {
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<I> Default for InputToStateMutator<I> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<I> Named for InputToStateMutator<I> {
+    fn name(&self) -> &Cow<'static, str> {
+        &Cow::Borrowed("InputToStateMutator")
+    }
+}
+
+impl<I, S> Mutator<I, S> for InputToStateMutator<I>
+where
+    I: FuzzerInput,
+    S: HasRand + HasMetadata,
+{
+    fn mutate(&mut self, state: &mut S, input: &mut I) -> Result<MutationResult, Error> {
+        let state_u64 = state.rand_mut().next();
+        let mut rng = rand::rngs::StdRng::seed_from_u64(state_u64);
+
+        let Some(cmps) = state.metadata_map().get::<CmpValuesMetadata>() else {
+            return Ok(MutationResult::Skipped);
+        };
+        if cmps.list.is_empty() {
+            return Ok(MutationResult::Skipped);
+        }
+        let Some((a, b)) = operands(&cmps.list[rng.random_range(0..cmps.list.len())]) else {
+            return Ok(MutationResult::Skipped);
+        };
+
+        let mut buffers = input.bytes_mut();
+        buffers.retain(|buffer| !buffer.is_empty());
+        if buffers.is_empty() {
+            return Ok(MutationResult::Skipped);
+        }
+        let index = rng.random_range(0..buffers.len());
+        let buffer = &mut *buffers[index];
+        let start = rng.random_range(0..buffer.len());
+
+        // Either side of a comparison may be the one derived from the input.
+        let (from, to) = if rng.random_bool(0.5) {
+            (&a, &b)
+        } else {
+            (&b, &a)
+        };
+        if replace(buffer, from, to, start) || replace(buffer, to, from, start) {
+            Ok(MutationResult::Mutated)
+        } else {
+            Ok(MutationResult::Skipped)
+        }
+    }
+
+    fn post_exec(
+        &mut self,
+        _state: &mut S,
+        _new_corpus_id: Option<libafl::corpus::CorpusId>,
+    ) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+/// Returns the little-endian bytes of the operands of a comparison, or `None` if they are
+/// equal and there is nothing to replace.
+fn operands(cmp: &CmpValues) -> Option<(Vec<u8>, Vec<u8>)> {
+    let (a, b): (Vec<u8>, Vec<u8>) = match cmp {
+        CmpValues::U8((a, b, _)) => (a.to_le_bytes().to_vec(), b.to_le_bytes().to_vec()),
+        CmpValues::U16((a, b, _)) => (a.to_le_bytes().to_vec(), b.to_le_bytes().to_vec()),
+        CmpValues::U32((a, b, _)) => (a.to_le_bytes().to_vec(), b.to_le_bytes().to_vec()),
+        CmpValues::U64((a, b, _)) => (a.to_le_bytes().to_vec(), b.to_le_bytes().to_vec()),
+        CmpValues::Bytes((a, b)) => (a.as_slice().to_vec(), b.as_slice().to_vec()),
+    };
+    (a != b && a.len() == b.len()).then_some((a, b))
+}
+
+/// Replaces the first occurrence of `from` in `buffer` at or after `start`, wrapping around to
+/// the beginning, with `to`. Returns whether an occurrence was found.
+fn replace(buffer: &mut [u8], from: &[u8], to: &[u8], start: usize) -> bool {
+    if from.is_empty() || buffer.len() < from.len() {
+        return false;
+    }
+    let positions = buffer.len() - from.len() + 1;
+    let start = start % positions;
+    let found = (start..positions)
+        .chain(0..start)
+        .find(|&i| &buffer[i..i + from.len()] == from);
+    if let Some(i) = found {
+        buffer[i..i + to.len()].copy_from_slice(to);
+    }
+    found.is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libafl::inputs::BytesInput;
+    use libafl::state::NopState;
+
+    #[test]
+    fn test_replace_wraps_around() {
+        let mut buffer = vec![1, 0xaa, 0xbb, 2, 0xaa, 0xbb];
+        assert!(replace(&mut buffer, &[0xaa, 0xbb], &[3, 4], 5));
+        assert_eq!(buffer, [1, 3, 4, 2, 0xaa, 0xbb]);
+        assert!(replace(&mut buffer, &[0xaa, 0xbb], &[5, 6], 2));
+        assert_eq!(buffer, [1, 3, 4, 2, 5, 6]);
+        assert!(!replace(&mut buffer, &[0xaa, 0xbb], &[5, 6], 0));
+        assert!(!replace(&mut buffer, &[1, 2, 3, 4, 5, 6, 7], &[0; 7], 0));
+    }
+
+    #[test]
+    fn test_mutate_replaces_operand() {
+        let mut state = NopState::<BytesInput>::new();
+        let mut mutator = InputToStateMutator::new();
+
+        let mut input = BytesInput::new(b"xx\x44\x33\x22\x11yy".to_vec());
+        assert_eq!(
+            mutator.mutate(&mut state, &mut input).unwrap(),
+            MutationResult::Skipped
+        );
+
+        state.add_metadata(CmpValuesMetadata {
+            list: vec![CmpValues::U32((0xdead_beef, 0x1122_3344, false))],
+        });
+        assert_eq!(
+            mutator.mutate(&mut state, &mut input).unwrap(),
+            MutationResult::Mutated
+        );
+        assert_eq!(input.as_ref(), b"xx\xef\xbe\xad\xdeyy");
+    }
+}
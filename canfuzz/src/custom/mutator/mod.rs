@@ -1 +1,4 @@
+pub mod bytes;
+pub mod call_sequence;
 pub mod candid;
+pub mod i2s;
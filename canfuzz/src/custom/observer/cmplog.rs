@@ -0,0 +1,135 @@
+//! Observer for the comparison operands recorded by the CmpLog instrumentation.
+//!
+//! This module provides the [`CmpLogMap`] type, which holds the comparisons of the last
+//! tracing execution, and the [`CmpLogObserver`] type alias for use with libafl's observer
+//! framework. Each [`FuzzerState`](crate::fuzzer::FuzzerState) owns a map, which is updated
+//! by
+//! [`FuzzerOrchestrator::set_cmplog_map`](crate::orchestrator::FuzzerOrchestrator::set_cmplog_map)
+//! after each tracing execution. The observer turns it into the `CmpValuesMetadata` of the
+//! state, which is read by the
+//! [`InputToStateMutator`](crate::custom::mutator::i2s::InputToStateMutator).
+
+use crate::constants::CMPLOG_MAP_ENTRIES;
+use crate::libafl::Error;
+use crate::libafl::observers::cmp::{CmpMap, CmpValues, StdCmpObserver};
+use crate::libafl_bolts::HasLen;
+use serde::{Deserialize, Serialize};
+
+/// The comparisons recorded during the last tracing execution, one per comparison site.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CmpLogMap {
+    values: Vec<CmpValues>,
+}
+
+impl CmpLogMap {
+    /// Creates an empty map.
+    pub const fn new() -> Self {
+        Self { values: Vec::new() }
+    }
+
+    /// Adds the comparisons of a CmpLog buffer, as returned by the CmpLog export of a canister.
+    ///
+    /// Entries that were not hit are skipped. Operands are narrowed to the smallest width
+    /// that holds both of them, so a byte compared as an `i32` is looked up as a single byte
+    /// in the input.
+    pub fn record(&mut self, buffer: &[u8]) {
+        let entries = CMPLOG_MAP_ENTRIES as usize;
+        let (operands, widths) = buffer.split_at(buffer.len().min(entries * 16));
+        let operand =
+            |offset: usize| u64::from_le_bytes(operands[offset..offset + 8].try_into().unwrap());
+        for (site, width) in widths.iter().enumerate().take(operands.len() / 16) {
+            if *width == 0 {
+                continue;
+            }
+            let (a, b) = (operand(site * 16), operand(site * 16 + 8));
+            self.values.push(match a.max(b) {
+                0..=0xff => CmpValues::U8((a as u8, b as u8, false)),
+                0x100..=0xffff => CmpValues::U16((a as u16, b as u16, false)),
+                0x1_0000..=0xffff_ffff => CmpValues::U32((a as u32, b as u32, false)),
+                _ => CmpValues::U64((a, b, false)),
+            });
+        }
+    }
+
+    /// Returns the recorded comparisons.
+    pub fn values(&self) -> &[CmpValues] {
+        &self.values
+    }
+}
+
+impl HasLen for CmpLogMap {
+    fn len(&self) -> usize {
+        self.values.len()
+    }
+}
+
+impl CmpMap for CmpLogMap {
+    fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    fn executions_for(&self, _idx: usize) -> usize {
+        1
+    }
+
+    fn usable_executions_for(&self, _idx: usize) -> usize {
+        1
+    }
+
+    fn values_of(&self, idx: usize, _execution: usize) -> Option<CmpValues> {
+        self.values.get(idx).cloned()
+    }
+
+    fn reset(&mut self) -> Result<(), Error> {
+        self.values.clear();
+        Ok(())
+    }
+}
+
+/// A libafl observer that reads from the [`CmpLogMap`] of a
+/// [`FuzzerState`](crate::fuzzer::FuzzerState) and stores its comparisons in the
+/// `CmpValuesMetadata` of the state after every tracing execution.
+pub type CmpLogObserver<'a> = StdCmpObserver<'a, CmpLogMap>;
+
+/// The name used to register the observer with libafl's observer tuple.
+pub const CMPLOG_OBSERVER_NAME: &str = "CmpLogObserver";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn buffer(entries: &[(usize, u64, u64, u8)]) -> Vec<u8> {
+        let mut buffer = vec![0u8; crate::constants::CMPLOG_MAP_SIZE as usize];
+        let widths = CMPLOG_MAP_ENTRIES as usize * 16;
+        for &(site, a, b, width) in entries {
+            buffer[site * 16..site * 16 + 8].copy_from_slice(&a.to_le_bytes());
+            buffer[site * 16 + 8..site * 16 + 16].copy_from_slice(&b.to_le_bytes());
+            buffer[widths + site] = width;
+        }
+        buffer
+    }
+
+    #[test]
+    fn test_record_narrows_operands() {
+        let mut map = CmpLogMap::new();
+        map.record(&buffer(&[
+            (0, 17, 206, 4),
+            (5, 0x1234, 12, 4),
+            (7, 0xdead_beef, 0, 4),
+            (2047, 1 << 40, 3, 8),
+        ]));
+        assert_eq!(
+            map.values(),
+            [
+                CmpValues::U8((17, 206, false)),
+                CmpValues::U16((0x1234, 12, false)),
+                CmpValues::U32((0xdead_beef, 0, false)),
+                CmpValues::U64((1 << 40, 3, false)),
+            ]
+        );
+
+        map.reset().unwrap();
+        map.record(&[]);
+        assert!(map.values().is_empty());
+    }
+}
@@ -22,6 +22,8 @@ pub struct InstructionCountMap {
     pub increased: bool,
 }
 
+libafl_bolts::impl_serdeany!(InstructionCountMap);
+
 /// Global mutable state for instruction counting, shared between the harness and observer.
 pub static mut INSTRUCTION_MAP: RefCell<InstructionCountMap> = RefCell::new(InstructionCountMap {
     max_instructions: 0,
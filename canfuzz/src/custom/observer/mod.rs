@@ -1 +1,2 @@
+pub mod cmplog;
 pub mod instruction_count;
@@ -1,10 +1,42 @@
+use candid::Principal;
+use chrono::Local;
 use ic_management_canister_types::CanisterId;
-use pocket_ic::PocketIc;
+use pocket_ic::{PocketIc, PocketIcBuilder};
+use slog::Level;
+use std::cell::{Cell, OnceCell, Ref, RefCell};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::slice::IterMut;
 use std::sync::Arc;
-use std::{path::PathBuf, slice::IterMut};
+use std::time::Duration;
 
+use crate::constants::{
+    CMPLOG_FN_EXPORT_NAME, COVERAGE_FN_EXPORT_NAME, VALUE_PROFILE_FN_EXPORT_NAME,
+};
+use crate::custom::observer::cmplog::CmpLogMap;
+
+use crate::instrumentation::{
+    DEFAULT_HISTORY_SIZE, FunctionFilter, InstrumentationArgs, MAX_MAP_SIZE, Seed,
+    extract_dictionary, instrument_wasm_for_fuzzing,
+};
+use crate::libafl::mutators::Tokens;
+use crate::libafl_bolts::core_affinity::Cores;
 use crate::util::read_canister_bytes;
 
+/// The environment variable that sets the root directory of the artifacts, if it is not set
+/// with [`FuzzerBuilder::with_artifacts_dir`].
+pub const ARTIFACTS_DIR_ENV_VAR: &str = "CANFUZZ_ARTIFACTS_DIR";
+
+/// The environment variable that sets the seed of the fuzzing RNG, if it is not set with
+/// [`FuzzerBuilder::with_rng_seed`].
+pub const RNG_SEED_ENV_VAR: &str = "CANFUZZ_RNG_SEED";
+
+/// The default TCP port of the LLMP broker used in parallel mode.
+const DEFAULT_BROKER_PORT: u16 = 1337;
+
+/// The default amount of cycles added to each canister.
+const DEFAULT_CANISTER_CYCLES: u128 = 20_000_000_000_000;
+
 /// Represents the global state for a fuzzing campaign.
 ///
 /// This struct holds a reference to the `PocketIc` instance (the IC state machine), a list of all
@@ -18,6 +50,176 @@ pub struct FuzzerState {
     state: Option<Arc<PocketIc>>,
     /// A list of all canisters involved in the fuzzing setup.
     canisters: Vec<CanisterInfo>,
+    /// Configuration for running the campaign on several worker processes.
+    /// `None` runs a single in-process fuzzer.
+    parallel: Option<ParallelConfig>,
+    /// The fuzzer-side copy of the coverage map, observed by `libafl`.
+    /// It is allocated by [`FuzzerState::init_coverage_map`] once the coverage canisters are installed.
+    coverage_map: Option<CoverageMap>,
+    /// The part of the coverage map owned by each coverage canister, as `(id, offset, len)`.
+    coverage_segments: Vec<(CanisterId, usize, usize)>,
+    /// The fuzzer-side copy of the value-profile map, observed by `libafl`. It is empty if no
+    /// coverage canister is instrumented for value profiling.
+    /// It is allocated by [`FuzzerState::init_coverage_map`] along with the coverage map.
+    value_profile_map: Option<CoverageMap>,
+    /// The part of the value-profile map owned by each canister, as `(id, offset, len)`.
+    value_profile_segments: Vec<(CanisterId, usize, usize)>,
+    /// The comparisons of the last CmpLog tracing execution, observed by `libafl` through
+    /// [`FuzzerState::cmplog_map_ptr`].
+    cmplog_map: RefCell<CmpLogMap>,
+    /// The canisters are reset before every `reset_interval`-th execution.
+    reset_interval: u64,
+    /// The number of executions since the start of the campaign, used with `reset_interval`.
+    executions: Cell<u64>,
+    /// The snapshots taken by [`FuzzerState::prepare_reset`], one per canister with
+    /// [`ResetStrategy::Snapshot`].
+    snapshots: Vec<(CanisterId, Vec<u8>)>,
+    /// The Wasm modules installed by [`FuzzerState::setup_canisters`], used by
+    /// [`ResetStrategy::Reinstall`].
+    installed_modules: Vec<(CanisterId, Vec<u8>)>,
+    /// The campaign to resume, if any.
+    resume: Option<Resume>,
+    /// The root directory of the artifacts, if set with [`FuzzerBuilder::with_artifacts_dir`].
+    artifacts_dir: Option<PathBuf>,
+    /// The directory of the campaign, resolved once by [`FuzzerState::campaign_dir`].
+    campaign_dir: OnceCell<PathBuf>,
+    /// The conditions that end the campaign. By default, it runs until it is interrupted.
+    stop_conditions: StopConditions,
+    /// The seed of the fuzzing RNG, if set with [`FuzzerBuilder::with_rng_seed`].
+    rng_seed: Option<u64>,
+    /// Whether to show the terminal UI, see [`FuzzerBuilder::with_tui`].
+    tui: bool,
+    /// The log level of the `PocketIc` instance created by [`FuzzerState::setup_canisters`],
+    /// see [`FuzzerBuilder::with_log_level`].
+    log_level: Option<Level>,
+    /// The magic values extracted from the Wasm of the coverage canisters by
+    /// [`FuzzerState::setup_canisters`].
+    dictionary: Tokens,
+}
+
+/// Fuzzer-side storage for the coverage map of the instrumented canisters.
+///
+/// The map is refreshed from the canisters after every execution and read by the `libafl`
+/// map observer through a raw pointer (see [`CoverageMap::as_mut_ptr`]). The bytes are
+/// `Cell`s, so the map can be refreshed through a shared reference while the observer holds
+/// the pointer. Each [`FuzzerState`] owns its own map, so several fuzzers can coexist in one
+/// process. With several coverage canisters, the map is the concatenation of their maps, so
+/// an edge in one canister never collides with an edge in another.
+pub struct CoverageMap {
+    map: Box<[Cell<u8>]>,
+}
+
+impl CoverageMap {
+    /// Allocates a zeroed coverage map of `len` bytes.
+    pub fn new(len: usize) -> Self {
+        Self {
+            map: (0..len).map(|_| Cell::new(0)).collect(),
+        }
+    }
+
+    /// Returns the size of the map in bytes.
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    /// Returns `true` if the map has a size of zero bytes.
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// Returns a raw pointer to the start of the map.
+    ///
+    /// The pointer stays valid for as long as the `CoverageMap` is alive. It is used to
+    /// construct the `libafl` map observer, which reads the map while the fuzzer updates it.
+    pub fn as_mut_ptr(&self) -> *mut u8 {
+        // Writes through the pointer are allowed, since the bytes are `Cell`s.
+        self.map.as_ptr().cast_mut().cast::<u8>()
+    }
+
+    /// Returns the map as a mutable byte slice.
+    pub fn as_mut_slice(&mut self) -> &mut [u8] {
+        let len = self.map.len();
+        // SAFETY: `Cell<u8>` has the same memory layout as `u8`, and the exclusive borrow of
+        // `self` rules out any other access to the cells while the slice is alive.
+        unsafe { std::slice::from_raw_parts_mut(self.map.as_mut_ptr().cast::<u8>(), len) }
+    }
+
+    /// Overwrites the map with `data`, as returned by the coverage export of the canister.
+    ///
+    /// If `data` is shorter than the map, the remaining bytes are zeroed; extra bytes are ignored.
+    pub fn update(&self, data: &[u8]) {
+        self.update_segment(0, self.len(), data);
+    }
+
+    /// Overwrites the `len` bytes of the map starting at `offset` with `data`.
+    ///
+    /// If `data` is shorter than the segment, the remaining bytes are zeroed; extra bytes are ignored.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the segment does not fit in the map.
+    pub fn update_segment(&self, offset: usize, len: usize, data: &[u8]) {
+        let segment = offset
+            .checked_add(len)
+            .and_then(|end| self.map.get(offset..end))
+            .unwrap_or_else(|| {
+                panic!(
+                    "Segment {offset}+{len} is out of the coverage map of {} bytes",
+                    self.len()
+                )
+            });
+        let padding = std::iter::repeat(0);
+        for (cell, byte) in segment.iter().zip(data.iter().copied().chain(padding)) {
+            cell.set(byte);
+        }
+    }
+
+    /// Returns a copy of the current contents of the map.
+    pub fn to_vec(&self) -> Vec<u8> {
+        self.map.iter().map(Cell::get).collect()
+    }
+}
+
+/// Configuration for parallel fuzzing.
+///
+/// Each worker is a separate process bound to one core. Every worker calls
+/// [`FuzzerOrchestrator::init`](crate::orchestrator::FuzzerOrchestrator::init) on its own,
+/// so it owns a dedicated `PocketIc` instance, coverage map and fuzzer state. Workers
+/// exchange new corpus entries and crashes through an LLMP broker running in the
+/// parent process.
+#[derive(Clone, Debug)]
+pub struct ParallelConfig {
+    /// The cores to spawn workers on, one worker per core.
+    pub cores: Cores,
+    /// The TCP port the LLMP broker listens on.
+    pub broker_port: u16,
+}
+
+/// The conditions that end a fuzzing campaign, set with the `FuzzerBuilder`.
+///
+/// The campaign stops as soon as one of the conditions is met. If none is set, it runs until
+/// it is interrupted. In parallel mode, every worker checks the conditions on its own, so the
+/// execution budget applies to each worker.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct StopConditions {
+    /// Stops after the given wall-clock duration.
+    pub max_duration: Option<Duration>,
+    /// Stops after the given number of executions, including the executions of the seed corpus.
+    pub max_execs: Option<u64>,
+    /// Stops when no new edge has been covered for the given duration.
+    pub max_time_without_coverage: Option<Duration>,
+    /// Stops as soon as a new objective (crash, timeout or OOM) is saved.
+    pub stop_on_first_crash: bool,
+}
+
+impl StopConditions {
+    /// Returns `true` if at least one condition is set.
+    pub fn is_set(&self) -> bool {
+        self.max_duration.is_some()
+            || self.max_execs.is_some()
+            || self.max_time_without_coverage.is_some()
+            || self.stop_on_first_crash
+    }
 }
 
 /// Contains information describing a single canister used in the fuzzer.
@@ -34,13 +236,90 @@ pub struct CanisterInfo {
     pub ty: CanisterType,
     /// Initialization arguments for the canister.
     pub init_args: Vec<u8>,
+    /// The number of previous locations to track for coverage (must be 1, 2, 4, or 8).
+    /// Only used for coverage canisters.
+    pub history_size: usize,
+    /// The seed used to instrument the canister. Only used for coverage canisters.
+    pub seed: Seed,
+    /// The size of the coverage map in bytes, a power of two. Defaults to
+    /// `AFL_COVERAGE_MAP_SIZE * history_size` if `None`. Only used for coverage canisters.
+    pub map_size: Option<usize>,
+    /// Whether to inline the probe logic instead of calling a helper function.
+    /// Only used for coverage canisters.
+    pub inline_probes: bool,
+    /// Whether to number the coverage probes sequentially instead of at random.
+    /// Only used for coverage canisters.
+    pub sequential_probe_ids: bool,
+    /// Whether to instrument update methods to track instruction counts.
+    /// Only used for coverage canisters.
+    pub instrument_instruction_count: bool,
+    /// Whether to record the operands of comparisons for the CmpLog stage of the fuzzer.
+    /// Only used for coverage canisters.
+    pub instrument_cmplog: bool,
+    /// Whether to record the distance of the operands of comparisons in a value-profile map.
+    /// Only used for coverage canisters.
+    pub instrument_value_profile: bool,
+    /// The functions to instrument, by name. Only used for coverage canisters.
+    pub function_filter: FunctionFilter,
+    /// The amount of cycles added to the canister after creation.
+    pub cycles: u128,
+    /// The controllers of the canister. If `None`, the anonymous principal is the only controller.
+    pub controllers: Option<Vec<Principal>>,
+    /// How the canister is reset between executions. If `None`, the strategy set with
+    /// [`FuzzerBuilder::with_reset`] is used, or [`ResetStrategy::None`] without one.
+    pub reset: Option<ResetStrategy>,
+}
+
+impl CanisterInfo {
+    /// Returns the principal used to manage the canister: its first controller, or `None`
+    /// (the anonymous principal) if no controllers are set.
+    fn sender(&self) -> Option<Principal> {
+        self.controllers
+            .as_ref()
+            .and_then(|controllers| controllers.first().copied())
+    }
+
+    /// Returns how the canister is reset between executions.
+    fn reset(&self) -> ResetStrategy {
+        self.reset.unwrap_or_default()
+    }
+}
+
+/// Defines how a canister is reset to a clean state between executions.
+///
+/// The reset is performed by the orchestrator before
+/// [`FuzzerOrchestrator::setup`](crate::orchestrator::FuzzerOrchestrator::setup) is called,
+/// every [`FuzzerBuilder::with_reset_interval`] executions.
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Default)]
+pub enum ResetStrategy {
+    /// The canister keeps its state across executions (default).
+    #[default]
+    None,
+    /// The canister is restored from a snapshot taken right after
+    /// [`FuzzerOrchestrator::init`](crate::orchestrator::FuzzerOrchestrator::init).
+    Snapshot,
+    /// The canister is reinstalled with the module and init arguments used by
+    /// [`FuzzerState::setup_canisters`]. This is faster than loading a snapshot for canisters
+    /// with a large state, but discards any changes made in `init` after the installation.
+    Reinstall,
+}
+
+/// Selects the campaign to continue with [`FuzzerBuilder::with_resume`].
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum Resume {
+    /// The most recent campaign of the fuzzer, i.e. the last timestamped directory under
+    /// `<artifacts_dir>/<fuzzer_name>/` (see [`FuzzerState::artifacts_dir`]).
+    Latest,
+    /// The campaign stored in the given directory, e.g. `<artifacts_dir>/<fuzzer_name>/<timestamp>`.
+    Dir(PathBuf),
 }
 
 /// Defines the role of a canister in the fuzzing setup.
 #[derive(PartialEq, Eq, PartialOrd, Ord, Debug, Clone, Copy)]
 pub enum CanisterType {
-    /// The canister that is instrumented for code coverage.
-    /// There must be exactly one coverage canister.
+    /// A canister that is instrumented for code coverage.
+    /// There must be at least one coverage canister. The first one is the main target
+    /// returned by [`FuzzerState::get_coverage_canister_id`].
     Coverage,
     /// A supporting canister that is part of the test environment but not instrumented for coverage.
     Support,
@@ -64,17 +343,31 @@ impl FuzzerState {
     /// * `canisters` - A vector of `CanisterInfo` structs, one for each canister involved in the test.
     pub fn new(name: &str, canisters: Vec<CanisterInfo>) -> Self {
         assert!(
-            canisters
-                .iter()
-                .filter(|c| c.ty == CanisterType::Coverage)
-                .count()
-                == 1,
-            "Only one coverage canister is allowed"
+            canisters.iter().any(|c| c.ty == CanisterType::Coverage),
+            "At least one coverage canister is required"
         );
         Self {
             name: name.to_string(),
             state: None,
             canisters,
+            parallel: None,
+            coverage_map: None,
+            coverage_segments: Vec::new(),
+            value_profile_map: None,
+            value_profile_segments: Vec::new(),
+            cmplog_map: RefCell::new(CmpLogMap::new()),
+            reset_interval: 1,
+            executions: Cell::new(0),
+            snapshots: Vec::new(),
+            installed_modules: Vec::new(),
+            resume: None,
+            artifacts_dir: None,
+            campaign_dir: OnceCell::new(),
+            stop_conditions: StopConditions::default(),
+            rng_seed: None,
+            tui: false,
+            log_level: None,
+            dictionary: Tokens::new(),
         }
     }
 
@@ -91,35 +384,78 @@ impl FuzzerState {
     /// Automatically sets up the environment by creating a PocketIC instance and installing all registered canisters.
     ///
     /// This method:
-    /// 1. Creates a new `PocketIc` instance (if one isn't already set).
+    /// 1. Creates a new `PocketIc` instance with an application subnet (if one isn't already set),
+    ///    with the log level set by [`FuzzerBuilder::with_log_level`].
     /// 2. Iterates through all registered canisters.
-    /// 3. Creates each canister on the IC.
-    /// 4. Installs the Wasm code for each canister.
-    /// 5. Updates the `CanisterInfo` with the assigned `CanisterId`.
+    /// 3. Creates each canister on the IC and tops it up with cycles.
+    /// 4. Extracts a dictionary from the Wasm code of the coverage canisters (see
+    ///    [`extract_dictionary`]) and instruments it, using their instrumentation settings.
+    /// 5. Installs the Wasm code for each canister.
+    /// 6. Updates the `CanisterInfo` with the assigned `CanisterId`.
+    ///
+    /// If controllers are set, the first controller creates and installs the canister,
+    /// and the full list of controllers is set once it is installed.
     ///
     /// # Panics
     ///
     /// Panics if `PocketIc` cannot be initialized or if canister creation/installation fails.
     pub fn setup_canisters(&mut self) {
         if self.state.is_none() {
-            let pic = PocketIc::new();
-            self.init_state(pic);
+            let mut builder = PocketIcBuilder::new().with_application_subnet();
+            if let Some(log_level) = self.log_level {
+                builder = builder.with_log_level(log_level);
+            }
+            self.init_state(builder.build());
         }
 
         let pic = self.get_state_machine();
 
         for canister_info in self.canisters.iter_mut() {
-            let canister_id = pic.create_canister();
-            pic.add_cycles(canister_id, 20_000_000_000_000);
+            let sender = canister_info.sender();
+            let canister_id = pic.create_canister_with_settings(sender, None);
+            pic.add_cycles(canister_id, canister_info.cycles);
 
-            let wasm_bytes = read_canister_bytes(canister_info.wasm_path.clone());
+            let mut wasm_bytes = read_canister_bytes(canister_info.wasm_path.clone());
+            if canister_info.ty == CanisterType::Coverage {
+                let tokens = extract_dictionary(&wasm_bytes);
+                println!(
+                    "Extracted {} dictionary tokens from '{}'",
+                    tokens.len(),
+                    canister_info.name
+                );
+                self.dictionary.add_tokens(&tokens);
+                wasm_bytes = instrument_wasm_for_fuzzing(InstrumentationArgs {
+                    wasm_bytes,
+                    history_size: canister_info.history_size,
+                    seed: canister_info.seed,
+                    map_size: canister_info.map_size,
+                    inline_probes: canister_info.inline_probes,
+                    sequential_probe_ids: canister_info.sequential_probe_ids,
+                    instrument_instruction_count: canister_info.instrument_instruction_count,
+                    instrument_cmplog: canister_info.instrument_cmplog,
+                    instrument_value_profile: canister_info.instrument_value_profile,
+                    function_filter: canister_info.function_filter.clone(),
+                });
+            }
 
             pic.install_canister(
                 canister_id,
-                wasm_bytes,
+                wasm_bytes.clone(),
                 canister_info.init_args.clone(),
-                None,
+                sender,
             );
+            if canister_info.reset() == ResetStrategy::Reinstall {
+                self.installed_modules.push((canister_id, wasm_bytes));
+            }
+            if let Some(controllers) = canister_info.controllers.clone() {
+                pic.set_controllers(canister_id, sender, controllers)
+                    .unwrap_or_else(|e| {
+                        panic!(
+                            "Failed to set controllers of '{}': {e:?}",
+                            canister_info.name
+                        )
+                    });
+            }
 
             canister_info.id = Some(canister_id);
             println!(
@@ -133,7 +469,392 @@ impl FuzzerState {
         &self.name
     }
 
-    /// Returns the `CanisterId` of the coverage canister.
+    /// Returns the parallel fuzzing configuration, if the campaign runs on several workers.
+    pub fn parallel_config(&self) -> Option<&ParallelConfig> {
+        self.parallel.as_ref()
+    }
+
+    /// Returns the campaign to resume, if set with [`FuzzerBuilder::with_resume`].
+    pub fn resume(&self) -> Option<&Resume> {
+        self.resume.as_ref()
+    }
+
+    /// Returns the conditions that end the campaign.
+    pub fn stop_conditions(&self) -> &StopConditions {
+        &self.stop_conditions
+    }
+
+    /// Returns the seed of the fuzzing RNG: the one set with [`FuzzerBuilder::with_rng_seed`],
+    /// else the one in the `CANFUZZ_RNG_SEED` environment variable, if any.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `CANFUZZ_RNG_SEED` is set but is not a `u64`.
+    pub fn rng_seed(&self) -> Option<u64> {
+        self.rng_seed.or_else(|| {
+            let seed = std::env::var(RNG_SEED_ENV_VAR).ok()?;
+            Some(
+                seed.trim()
+                    .parse()
+                    .unwrap_or_else(|e| panic!("Invalid {RNG_SEED_ENV_VAR} {seed:?}: {e}")),
+            )
+        })
+    }
+
+    /// Returns `true` if the terminal UI was requested with [`FuzzerBuilder::with_tui`].
+    pub fn tui(&self) -> bool {
+        self.tui
+    }
+
+    /// Returns the dictionary extracted from the coverage canisters by
+    /// [`FuzzerState::setup_canisters`] or added with [`FuzzerState::extend_dictionary`],
+    /// used by the token mutations.
+    pub fn dictionary(&self) -> &Tokens {
+        &self.dictionary
+    }
+
+    /// Adds `tokens` to the dictionary, skipping duplicates.
+    ///
+    /// A custom [`FuzzerOrchestrator::init`](crate::orchestrator::FuzzerOrchestrator::init)
+    /// that instruments and installs canisters itself should add the [`extract_dictionary`]
+    /// of their Wasm, as [`FuzzerState::setup_canisters`] does for the coverage canisters.
+    pub fn extend_dictionary(&mut self, tokens: &[Vec<u8>]) {
+        self.dictionary.add_tokens(tokens);
+    }
+
+    /// Returns the root directory of the artifacts of all campaigns.
+    ///
+    /// This is the directory set with [`FuzzerBuilder::with_artifacts_dir`], else the
+    /// directory in the `CANFUZZ_ARTIFACTS_DIR` environment variable, else `$OUT_DIR/artifacts`
+    /// if `OUT_DIR` is set (when the fuzzer is launched with `cargo run` from a crate with a
+    /// build script), else `artifacts` in the current directory.
+    pub fn artifacts_dir(&self) -> PathBuf {
+        if let Some(dir) = &self.artifacts_dir {
+            return dir.clone();
+        }
+        if let Some(dir) = std::env::var_os(ARTIFACTS_DIR_ENV_VAR) {
+            return PathBuf::from(dir);
+        }
+        std::env::var_os("OUT_DIR")
+            .map(PathBuf::from)
+            .unwrap_or_default()
+            .join("artifacts")
+    }
+
+    /// Returns the directory of the current campaign.
+    ///
+    /// It is `<artifacts_dir>/<fuzzer_name>/<timestamp>`, with the timestamp of the first call,
+    /// so all the artifacts of a session end up in the same directory. When resuming a
+    /// campaign, it is the directory of that campaign instead.
+    ///
+    /// # Panics
+    ///
+    /// Panics if [`Resume::Latest`] is set and there is no campaign to resume.
+    pub fn campaign_dir(&self) -> &Path {
+        self.campaign_dir.get_or_init(|| {
+            let fuzzer_dir = self.artifacts_dir().join(&self.name);
+            match &self.resume {
+                None => fuzzer_dir.join(Local::now().format("%Y%m%d_%H%M%S").to_string()),
+                Some(Resume::Dir(dir)) => dir.clone(),
+                // The timestamps sort chronologically.
+                Some(Resume::Latest) => fs::read_dir(&fuzzer_dir)
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|e| e.ok())
+                    .map(|e| e.path())
+                    .filter(|p| p.is_dir())
+                    .max()
+                    .unwrap_or_else(|| panic!("No campaign to resume in {}", fuzzer_dir.display())),
+            }
+        })
+    }
+
+    /// Allocates the coverage map, sized to the maps exported by the coverage canisters, and
+    /// the value-profile map, sized to the maps exported by the canisters instrumented for it.
+    ///
+    /// The exports of every coverage canister are called once to determine the size of its
+    /// part of the maps. This also resets the canisters' maps, discarding any coverage
+    /// collected while installing and setting up the canisters.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the state machine is not initialized, a coverage canister is not installed,
+    /// or an export call fails (e.g. because the canister is not instrumented).
+    pub fn init_coverage_map(&mut self) {
+        let (coverage_map, coverage_segments) = self.init_map(COVERAGE_FN_EXPORT_NAME, |_| true);
+        let (value_profile_map, value_profile_segments) = self
+            .init_map(VALUE_PROFILE_FN_EXPORT_NAME, |info| {
+                info.instrument_value_profile
+            });
+        self.coverage_map = Some(coverage_map);
+        self.coverage_segments = coverage_segments;
+        self.value_profile_map = Some(value_profile_map);
+        self.value_profile_segments = value_profile_segments;
+    }
+
+    /// Allocates a map holding the maps returned by `export_name` on the coverage canisters
+    /// selected by `filter`, one segment per canister.
+    fn init_map(
+        &self,
+        export_name: &str,
+        filter: impl Fn(&CanisterInfo) -> bool,
+    ) -> (CoverageMap, Vec<(CanisterId, usize, usize)>) {
+        let pic = self.get_state_machine();
+        let mut segments = Vec::new();
+        let mut offset = 0;
+        for info in self
+            .canisters
+            .iter()
+            .filter(|c| c.ty == CanisterType::Coverage && filter(c))
+        {
+            let canister_id = info
+                .id
+                .unwrap_or_else(|| panic!("CanisterId is not initialized for {}", info.name));
+            let map = Self::take_map(&pic, canister_id, &info.name, export_name);
+            segments.push((canister_id, offset, map.len()));
+            offset += map.len();
+        }
+        (CoverageMap::new(offset), segments)
+    }
+
+    /// Returns the map of a canister from its `export_name` export, which also clears it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the call fails (e.g. because the canister is not instrumented).
+    fn take_map(pic: &PocketIc, canister_id: CanisterId, name: &str, export_name: &str) -> Vec<u8> {
+        pic.update_call(canister_id, Principal::anonymous(), export_name, vec![])
+            .unwrap_or_else(|e| {
+                panic!(
+                    "Failed to fetch the map of '{name}' from {export_name}. Is the canister instrumented? {e:?}"
+                )
+            })
+    }
+
+    /// Refreshes the coverage map from the coverage export of every coverage canister, and
+    /// the value-profile map from the value-profile export of the canisters instrumented for it.
+    ///
+    /// If a call fails, the part of the map of that canister is not updated.
+    pub fn update_coverage_map(&self) {
+        let pic = self.get_state_machine();
+        Self::update_map(
+            &pic,
+            self.coverage_map(),
+            &self.coverage_segments,
+            COVERAGE_FN_EXPORT_NAME,
+        );
+        Self::update_map(
+            &pic,
+            self.value_profile_map(),
+            &self.value_profile_segments,
+            VALUE_PROFILE_FN_EXPORT_NAME,
+        );
+    }
+
+    fn update_map(
+        pic: &PocketIc,
+        map: &CoverageMap,
+        segments: &[(CanisterId, usize, usize)],
+        export_name: &str,
+    ) {
+        for &(canister_id, offset, len) in segments {
+            if let Ok(result) =
+                pic.update_call(canister_id, Principal::anonymous(), export_name, vec![])
+            {
+                map.update_segment(offset, len, &result);
+            }
+        }
+    }
+
+    /// Returns whether any coverage canister numbers its coverage probes at random, so its
+    /// edges collide more often as its part of the coverage map fills up.
+    pub fn hashed_coverage(&self) -> bool {
+        self.canisters
+            .iter()
+            .any(|info| info.ty == CanisterType::Coverage && !info.sequential_probe_ids)
+    }
+
+    /// Returns whether any coverage canister is instrumented for CmpLog.
+    pub fn cmplog_enabled(&self) -> bool {
+        self.cmplog_canisters().next().is_some()
+    }
+
+    /// Adds the comparisons traced since the last call to the CmpLog map of this fuzzer, from
+    /// the CmpLog export of every coverage canister instrumented for CmpLog. The exports clear
+    /// their buffers.
+    ///
+    /// If a call fails, the comparisons of that canister are skipped.
+    pub fn update_cmplog_map(&self) {
+        let pic = self.get_state_machine();
+        let mut map = self.cmplog_map.borrow_mut();
+        for canister_id in self.cmplog_canisters() {
+            if let Ok(result) = pic.update_call(
+                canister_id,
+                Principal::anonymous(),
+                CMPLOG_FN_EXPORT_NAME,
+                vec![],
+            ) {
+                map.record(&result);
+            }
+        }
+    }
+
+    fn cmplog_canisters(&self) -> impl Iterator<Item = CanisterId> + '_ {
+        self.canisters
+            .iter()
+            .filter(|c| c.ty == CanisterType::Coverage && c.instrument_cmplog)
+            .filter_map(|c| c.id)
+    }
+
+    /// Prepares the canisters for [`FuzzerState::reset_canisters`] by taking a snapshot of
+    /// every canister with [`ResetStrategy::Snapshot`].
+    ///
+    /// This is called by the orchestrator after `init` and after the coverage map has been
+    /// allocated, so the snapshots hold the post-init state and an empty coverage map.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a snapshot cannot be taken, or if a canister with [`ResetStrategy::Reinstall`]
+    /// was not installed by [`FuzzerState::setup_canisters`].
+    pub fn prepare_reset(&mut self) {
+        let pic = self.get_state_machine();
+        self.snapshots.clear();
+        for info in &self.canisters {
+            let canister_id = info
+                .id
+                .unwrap_or_else(|| panic!("CanisterId is not initialized for {}", info.name));
+            match info.reset() {
+                ResetStrategy::None => {}
+                ResetStrategy::Snapshot => {
+                    let snapshot = pic
+                        .take_canister_snapshot(canister_id, info.sender(), None)
+                        .unwrap_or_else(|e| {
+                            panic!("Failed to take a snapshot of '{}': {e:?}", info.name)
+                        });
+                    self.snapshots.push((canister_id, snapshot.id));
+                }
+                ResetStrategy::Reinstall => assert!(
+                    self.installed_modules
+                        .iter()
+                        .any(|(id, _)| *id == canister_id),
+                    "Canister '{}' must be installed by setup_canisters() to be reset by reinstalling",
+                    info.name
+                ),
+            }
+        }
+    }
+
+    /// Resets all canisters according to their [`ResetStrategy`].
+    ///
+    /// The orchestrator calls this before every execution, but the canisters are only reset
+    /// every [`FuzzerBuilder::with_reset_interval`] executions. If a coverage canister is
+    /// reinstalled, the coverage and value-profile scores collected by its installation are
+    /// discarded.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a snapshot cannot be loaded, a canister cannot be reinstalled, or the maps of
+    /// a reinstalled coverage canister cannot be cleared.
+    pub fn reset_canisters(&self) {
+        let executions = self.executions.get();
+        self.executions.set(executions + 1);
+        if !executions.is_multiple_of(self.reset_interval) {
+            return;
+        }
+
+        let pic = self.get_state_machine();
+        for info in &self.canisters {
+            let Some(canister_id) = info.id else {
+                continue;
+            };
+            match info.reset() {
+                ResetStrategy::None => {}
+                ResetStrategy::Snapshot => {
+                    let (_, snapshot_id) = self
+                        .snapshots
+                        .iter()
+                        .find(|(id, _)| *id == canister_id)
+                        .expect("Snapshot not taken. Did you call prepare_reset()?");
+                    pic.load_canister_snapshot(canister_id, info.sender(), snapshot_id.clone())
+                        .unwrap_or_else(|e| {
+                            panic!("Failed to load the snapshot of '{}': {e:?}", info.name)
+                        });
+                }
+                ResetStrategy::Reinstall => {
+                    let (_, module) = self
+                        .installed_modules
+                        .iter()
+                        .find(|(id, _)| *id == canister_id)
+                        .expect("Canister not installed by setup_canisters()");
+                    pic.reinstall_canister(
+                        canister_id,
+                        module.clone(),
+                        info.init_args.clone(),
+                        info.sender(),
+                    )
+                    .unwrap_or_else(|e| panic!("Failed to reinstall '{}': {e:?}", info.name));
+                    if info.ty == CanisterType::Coverage {
+                        Self::take_map(&pic, canister_id, &info.name, COVERAGE_FN_EXPORT_NAME);
+                        if info.instrument_value_profile {
+                            Self::take_map(
+                                &pic,
+                                canister_id,
+                                &info.name,
+                                VALUE_PROFILE_FN_EXPORT_NAME,
+                            );
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Returns the coverage map of this fuzzer.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the map has not been allocated via `init_coverage_map`.
+    pub fn coverage_map(&self) -> &CoverageMap {
+        self.coverage_map
+            .as_ref()
+            .expect("Coverage map not initialized. Did you call init_coverage_map()?")
+    }
+
+    /// Returns the coverage map of this fuzzer for exclusive access.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the map has not been allocated via `init_coverage_map`.
+    pub fn coverage_map_mut(&mut self) -> &mut CoverageMap {
+        self.coverage_map
+            .as_mut()
+            .expect("Coverage map not initialized. Did you call init_coverage_map()?")
+    }
+
+    /// Returns the CmpLog map of this fuzzer.
+    pub fn cmplog_map(&self) -> Ref<'_, CmpLogMap> {
+        self.cmplog_map.borrow()
+    }
+
+    /// Returns a raw pointer to the CmpLog map of this fuzzer, for the `libafl` CmpLog
+    /// observer. The map must not be borrowed while the observer uses the pointer.
+    pub fn cmplog_map_ptr(&self) -> *mut CmpLogMap {
+        self.cmplog_map.as_ptr()
+    }
+
+    /// Returns the value-profile map of this fuzzer. It is empty if no coverage canister is
+    /// built [`with_value_profile`](CanisterBuilder::with_value_profile).
+    ///
+    /// # Panics
+    ///
+    /// Panics if the map has not been allocated via `init_coverage_map`.
+    pub fn value_profile_map(&self) -> &CoverageMap {
+        self.value_profile_map
+            .as_ref()
+            .expect("Value-profile map not initialized. Did you call init_coverage_map()?")
+    }
+
+    /// Returns the `CanisterId` of the main coverage canister, i.e. the first registered one.
     ///
     /// # Panics
     ///
@@ -206,6 +927,16 @@ impl FuzzerState {
 pub struct FuzzerBuilder {
     name: String,
     canisters: Vec<CanisterInfo>,
+    cores: Option<Cores>,
+    broker_port: u16,
+    reset: Option<ResetStrategy>,
+    reset_interval: u64,
+    resume: Option<Resume>,
+    artifacts_dir: Option<PathBuf>,
+    stop_conditions: StopConditions,
+    rng_seed: Option<u64>,
+    tui: bool,
+    log_level: Option<Level>,
 }
 
 impl FuzzerBuilder {
@@ -213,6 +944,16 @@ impl FuzzerBuilder {
         Self {
             name: "default_fuzzer".to_string(),
             canisters: Vec::new(),
+            cores: None,
+            broker_port: DEFAULT_BROKER_PORT,
+            reset: None,
+            reset_interval: 1,
+            resume: None,
+            artifacts_dir: None,
+            stop_conditions: StopConditions::default(),
+            rng_seed: None,
+            tui: false,
+            log_level: None,
         }
     }
 
@@ -228,13 +969,171 @@ impl FuzzerBuilder {
         self
     }
 
+    /// Runs the campaign in parallel with `workers` worker processes, bound to cores `0..workers`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `workers` is zero.
+    pub fn with_workers(mut self, workers: usize) -> Self {
+        assert!(workers > 0, "At least one worker is required");
+        self.cores = Some(Cores::from((0..workers).collect::<Vec<_>>()));
+        self
+    }
+
+    /// Runs the campaign in parallel on the given cores, one worker process per core.
+    ///
+    /// `cores` uses the libafl core specification syntax, e.g. `"all"`, `"0-7"` or `"0,2,4"`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `cores` is not a valid core specification.
+    pub fn with_cores(mut self, cores: &str) -> Self {
+        let cores = Cores::from_cmdline(cores)
+            .unwrap_or_else(|e| panic!("Invalid core specification {cores:?}: {e}"));
+        self.cores = Some(cores);
+        self
+    }
+
+    /// Sets the TCP port of the LLMP broker used in parallel mode (default: 1337).
+    pub fn with_broker_port(mut self, port: u16) -> Self {
+        self.broker_port = port;
+        self
+    }
+
+    /// Resets the canisters before every `interval`-th execution instead of before every
+    /// execution (default: 1). Only canisters with a [`ResetStrategy`] other than
+    /// [`ResetStrategy::None`] are reset.
+    ///
+    /// A larger interval trades the isolation of executions for speed when resetting a
+    /// canister is slow.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `interval` is zero.
+    pub fn with_reset_interval(mut self, interval: u64) -> Self {
+        assert!(interval > 0, "The reset interval must be at least 1");
+        self.reset_interval = interval;
+        self
+    }
+
+    /// Sets the [`ResetStrategy`] of the canisters that have none set with
+    /// [`CanisterBuilder::with_reset`], whether they are added before or after this call.
+    pub fn with_reset(mut self, reset: ResetStrategy) -> Self {
+        self.reset = Some(reset);
+        self
+    }
+
+    /// Sets the root directory of the artifacts. Each campaign stores its corpus, crashes and
+    /// state in `<dir>/<fuzzer_name>/<timestamp>`.
+    ///
+    /// This takes precedence over the `CANFUZZ_ARTIFACTS_DIR` environment variable and the
+    /// default location (see [`FuzzerState::artifacts_dir`]).
+    pub fn with_artifacts_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.artifacts_dir = Some(dir.into());
+        self
+    }
+
+    /// Continues a previous campaign instead of starting a new one.
+    ///
+    /// The artifact directories of the campaign are reused and the fuzzer state saved by
+    /// the campaign (corpus with its metadata, coverage history, scheduler metadata, maximum
+    /// instruction count and statistics) is restored, so fuzzing continues where it stopped.
+    /// The coverage canisters installed by [`FuzzerState::setup_canisters`] are instrumented
+    /// with the same seeds as before; canisters instrumented in a custom
+    /// [`FuzzerOrchestrator::init`](crate::orchestrator::FuzzerOrchestrator::init) must use a
+    /// [`Seed::Static`] for the restored coverage history to be meaningful.
+    pub fn with_resume(mut self, resume: Resume) -> Self {
+        self.resume = Some(resume);
+        self
+    }
+
+    /// Stops the campaign after `duration` of wall-clock time.
+    pub fn with_max_duration(mut self, duration: Duration) -> Self {
+        self.stop_conditions.max_duration = Some(duration);
+        self
+    }
+
+    /// Stops the campaign after `execs` executions of the harness.
+    pub fn with_max_execs(mut self, execs: u64) -> Self {
+        self.stop_conditions.max_execs = Some(execs);
+        self
+    }
+
+    /// Stops the campaign when no new edge has been covered for `duration`.
+    pub fn with_max_time_without_coverage(mut self, duration: Duration) -> Self {
+        self.stop_conditions.max_time_without_coverage = Some(duration);
+        self
+    }
+
+    /// Stops the campaign as soon as the first crash, timeout or OOM is saved.
+    pub fn with_stop_on_first_crash(mut self) -> Self {
+        self.stop_conditions.stop_on_first_crash = true;
+        self
+    }
+
+    /// Seeds the fuzzing RNG with `seed` instead of the current time, taking precedence over
+    /// the `CANFUZZ_RNG_SEED` environment variable.
+    ///
+    /// The seed drives the mutations, the scheduling, the input generated when the seed corpus
+    /// is empty and the instrumentation of the coverage canisters with a [`Seed::Random`]. With
+    /// the same seed, seed corpus and canisters, a single-worker campaign makes the same
+    /// choices again, as long as the canisters behave deterministically. Scheduling also
+    /// weighs the measured execution times, so runs can still diverge slightly over time.
+    /// In parallel mode, worker `n` uses `seed + n`.
+    pub fn with_rng_seed(mut self, seed: u64) -> Self {
+        self.rng_seed = Some(seed);
+        self
+    }
+
+    /// Shows a terminal UI instead of printing a line per event. It shows the session (canister
+    /// IDs, artifact paths and RNG seed), the progress (executions, coverage, corpus, crash
+    /// buckets and maximum instruction count) and the latest crash buckets. The coverage is the
+    /// share of the instrumented probes that are covered if all coverage canisters use
+    /// [sequential probe IDs](CanisterBuilder::with_sequential_probe_ids). With random probe
+    /// IDs, the number of edges is not known, so it is the number of covered map entries.
+    /// While it is shown, the output of the fuzzer goes to `fuzzer.log` in the campaign
+    /// directory.
+    ///
+    /// This requires the `tui` feature of `canfuzz`. Without it, or when stdout is not a
+    /// terminal, the plain output is used.
+    pub fn with_tui(mut self) -> Self {
+        self.tui = true;
+        self
+    }
+
+    /// Sets the log level of the `PocketIc` instance created when the canisters are set up.
+    /// By default, the level of the PocketIC server is used. A custom
+    /// [`FuzzerOrchestrator::init`](crate::orchestrator::FuzzerOrchestrator::init) that
+    /// creates its own instance ignores this setting.
+    pub fn with_log_level(mut self, log_level: Level) -> Self {
+        self.log_level = Some(log_level);
+        self
+    }
+
     /// Builds the `FuzzerState`.
     ///
     /// # Panics
     ///
-    /// Panics if there is not exactly one coverage canister.
-    pub fn build(self) -> FuzzerState {
-        FuzzerState::new(&self.name, self.canisters)
+    /// Panics if there is no coverage canister.
+    pub fn build(mut self) -> FuzzerState {
+        if let Some(reset) = self.reset {
+            for canister in &mut self.canisters {
+                canister.reset.get_or_insert(reset);
+            }
+        }
+        let mut state = FuzzerState::new(&self.name, self.canisters);
+        state.reset_interval = self.reset_interval;
+        state.resume = self.resume;
+        state.artifacts_dir = self.artifacts_dir;
+        state.stop_conditions = self.stop_conditions;
+        state.rng_seed = self.rng_seed;
+        state.tui = self.tui;
+        state.log_level = self.log_level;
+        state.parallel = self.cores.map(|cores| ParallelConfig {
+            cores,
+            broker_port: self.broker_port,
+        });
+        state
     }
 }
 
@@ -250,6 +1149,18 @@ pub struct CanisterBuilder {
     wasm_path: Option<WasmPath>,
     ty: CanisterType,
     init_args: Vec<u8>,
+    history_size: usize,
+    seed: Seed,
+    map_size: Option<usize>,
+    inline_probes: bool,
+    sequential_probe_ids: bool,
+    instrument_instruction_count: bool,
+    instrument_cmplog: bool,
+    instrument_value_profile: bool,
+    function_filter: FunctionFilter,
+    cycles: u128,
+    controllers: Option<Vec<Principal>>,
+    reset: Option<ResetStrategy>,
 }
 
 impl CanisterBuilder {
@@ -260,6 +1171,18 @@ impl CanisterBuilder {
             wasm_path: None,
             ty: CanisterType::Support, // Default to Support
             init_args: Vec::new(),
+            history_size: DEFAULT_HISTORY_SIZE,
+            seed: Seed::Random,
+            map_size: None,
+            inline_probes: false,
+            sequential_probe_ids: false,
+            instrument_instruction_count: false,
+            instrument_cmplog: false,
+            instrument_value_profile: false,
+            function_filter: FunctionFilter::default(),
+            cycles: DEFAULT_CANISTER_CYCLES,
+            controllers: None,
+            reset: None,
         }
     }
 
@@ -281,6 +1204,126 @@ impl CanisterBuilder {
         self
     }
 
+    /// Sets the number of previous locations tracked for coverage (default: 8).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `history_size` is not 1, 2, 4, or 8.
+    pub fn with_history_size(mut self, history_size: usize) -> Self {
+        assert!(
+            matches!(history_size, 1 | 2 | 4 | 8),
+            "History size must be 1, 2, 4, or 8"
+        );
+        self.history_size = history_size;
+        self
+    }
+
+    /// Sets the seed used to instrument the canister (default: `Seed::Random`).
+    pub fn with_seed(mut self, seed: Seed) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Sets the size of the coverage map in bytes (default: `AFL_COVERAGE_MAP_SIZE *
+    /// history_size`). Large canisters need a larger map to keep edges from colliding, and the
+    /// fuzzer warns when the map fills up. Small canisters are faster with a smaller map, as
+    /// the whole map is copied after every execution.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `map_size` is not a power of two of at most
+    /// [`MAX_MAP_SIZE`](crate::instrumentation::MAX_MAP_SIZE).
+    pub fn with_map_size(mut self, map_size: usize) -> Self {
+        assert!(
+            map_size.is_power_of_two() && map_size <= MAX_MAP_SIZE,
+            "Map size must be a power of two of at most 1 GiB"
+        );
+        self.map_size = Some(map_size);
+        self
+    }
+
+    /// Inlines the probe logic at every probe instead of calling a helper function (see
+    /// [`InstrumentationArgs::inline_probes`](crate::instrumentation::InstrumentationArgs::inline_probes)).
+    /// Probes then cost fewer instructions, so instruction counts are less inflated and inputs
+    /// hit the instruction limit later, at the cost of a larger module.
+    pub fn with_inline_probes(mut self) -> Self {
+        self.inline_probes = true;
+        self
+    }
+
+    /// Numbers the coverage probes sequentially instead of at random, so the coverage map
+    /// has exactly one entry per probe and is the same across runs (see
+    /// [`InstrumentationArgs::sequential_probe_ids`](crate::instrumentation::InstrumentationArgs::sequential_probe_ids)).
+    /// The history size and seed are then ignored, and the instrumentation panics if a map
+    /// size is also set with [`CanisterBuilder::with_map_size`]. The function and instruction
+    /// offset of every probe are listed in the `probe_table` file of the campaign directory.
+    pub fn with_sequential_probe_ids(mut self) -> Self {
+        self.sequential_probe_ids = true;
+        self
+    }
+
+    /// Enables instrumentation of update methods to track instruction counts.
+    ///
+    /// This is required when the orchestrator enables instruction count feedback
+    /// via `instruction_config()`.
+    pub fn with_instruction_count(mut self) -> Self {
+        self.instrument_instruction_count = true;
+        self
+    }
+
+    /// Enables CmpLog instrumentation, which records the operands of the canister's
+    /// comparisons. The fuzzer then adds a tracing stage and an input-to-state mutation stage
+    /// to solve multi-byte comparisons (see
+    /// [`InstrumentationArgs::instrument_cmplog`](crate::instrumentation::InstrumentationArgs::instrument_cmplog)).
+    pub fn with_cmplog(mut self) -> Self {
+        self.instrument_cmplog = true;
+        self
+    }
+
+    /// Enables value-profile instrumentation, which records how close the operands of the
+    /// canister's comparisons are. Inputs that bring them closer are kept in the corpus even
+    /// if they reach no new edge (see
+    /// [`InstrumentationArgs::instrument_value_profile`](crate::instrumentation::InstrumentationArgs::instrument_value_profile)).
+    pub fn with_value_profile(mut self) -> Self {
+        self.instrument_value_profile = true;
+        self
+    }
+
+    /// Restricts the instrumentation to the functions selected by `filter`, e.g. to exclude
+    /// `candid::*` (see [`FunctionFilter`]). The skipped functions are listed in the
+    /// `skipped_functions` file of the campaign directory.
+    pub fn with_function_filter(mut self, filter: FunctionFilter) -> Self {
+        self.function_filter = filter;
+        self
+    }
+
+    /// Sets the amount of cycles added to the canister after creation (default: `20_000_000_000_000`).
+    pub fn with_cycles(mut self, cycles: u128) -> Self {
+        self.cycles = cycles;
+        self
+    }
+
+    /// Sets the controllers of the canister. The first controller creates and installs it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `controllers` is empty.
+    pub fn with_controllers(mut self, controllers: Vec<Principal>) -> Self {
+        assert!(
+            !controllers.is_empty(),
+            "At least one controller is required"
+        );
+        self.controllers = Some(controllers);
+        self
+    }
+
+    /// Sets how the canister is reset between executions (default: the strategy set with
+    /// [`FuzzerBuilder::with_reset`], or [`ResetStrategy::None`]).
+    pub fn with_reset(mut self, reset: ResetStrategy) -> Self {
+        self.reset = Some(reset);
+        self
+    }
+
     /// Marks this canister as the coverage target.
     pub fn as_coverage(mut self) -> Self {
         self.ty = CanisterType::Coverage;
@@ -305,6 +1348,106 @@ impl CanisterBuilder {
             wasm_path: self.wasm_path.expect("Wasm path must be set"),
             ty: self.ty,
             init_args: self.init_args,
+            history_size: self.history_size,
+            seed: self.seed,
+            map_size: self.map_size,
+            inline_probes: self.inline_probes,
+            sequential_probe_ids: self.sequential_probe_ids,
+            instrument_instruction_count: self.instrument_instruction_count,
+            instrument_cmplog: self.instrument_cmplog,
+            instrument_value_profile: self.instrument_value_profile,
+            function_filter: self.function_filter,
+            cycles: self.cycles,
+            controllers: self.controllers,
+            reset: self.reset,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn canister(name: &str) -> CanisterBuilder {
+        CanisterBuilder::new(name).with_wasm_path(format!("{name}.wasm"))
+    }
+
+    #[test]
+    fn builder_reset_applies_to_canisters_without_their_own() {
+        let state = FuzzerBuilder::new()
+            .with_canister(canister("before").as_coverage().build())
+            .with_reset(ResetStrategy::Snapshot)
+            .with_canister(canister("after").build())
+            .with_canister(
+                canister("explicit")
+                    .with_reset(ResetStrategy::Reinstall)
+                    .build(),
+            )
+            .build();
+
+        let resets: Vec<_> = state.canisters.iter().map(|c| c.reset()).collect();
+        assert_eq!(
+            resets,
+            [
+                ResetStrategy::Snapshot,
+                ResetStrategy::Snapshot,
+                ResetStrategy::Reinstall
+            ]
+        );
+    }
+
+    #[ignore = "requires the PocketIC server"]
+    #[test]
+    fn reinstall_clears_value_profile_map() {
+        // `canister_init` compares a value with a constant, which scores its comparison site.
+        let wasm = wat::parse_str(
+            r#"
+            (module
+                (memory 1)
+                (func (export "canister_init")
+                    i32.const 0
+                    i64.load
+                    i64.const 1234567
+                    i64.lt_u
+                    drop
+                )
+            )
+            "#,
+        )
+        .unwrap();
+        let wasm_path = std::env::temp_dir().join(format!(
+            "canfuzz_reinstall_value_profile_{}.wasm",
+            std::process::id()
+        ));
+        fs::write(&wasm_path, wasm).unwrap();
+
+        let mut state = FuzzerBuilder::new()
+            .with_canister(
+                CanisterBuilder::new("target")
+                    .with_wasm_path(&wasm_path)
+                    .with_value_profile()
+                    .with_reset(ResetStrategy::Reinstall)
+                    .as_coverage()
+                    .build(),
+            )
+            .build();
+        state.setup_canisters();
+        state.init_coverage_map();
+        state.prepare_reset();
+        fs::remove_file(&wasm_path).unwrap();
+
+        state.reset_canisters();
+        state.update_coverage_map();
+        let map = state.value_profile_map().to_vec();
+        assert!(!map.is_empty());
+        assert!(map.iter().all(|&score| score == 0), "{map:?}");
+    }
+
+    #[test]
+    fn canisters_are_not_reset_by_default() {
+        let state = FuzzerBuilder::new()
+            .with_canister(canister("target").as_coverage().build())
+            .build();
+        assert_eq!(state.canisters[0].reset(), ResetStrategy::None);
+    }
+}
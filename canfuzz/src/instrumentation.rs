@@ -10,6 +10,92 @@
 //! 4.  Exporting a coverage function that allows the fuzzer to retrieve the
 //!     coverage map from the canister.
 //!
+//! ## Coverage Map Placement
+//!
+//! The coverage map must never overlap with memory used by the program under test, otherwise
+//! the instrumentation corrupts the canister's own data (false crashes) or the canister
+//! overwrites the map (bogus coverage). The IC only supports a single Wasm memory, so the map
+//! cannot live in a dedicated memory. Instead, the instrumentation reserves a region at the
+//! end of memory 0:
+//!
+//! 1.  A start function grows memory 0 by the reserved pages (a guard page, the coverage map
+//!     and a scratch area) and points `__afl_mem_ptr` at the coverage map.
+//! 2.  Every `memory.size` and `memory.grow` in the program is replaced by a call to a helper
+//!     that hides the reserved pages. The program observes exactly the memory size it would
+//!     have without instrumentation.
+//! 3.  When the program grows its memory, the helper moves the reserved region to the new
+//!     end of memory and zeroes the pages that are handed to the program, so newly grown
+//!     memory still reads as zero.
+//!
+//! A declared maximum memory size is raised by the number of reserved pages, so the program
+//! can still grow up to its original limit.
+//!
+//! ### Limitation: out-of-bounds accesses
+//!
+//! Without instrumentation, an access just past the end of the program's memory traps. With
+//! the reserved region behind it, the same access stays within the real memory and succeeds,
+//! so the fuzzer does not report these out-of-bounds bugs. Wasm has no page protection, so
+//! the region cannot be made to trap. The reserved region starts with an unused guard page,
+//! which keeps writes that overflow the program's memory by less than a page from corrupting
+//! the coverage map, but such writes and all out-of-bounds reads still go unnoticed.
+//!
+//! ## Sequential Probe IDs
+//!
+//! By default, every probe gets a random location drawn from the instrumentation seed, and the
+//! helper counts the edge `location ^ prev_loc[0] ^ ...` in a map of `AFL_COVERAGE_MAP_SIZE *
+//! history_size` bytes. Edges can collide, and the map differs between seeds.
+//!
+//! When [`InstrumentationArgs::sequential_probe_ids`] is enabled, the probes are numbered
+//! `0, 1, 2, ...` in the order of the functions and their instructions, and the helper counts
+//! the probe itself in a map with exactly one byte per probe. The coverage is then exact and
+//! comparable across runs, at the cost of the edge history. [`probe_table`] maps every entry of
+//! the map back to the function and instruction of its probe.
+//!
+//! ## Inline Probes
+//!
+//! By default, every probe pushes its location and calls a helper, which costs the call and
+//! reads the location from a parameter. When [`InstrumentationArgs::inline_probes`] is enabled,
+//! the helper body is inlined at every probe instead, with the location and the new previous
+//! location folded into constants. With `history_size == 1`, a probe takes 13 instructions
+//! instead of 21. Without history, as for sequential probe IDs, the location becomes the
+//! offset of the load and store of the map byte, and a probe takes 6 instructions instead of
+//! 15. Instruction counting adds 4 instructions to both. The instrumented module is larger,
+//! but instruction counts are less inflated by the instrumentation, so inputs reach the
+//! instruction limit later.
+//!
+//! ## CmpLog Instrumentation
+//!
+//! When [`InstrumentationArgs::instrument_cmplog`] is enabled, the module also records the
+//! operands of its integer comparisons, so the fuzzer can solve multi-byte comparisons with
+//! input-to-state replacement instead of guessing the magic values:
+//! 1.  Every `i32.eq`/`i64.eq`/`ne` and every `lt`/`gt`/`le`/`ge` comparison directly followed by
+//!     a `br_if` or an `if` is a comparison site. Sites are numbered in order.
+//! 2.  The operands of a site are saved in two new locals and passed, with the site number, to
+//!     a helper that writes them to the site's entry of a CmpLog buffer. The buffer lives in the
+//!     reserved region, right after the coverage map and the scratch area.
+//! 3.  A [`CMPLOG_FN_EXPORT_NAME`](crate::constants::CMPLOG_FN_EXPORT_NAME) update function
+//!     replies with the buffer and clears it.
+//!
+//! An entry only holds the operands of the last execution of its site. The fuzzer only
+//! fetches the buffer in its tracing stage, once per corpus entry, so regular executions pay
+//! for the helper calls but not for the export. The buffer is not cleared before a trace, so
+//! it may still hold the operands of sites only reached by earlier executions; these merely
+//! add candidate replacements. Instruction counts are not discounted for these calls.
+//!
+//! ## Value Profile Instrumentation
+//!
+//! When [`InstrumentationArgs::instrument_value_profile`] is enabled, the comparison sites
+//! described above are also passed to a helper that measures how close their operands are:
+//! the number of equal bits plus one. The helper keeps the maximum per site in a
+//! value-profile map of [`VALUE_PROFILE_MAP_SIZE`](crate::constants::VALUE_PROFILE_MAP_SIZE)
+//! bytes, which lives in the reserved region after the CmpLog buffer. A
+//! [`VALUE_PROFILE_FN_EXPORT_NAME`](crate::constants::VALUE_PROFILE_FN_EXPORT_NAME) update
+//! function replies with the map and clears it, like the coverage export. The fuzzer rewards
+//! inputs that bring the operands of a site closer, even if they reach no new edge, so
+//! comparisons against values computed at run time are solved bit by bit. The helper runs on
+//! every execution of a comparison site, and instruction counts are not discounted for these
+//! calls either.
+//!
 //! ## Instruction Count Instrumentation
 //!
 //! When [`InstrumentationArgs::instrument_instruction_count`] is enabled, the module also
@@ -71,19 +157,27 @@ use rand::SeedableRng;
 use wirm::ir::function::FunctionBuilder;
 use wirm::ir::id::{FunctionID, GlobalID, LocalID, MemoryID};
 use wirm::ir::module::module_functions::FuncKind;
-use wirm::ir::types::{InitExpr, Instructions, Value};
+use wirm::ir::types::{BlockType, InitExpr, Instructions, Value};
 use wirm::module_builder::AddLocal;
 use wirm::opcode::Inject;
-use wirm::wasmparser::{ExternalKind, MemArg, Operator, Validator};
+use wirm::wasmparser::{ExternalKind, MemArg, MemoryType, Operator, Validator};
 use wirm::{DataType, InitInstr, Module, Opcode};
 
 use crate::constants::{
-    AFL_COVERAGE_MAP_SIZE, API_VERSION_IC0, COVERAGE_FN_EXPORT_NAME,
-    INSTRUCTION_COUNT_FN_EXPORT_NAME,
+    AFL_COVERAGE_MAP_SIZE, API_VERSION_IC0, CMPLOG_FN_EXPORT_NAME, CMPLOG_MAP_ENTRIES,
+    CMPLOG_MAP_SIZE, COVERAGE_FN_EXPORT_NAME, INSTRUCTION_COUNT_FN_EXPORT_NAME,
+    VALUE_PROFILE_FN_EXPORT_NAME, VALUE_PROFILE_MAP_SIZE, WASM_PAGE_SIZE,
 };
 use std::collections::HashSet;
 
+/// The default number of previous locations tracked by the coverage instrumentation.
+pub(crate) const DEFAULT_HISTORY_SIZE: usize = 8;
+
 /// Arguments for configuring the Wasm instrumentation process.
+///
+/// The [`Default`] arguments have no Wasm bytes, track 8 previous locations with a random
+/// seed and enable none of the optional instrumentation, e.g.
+/// `InstrumentationArgs { wasm_bytes, ..Default::default() }`.
 pub struct InstrumentationArgs {
     /// The raw Wasm module to instrument.
     pub wasm_bytes: Vec<u8>,
@@ -91,11 +185,115 @@ pub struct InstrumentationArgs {
     pub history_size: usize,
     /// The seed to use for instrumentation.
     pub seed: Seed,
+    /// The size of the coverage map in bytes, which must be a power of two of at most 1 GiB.
+    /// Defaults to `AFL_COVERAGE_MAP_SIZE * history_size` if `None`. Large canisters need a
+    /// larger map to keep edges from colliding, while small ones are faster with a smaller map.
+    pub map_size: Option<usize>,
+    /// Whether to inline the probe logic at every probe instead of calling a helper function
+    /// (see the module-level documentation). Inline probes cost fewer instructions but make
+    /// the module larger.
+    pub inline_probes: bool,
+    /// Whether to number the coverage probes sequentially instead of at random (see the
+    /// module-level documentation). The coverage map then has one entry per probe, and
+    /// `history_size` and `seed` are ignored. It cannot be combined with a `map_size`.
+    pub sequential_probe_ids: bool,
     /// Whether to instrument `canister_update` methods to track instruction counts.
     /// When enabled, wrapper functions are injected that read the IC performance counter
     /// after each method execution and an export function is added to retrieve the count.
     /// Query methods are not wrapped (see module-level limitations).
     pub instrument_instruction_count: bool,
+    /// Whether to record the operands of comparisons for CmpLog (see the module-level
+    /// documentation). An export function is added to retrieve them.
+    pub instrument_cmplog: bool,
+    /// Whether to record the distance of the operands of comparisons in a value-profile map
+    /// (see the module-level documentation). An export function is added to retrieve it.
+    pub instrument_value_profile: bool,
+    /// The functions that get coverage probes and traced comparisons. By default, all
+    /// functions are instrumented.
+    pub function_filter: FunctionFilter,
+}
+
+impl Default for InstrumentationArgs {
+    fn default() -> Self {
+        Self {
+            wasm_bytes: Vec::new(),
+            history_size: DEFAULT_HISTORY_SIZE,
+            seed: Seed::Random,
+            map_size: None,
+            inline_probes: false,
+            sequential_probe_ids: false,
+            instrument_instruction_count: false,
+            instrument_cmplog: false,
+            instrument_value_profile: false,
+            function_filter: FunctionFilter::default(),
+        }
+    }
+}
+
+/// Selects the functions to instrument by their names in the Wasm `name` section.
+///
+/// Patterns match whole names, and `*` matches any sequence of characters, e.g. `candid::*`
+/// or `*::fmt::*`. Names are matched as they appear in the `name` section, which `wasm-ld`
+/// demangles for Rust canisters. Library code like Candid decoding, allocators or the Motoko
+/// RTS can be excluded to make the coverage less noisy and cheaper.
+///
+/// Functions that are filtered out get no coverage probes and their comparisons are not
+/// traced. Their `memory.size` and `memory.grow` instructions are still rewritten (see
+/// [Coverage Map Placement](self#coverage-map-placement)).
+#[derive(Clone, Debug, Default)]
+pub struct FunctionFilter {
+    /// If not empty, only the functions matching one of these patterns are instrumented.
+    /// Functions without a name never match.
+    pub include: Vec<String>,
+    /// The functions matching one of these patterns are not instrumented, even if they match
+    /// an `include` pattern.
+    pub exclude: Vec<String>,
+}
+
+impl FunctionFilter {
+    /// Returns `true` if the filter has no patterns, so all functions are instrumented.
+    pub fn is_empty(&self) -> bool {
+        self.include.is_empty() && self.exclude.is_empty()
+    }
+
+    /// Returns whether a function with the given name (`None` if it has none) is instrumented.
+    pub fn instruments(&self, name: Option<&str>) -> bool {
+        let matches_any =
+            |patterns: &[String], name: &str| patterns.iter().any(|p| matches_pattern(p, name));
+        match name {
+            Some(name) => {
+                (self.include.is_empty() || matches_any(&self.include, name))
+                    && !matches_any(&self.exclude, name)
+            }
+            None => self.include.is_empty(),
+        }
+    }
+}
+
+/// Returns whether `name` matches `pattern`, where `*` matches any sequence of characters.
+fn matches_pattern(pattern: &str, name: &str) -> bool {
+    let (pattern, name) = (pattern.as_bytes(), name.as_bytes());
+    let (mut p, mut n) = (0, 0);
+    // The position after the last `*` in the pattern, and the position in the name it
+    // currently matches up to.
+    let mut star: Option<(usize, usize)> = None;
+    while n < name.len() {
+        if p < pattern.len() && pattern[p] == b'*' {
+            star = Some((p + 1, n));
+            p += 1;
+        } else if p < pattern.len() && pattern[p] == name[n] {
+            p += 1;
+            n += 1;
+        } else if let Some((star_p, star_n)) = star {
+            // Let the `*` match one more character and retry.
+            star = Some((star_p, star_n + 1));
+            p = star_p;
+            n = star_n + 1;
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == b'*')
 }
 
 /// Specifies the seed for the random number generator used during instrumentation.
@@ -109,15 +307,6 @@ pub enum Seed {
     /// A user-provided static seed will be used.
     Static(u32),
 }
-/// A global, mutable static array to hold the coverage map.
-///
-/// # Safety
-///
-/// This is a raw pointer to a mutable static memory region, which is inherently `unsafe`.
-/// It is used as a shared memory region between the fuzzer and the instrumented canister.
-/// The `libafl` framework is designed to work with such a mechanism, which is a highly
-/// optimized approach for coverage-guided fuzzing, inspired by AFL.
-pub static mut COVERAGE_MAP: &mut [u8] = &mut [0; AFL_COVERAGE_MAP_SIZE as usize];
 
 /// Instruments the given Wasm bytes for fuzzing.
 ///
@@ -125,28 +314,36 @@ pub static mut COVERAGE_MAP: &mut [u8] = &mut [0; AFL_COVERAGE_MAP_SIZE as usize
 /// coverage tracking, and returns the instrumented Wasm module as a vector of bytes.
 /// The resulting Wasm is validated before being returned.
 ///
+/// The instrumented canister holds its coverage map of [`InstrumentationArgs::map_size`] bytes
+/// (or one byte per probe with sequential probe IDs) itself. The fuzzer-side copy is owned by
+/// the [`FuzzerState`](crate::fuzzer::FuzzerState) and sized to the map exported by the
+/// canister, so this function has no global side effects.
+///
 /// # Arguments
 ///
-/// * `instrumentation_args` - A struct containing the Wasm bytes, history size, and instrumentation seed.
+/// * `instrumentation_args` - A struct containing the Wasm bytes, history size, and
+///   instrumentation seed.
 pub fn instrument_wasm_for_fuzzing(instrumentation_args: InstrumentationArgs) -> Vec<u8> {
     assert!(
         matches!(instrumentation_args.history_size, 1 | 2 | 4 | 8),
         "History size must be 1, 2, 4, or 8"
     );
+    assert!(
+        instrumentation_args
+            .map_size
+            .is_none_or(|size| size.is_power_of_two() && size <= MAX_MAP_SIZE),
+        "Map size must be a power of two of at most 1 GiB"
+    );
+    assert!(
+        !(instrumentation_args.sequential_probe_ids && instrumentation_args.map_size.is_some()),
+        "Map size cannot be set with sequential probe IDs, as the map is sized to the probes"
+    );
     let mut module = Module::parse(&instrumentation_args.wasm_bytes, false, false)
         .expect("Failed to parse module with wirm");
 
     instrument_for_afl(&mut module, &instrumentation_args)
         .expect("Unable to instrument wasm module for AFL");
 
-    // Sorry it has to be this way :(
-    let buf = vec![0u8; AFL_COVERAGE_MAP_SIZE as usize * instrumentation_args.history_size]
-        .into_boxed_slice();
-    let buf: &'static mut [u8] = Box::leak(buf);
-    unsafe {
-        COVERAGE_MAP = buf;
-    }
-
     let instrumented_wasm = module.encode();
 
     validate_wasm(&instrumented_wasm).expect("Wasm is not valid");
@@ -154,19 +351,177 @@ pub fn instrument_wasm_for_fuzzing(instrumentation_args: InstrumentationArgs) ->
     instrumented_wasm
 }
 
+/// The largest supported coverage map size. Probe IDs and map offsets are `i32` constants in
+/// wasm32 modules.
+pub const MAX_MAP_SIZE: usize = 1 << 30;
+
+/// Returns the functions of the given (uninstrumented) Wasm bytes that the `filter` excludes
+/// from instrumentation, as `(function index, name)` pairs.
+///
+/// # Panics
+///
+/// Panics if the Wasm bytes cannot be parsed.
+pub fn skipped_functions(wasm_bytes: &[u8], filter: &FunctionFilter) -> Vec<(u32, Option<String>)> {
+    let module = Module::parse(wasm_bytes, false, false).expect("Failed to parse module with wirm");
+    filtered_functions(&module, filter)
+        .into_iter()
+        .map(|id| (*id, module.functions.get_name(id).clone()))
+        .collect()
+}
+
+/// Returns the local functions of `module` that the `filter` excludes from instrumentation.
+fn filtered_functions(module: &Module<'_>, filter: &FunctionFilter) -> Vec<FunctionID> {
+    if filter.is_empty() {
+        return vec![];
+    }
+    module
+        .functions
+        .iter()
+        .enumerate()
+        .map(|(function_index, _)| FunctionID(function_index as u32))
+        .filter(|&id| {
+            module.functions.is_local(id)
+                && !filter.instruments(module.functions.get_name(id).as_deref())
+        })
+        .collect()
+}
+
+/// The minimum length of a string literal collected by [`extract_dictionary`].
+const MIN_DICTIONARY_STRING_LEN: usize = 4;
+
+/// The maximum length of a string literal collected by [`extract_dictionary`]. Longer runs of
+/// printable bytes are usually several literals laid out back to back, and are skipped.
+const MAX_DICTIONARY_STRING_LEN: usize = 64;
+
+/// Extracts a dictionary of magic values from the given (uninstrumented) Wasm bytes.
+///
+/// The dictionary contains, without duplicates:
+/// 1. The constant operands of comparisons: an `i32.const` or `i64.const` immediately before
+///    an `eq`, `ne`, `lt`, `gt`, `le` or `ge` instruction, or before a `local.get`/`global.get`
+///    that is the other operand. Constants are encoded in little-endian with the fewest bytes
+///    (1, 2, 4 or 8) that represent them, e.g. `i32.const 206` becomes `[206]`. The
+///    constants `-1`, `0` and `1` are skipped.
+/// 2. Runs of printable ASCII of [`MIN_DICTIONARY_STRING_LEN`] to [`MAX_DICTIONARY_STRING_LEN`]
+///    bytes in the data segments, such as method names or error messages.
+///
+/// The fuzzer stores the dictionary as `libafl` `Tokens`, used by the token mutations of the
+/// havoc mutator and by the Candid text and blob mutations.
+pub fn extract_dictionary(wasm_bytes: &[u8]) -> Vec<Vec<u8>> {
+    let module = Module::parse(wasm_bytes, false, false).expect("Failed to parse module with wirm");
+    let mut seen = HashSet::new();
+    let mut tokens = Vec::new();
+    let mut add = |token: Vec<u8>| {
+        if seen.insert(token.clone()) {
+            tokens.push(token);
+        }
+    };
+
+    for function in module.functions.iter() {
+        if let FuncKind::Local(local_function) = function.kind() {
+            comparison_constants(local_function.body.instructions.get_ops(), &mut add);
+        }
+    }
+    for segment in &module.data {
+        segment
+            .data
+            .split(|b| !(b.is_ascii_graphic() || *b == b' '))
+            .filter(|s| (MIN_DICTIONARY_STRING_LEN..=MAX_DICTIONARY_STRING_LEN).contains(&s.len()))
+            .for_each(|s| add(s.to_vec()));
+    }
+    tokens
+}
+
+/// Passes the encoded constant operands of the comparisons in `ops` to `add`, see
+/// [`extract_dictionary`].
+fn comparison_constants(ops: &[Operator], add: &mut impl FnMut(Vec<u8>)) {
+    let constant = |op: &Operator| match op {
+        Operator::I32Const { value } => Some((*value as u32 as u64, 4)),
+        Operator::I64Const { value } => Some((*value as u64, 8)),
+        _ => None,
+    };
+    for (i, op) in ops.iter().enumerate() {
+        if !is_comparison(op) || i == 0 {
+            continue;
+        }
+        let operand = constant(&ops[i - 1]).or_else(|| match (i.checked_sub(2), &ops[i - 1]) {
+            (Some(j), Operator::LocalGet { .. } | Operator::GlobalGet { .. }) => constant(&ops[j]),
+            _ => None,
+        });
+        if let Some(token) = operand.and_then(|(value, width)| encode_constant(value, width)) {
+            add(token);
+        }
+    }
+}
+
+fn is_comparison(op: &Operator) -> bool {
+    matches!(
+        op,
+        Operator::I32Eq
+            | Operator::I32Ne
+            | Operator::I32LtS
+            | Operator::I32LtU
+            | Operator::I32GtS
+            | Operator::I32GtU
+            | Operator::I32LeS
+            | Operator::I32LeU
+            | Operator::I32GeS
+            | Operator::I32GeU
+            | Operator::I64Eq
+            | Operator::I64Ne
+            | Operator::I64LtS
+            | Operator::I64LtU
+            | Operator::I64GtS
+            | Operator::I64GtU
+            | Operator::I64LeS
+            | Operator::I64LeU
+            | Operator::I64GeS
+            | Operator::I64GeU
+    )
+}
+
+/// Encodes the `width`-byte constant `value` in little-endian with the fewest bytes that
+/// represent it. Returns `None` for `-1`, `0` and `1`, which are too common to be useful.
+fn encode_constant(value: u64, width: usize) -> Option<Vec<u8>> {
+    let all_ones = u64::MAX >> (64 - 8 * width);
+    if value <= 1 || value == all_ones {
+        return None;
+    }
+    let len = [1, 2, 4, 8]
+        .into_iter()
+        .find(|len| *len == 8 || value >> (8 * len) == 0)
+        .unwrap()
+        .min(width);
+    Some(value.to_le_bytes()[..len].to_vec())
+}
+
 /// The main orchestration function for applying AFL instrumentation.
 ///
 /// It performs the following steps:
-/// 1. Resolves all required `ic0` imports upfront (before adding local functions).
+/// 1. Resolves all required `ic0` imports upfront (before adding local functions), and the
+///    functions excluded by [`InstrumentationArgs::function_filter`].
 /// 2. Injects global variables required for tracking coverage.
 /// 3. Injects the [`COVERAGE_FN_EXPORT_NAME`] update function to expose the coverage map.
-/// 4. Instruments all functions by inserting calls to a helper function at the
+/// 4. Reserves the coverage map region at the end of memory 0 and hides it from the program.
+/// 5. Instruments all functions by inserting calls to a helper function at the
 ///    start of each function and before each branch instruction.
 ///
+/// With [`InstrumentationArgs::sequential_probe_ids`], the probes are counted upfront to size
+/// the coverage map, and no history globals are injected. With
+/// [`InstrumentationArgs::inline_probes`], step 5 inlines the helper instead of calling it.
+///
 /// When [`InstrumentationArgs::instrument_instruction_count`] is enabled, it additionally:
-/// 5. Imports `ic0.performance_counter` and injects instruction-counting globals.
-/// 6. Wraps each `canister_update` export to read the instruction counter.
-/// 7. Injects the [`INSTRUCTION_COUNT_FN_EXPORT_NAME`] export to retrieve the count.
+/// 6. Imports `ic0.performance_counter` and injects instruction-counting globals.
+/// 7. Wraps each `canister_update` export to read the instruction counter.
+/// 8. Injects the [`INSTRUCTION_COUNT_FN_EXPORT_NAME`] export to retrieve the count.
+///
+/// When [`InstrumentationArgs::instrument_cmplog`] is enabled, it also:
+/// 9. Injects the [`CMPLOG_FN_EXPORT_NAME`] export and the CmpLog helpers.
+///
+/// When [`InstrumentationArgs::instrument_value_profile`] is enabled, it also:
+/// 10. Injects the [`VALUE_PROFILE_FN_EXPORT_NAME`] export and the value-profile helpers.
+///
+/// If either is enabled:
+/// 11. Passes the operands of all comparison sites to the helpers.
 fn instrument_for_afl(
     module: &mut Module<'_>,
     instrumentation_args: &InstrumentationArgs,
@@ -180,19 +535,44 @@ fn instrument_for_afl(
     let (msg_reply_data_append_idx, msg_reply_idx, perf_counter_idx) =
         ensure_ic0_imports(module, is_memory64, inst_count)?;
 
-    let (afl_prev_loc_indices, afl_mem_ptr_idx, instruction_count_globals) = inject_globals(
-        module,
-        instrumentation_args.history_size,
-        is_memory64,
-        inst_count,
-    );
+    // The filter only applies to the functions of the program, so it runs before any
+    // function is injected.
+    let filtered = filtered_functions(module, &instrumentation_args.function_filter);
+    if !instrumentation_args.function_filter.is_empty() {
+        println!(
+            "  -> Skipping {} function(s) excluded by the function filter.",
+            filtered.len()
+        );
+    }
+
+    // Sequential probes index the map directly, so it has one entry per probe and no history.
+    let (history_size, probe_ids, map_size) = if instrumentation_args.sequential_probe_ids {
+        let probes = probe_sites(module, &filtered).len();
+        println!("  -> Numbering {probes} coverage probe(s) sequentially.");
+        (0, ProbeIds::Sequential, probes.max(1) as i64)
+    } else {
+        let history_size = instrumentation_args.history_size;
+        let map_size = instrumentation_args
+            .map_size
+            .map_or(AFL_COVERAGE_MAP_SIZE as i64 * history_size as i64, |size| {
+                size as i64
+            });
+        let probe_ids = ProbeIds::Random {
+            seed: instrumentation_args.seed,
+            map_size: map_size as i32,
+        };
+        (history_size, probe_ids, map_size)
+    };
+
+    let (afl_prev_loc_indices, afl_mem_ptr_idx, instruction_count_globals) =
+        inject_globals(module, history_size, is_memory64, inst_count);
     println!(
         "  -> Injected globals: prev_locs @ indices {afl_prev_loc_indices:?}, mem_ptr @ index {afl_mem_ptr_idx:?}"
     );
 
-    inject_afl_coverage_export(
+    let coverage_export_id = inject_afl_coverage_export(
         module,
-        instrumentation_args.history_size,
+        map_size,
         afl_mem_ptr_idx,
         msg_reply_data_append_idx,
         msg_reply_idx,
@@ -200,8 +580,22 @@ fn instrument_for_afl(
     )?;
     println!("  -> Injected `canister_update __export_coverage_for_afl` function.");
 
+    let mut skip_function_ids: HashSet<FunctionID> = filtered.into_iter().collect();
+    // A probe in the export would get an ID past the end of the map sized to the probes.
+    if matches!(probe_ids, ProbeIds::Sequential) {
+        skip_function_ids.insert(coverage_export_id);
+    }
+
+    let buffers = ReservedBuffers::from(instrumentation_args);
+    let memory_function_ids =
+        reserve_coverage_memory(module, map_size, buffers, afl_mem_ptr_idx, is_memory64);
+    skip_function_ids.extend(memory_function_ids);
+    println!(
+        "  -> Reserved {} page(s) at the end of memory 0 for the coverage map.",
+        reserved_pages(map_size, buffers)
+    );
+
     // Instruction count wrapper functions (injected before branch instrumentation)
-    let mut skip_function_ids = HashSet::new();
 
     let call_count_global = if let Some((ic_global, call_count_global)) = instruction_count_globals
     {
@@ -211,7 +605,8 @@ fn instrument_for_afl(
         );
         println!("  -> Ensured ic0.performance_counter import @ {perf_counter_idx:?}");
 
-        let cost_per_afl_call = compute_cost_per_afl_call(instrumentation_args.history_size);
+        let cost_per_afl_call =
+            compute_cost_per_afl_call(history_size, instrumentation_args.inline_probes);
         println!("  -> Computed AFL instrumentation cost per call: {cost_per_afl_call}");
 
         let wrapper_ids = inject_method_wrappers(
@@ -231,7 +626,7 @@ fn instrument_for_afl(
 
         let export_fn_id = inject_instruction_count_export(
             module,
-            instrumentation_args.history_size,
+            map_size,
             afl_mem_ptr_idx,
             ic_global,
             msg_reply_data_append_idx,
@@ -246,11 +641,63 @@ fn instrument_for_afl(
         None
     };
 
+    let mut hooks = Vec::new();
+    if instrumentation_args.instrument_cmplog {
+        let cmplog_offset = buffers.cmplog_offset(map_size);
+        let export_fn_id = inject_buffer_export(
+            module,
+            CMPLOG_FN_EXPORT_NAME,
+            cmplog_offset,
+            CMPLOG_MAP_SIZE,
+            afl_mem_ptr_idx,
+            msg_reply_data_append_idx,
+            msg_reply_idx,
+            is_memory64,
+        );
+        println!("  -> Injected `canister_update {CMPLOG_FN_EXPORT_NAME}` function.");
+        let helpers = inject_cmplog_helpers(module, cmplog_offset, afl_mem_ptr_idx, is_memory64);
+        skip_function_ids.extend([export_fn_id, helpers.0, helpers.1]);
+        hooks.push(ComparisonHook {
+            helpers,
+            entries: CMPLOG_MAP_ENTRIES as usize,
+        });
+    }
+    if instrumentation_args.instrument_value_profile {
+        let value_profile_offset = buffers.value_profile_offset(map_size);
+        let export_fn_id = inject_buffer_export(
+            module,
+            VALUE_PROFILE_FN_EXPORT_NAME,
+            value_profile_offset,
+            VALUE_PROFILE_MAP_SIZE,
+            afl_mem_ptr_idx,
+            msg_reply_data_append_idx,
+            msg_reply_idx,
+            is_memory64,
+        );
+        println!("  -> Injected `canister_update {VALUE_PROFILE_FN_EXPORT_NAME}` function.");
+        let helpers = inject_value_profile_helpers(
+            module,
+            value_profile_offset,
+            afl_mem_ptr_idx,
+            is_memory64,
+        );
+        skip_function_ids.extend([export_fn_id, helpers.0, helpers.1]);
+        hooks.push(ComparisonHook {
+            helpers,
+            entries: VALUE_PROFILE_MAP_SIZE as usize,
+        });
+    }
+    if !hooks.is_empty() {
+        let sites = instrument_comparisons(module, &hooks, is_memory64, &skip_function_ids);
+        println!("  -> Instrumented {sites} comparison(s).");
+    }
+
     instrument_branches(
         module,
         &afl_prev_loc_indices,
         afl_mem_ptr_idx,
-        instrumentation_args.seed,
+        probe_ids,
+        instrumentation_args.inline_probes,
         is_memory64,
         &skip_function_ids,
         call_count_global,
@@ -265,7 +712,9 @@ fn instrument_for_afl(
 /// **Always injected:**
 /// - `__afl_prev_loc_N`: A set of `history_size` mutable i32 (or i64 for wasm64) globals to store the IDs
 ///   of the previously executed basic blocks.
-/// - `__afl_mem_ptr`: An immutable i32 (or i64 for wasm64) global that holds the base address (0) of the coverage map.
+/// - `__afl_mem_ptr`: A mutable i32 (or i64 for wasm64) global that holds the base address of the coverage map.
+///   It is set by the injected start function and updated whenever the program grows its memory
+///   (see [`reserve_coverage_memory`]).
 ///
 /// **When `instrument_instruction_count` is true, also injects:**
 /// - `__afl_instruction_count`: mutable i64 global storing the instruction count after method execution.
@@ -296,7 +745,7 @@ fn inject_globals(
     let afl_mem_ptr_idx = module.add_global(
         InitExpr::new(vec![InitInstr::Value(init_val)]),
         ptr_type,
-        false,
+        true,
         false,
     );
 
@@ -331,35 +780,39 @@ fn inject_globals(
 /// retrieve the coverage map and reset it. It uses the `ic0.msg_reply_data_append` and
 /// `ic0.msg_reply` System API calls to send the contents of the coverage map
 /// back to the caller.
+///
+/// # Returns
+///
+/// The `FunctionID` of the export.
 fn inject_afl_coverage_export<'a>(
     module: &mut Module<'a>,
-    history_size: usize,
+    map_size: i64,
     afl_mem_ptr_idx: GlobalID,
     msg_reply_data_append_idx: FunctionID,
     msg_reply_idx: FunctionID,
     is_memory64: bool,
-) -> Result<()> {
+) -> Result<FunctionID> {
     let mut func_builder = FunctionBuilder::new(&[], &[]);
 
     if is_memory64 {
         func_builder
             .global_get(afl_mem_ptr_idx)
-            .i64_const(AFL_COVERAGE_MAP_SIZE as i64 * history_size as i64)
+            .i64_const(map_size)
             .call(msg_reply_data_append_idx)
             .call(msg_reply_idx)
             .global_get(afl_mem_ptr_idx)
             .i32_const(0)
-            .i64_const(AFL_COVERAGE_MAP_SIZE as i64 * history_size as i64)
+            .i64_const(map_size)
             .memory_fill(0);
     } else {
         func_builder
             .global_get(afl_mem_ptr_idx)
-            .i32_const(AFL_COVERAGE_MAP_SIZE * history_size as i32)
+            .i32_const(map_size as i32)
             .call(msg_reply_data_append_idx)
             .call(msg_reply_idx)
             .global_get(afl_mem_ptr_idx)
             .i32_const(0)
-            .i32_const(AFL_COVERAGE_MAP_SIZE * history_size as i32)
+            .i32_const(map_size as i32)
             .memory_fill(0);
     }
 
@@ -369,136 +822,735 @@ fn inject_afl_coverage_export<'a>(
         .exports
         .add_export_func(export_name, coverage_function_id.0);
 
-    Ok(())
+    Ok(coverage_function_id)
 }
 
-/// Instruments all local functions in the module to track code coverage.
-///
-/// This function iterates through every instruction in every function body.
-/// It inserts a call to a helper instrumentation function at the beginning of the function
-/// and before each branch-like instruction (`If`, `Else`, `Block`, `Loop`, `Br`, `BrIf`, `BrTable`).
-/// This ensures that every basic block is instrumented.
-fn instrument_branches(
-    module: &mut Module<'_>,
-    afl_prev_loc_indices: &[GlobalID],
-    afl_mem_ptr_idx: GlobalID,
-    seed: Seed,
-    is_memory64: bool,
-    skip_function_ids: &HashSet<FunctionID>,
-    call_count_global: Option<GlobalID>,
-) {
-    let instrumentation_function = afl_instrumentation_slice(
-        module,
-        afl_prev_loc_indices,
-        afl_mem_ptr_idx,
-        is_memory64,
-        call_count_global,
-    );
-
-    let seed = match seed {
-        Seed::Random => rand::rng().next_u32(),
-        Seed::Static(s) => s,
-    };
-    println!("The seed used for instrumentation is {seed}");
-    let mut rng = rand::rngs::StdRng::seed_from_u64(seed as u64);
-
-    let mut create_instrumentation_ops = |ops: &mut Vec<Operator>| {
-        let curr_location =
-            rng.random_range(0..AFL_COVERAGE_MAP_SIZE * afl_prev_loc_indices.len() as i32);
+/// Number of unused Wasm pages at the start of the reserved region, between the end of the
+/// program's memory and the coverage map. Writes that overflow the program's memory by less
+/// than a page land here instead of corrupting the coverage map.
+const GUARD_PAGES: i64 = 1;
+
+/// Size in bytes of the scratch area that follows the coverage map in the reserved region.
+/// It is used by the instruction count export to stage its 8-byte reply.
+const SCRATCH_SIZE: i64 = 8;
+
+/// The optional buffers stored in the reserved region, in this order, after the coverage
+/// map and the scratch area.
+#[derive(Clone, Copy, Debug, Default)]
+struct ReservedBuffers {
+    /// The CmpLog buffer of [`CMPLOG_MAP_SIZE`] bytes.
+    cmplog: bool,
+    /// The value-profile map of [`VALUE_PROFILE_MAP_SIZE`] bytes.
+    value_profile: bool,
+}
 
-        if is_memory64 {
-            ops.push(Operator::I64Const {
-                value: curr_location as i64,
-            });
-        } else {
-            ops.push(Operator::I32Const {
-                value: curr_location as i32,
-            });
+impl From<&InstrumentationArgs> for ReservedBuffers {
+    fn from(args: &InstrumentationArgs) -> Self {
+        Self {
+            cmplog: args.instrument_cmplog,
+            value_profile: args.instrument_value_profile,
         }
-        ops.push(Operator::Call {
-            function_index: instrumentation_function.0,
-        });
-    };
+    }
+}
 
-    for (function_index, function) in module.functions.iter_mut().enumerate() {
-        let func_id = FunctionID(function_index as u32);
-        if matches!(function.kind(), FuncKind::Local(_))
-            && func_id != instrumentation_function
-            && !skip_function_ids.contains(&func_id)
-        {
-            let local_function = function.unwrap_local_mut();
-            let mut new_instructions = Vec::with_capacity(local_function.body.num_instructions * 2);
+impl ReservedBuffers {
+    /// Returns the offset of the CmpLog buffer in the reserved region, after the coverage map
+    /// and the scratch area.
+    fn cmplog_offset(self, map_size: i64) -> i64 {
+        map_size + SCRATCH_SIZE
+    }
 
-            create_instrumentation_ops(&mut new_instructions);
+    /// Returns the offset of the value-profile map in the reserved region, after the CmpLog
+    /// buffer if there is one.
+    fn value_profile_offset(self, map_size: i64) -> i64 {
+        let cmplog_bytes = if self.cmplog {
+            CMPLOG_MAP_SIZE as i64
+        } else {
+            0
+        };
+        self.cmplog_offset(map_size) + cmplog_bytes
+    }
 
-            for instruction in local_function.body.instructions.get_ops() {
-                match instruction {
-                    Operator::Block { .. }
-                    | Operator::Loop { .. }
-                    | Operator::If { .. }
-                    | Operator::Else => {
-                        new_instructions.push(instruction.clone());
-                        create_instrumentation_ops(&mut new_instructions);
-                    }
-                    Operator::Br { .. }
-                    | Operator::BrIf { .. }
-                    | Operator::BrTable { .. }
-                    | Operator::Return => {
-                        create_instrumentation_ops(&mut new_instructions);
-                        new_instructions.push(instruction.clone());
-                    }
-                    _ => new_instructions.push(instruction.clone()),
-                }
-            }
-            // offsets are set to zero, as we are not interested in preserving them.
-            local_function.body.instructions = Instructions::new(
-                new_instructions.iter().map(|i| (i.clone(), 0)).collect(),
-                0,
-                false,
-            );
-        }
+    /// Returns the size of the reserved region in bytes.
+    fn end(self, map_size: i64) -> i64 {
+        let value_profile_bytes = if self.value_profile {
+            VALUE_PROFILE_MAP_SIZE as i64
+        } else {
+            0
+        };
+        self.value_profile_offset(map_size) + value_profile_bytes
     }
 }
 
-/// Creates and injects a helper function that contains the core AFL instrumentation logic.
+/// Returns the number of Wasm pages reserved at the end of memory 0 for the guard page, the
+/// coverage map (of `map_size` bytes), the scratch area and the `buffers`.
+fn reserved_pages(map_size: i64, buffers: ReservedBuffers) -> i64 {
+    GUARD_PAGES + (buffers.end(map_size) + WASM_PAGE_SIZE - 1) / WASM_PAGE_SIZE
+}
+
+/// Reserves a region at the end of memory 0 for the coverage map and hides it from the program.
 ///
-/// This function will be called from instrumented locations (start of functions, before branches).
-/// It implements the standard AFL coverage tracking mechanism:
-/// ```text
-///   curr_location = <COMPILE_TIME_RANDOM>;
-///   key = curr_location ^ prev_loc[0] ^ ... ^ prev_loc[history_size-1];
-///   shared_mem[key]++;
-///   prev_loc[history_size-1] = prev_loc[history_size-2] >> 1;
-///   ...
-///   prev_loc[0] = curr_location >> 1;
-/// ```
-/// The generated function takes the current location (`curr_location`) as an i32 (or i64 for wasm64)
-/// parameter and is added to the module.
+/// The following functions are injected:
+/// - A start function that grows memory 0 by [`reserved_pages`] pages and stores the address
+///   of the coverage map, right after the [`GUARD_PAGES`], in `__afl_mem_ptr`. An existing
+///   start function is called afterwards, so the program never runs before the region is
+///   reserved.
+/// - A `memory.size` replacement returning the real size minus the reserved pages.
+/// - A `memory.grow` replacement that grows the real memory, moves the reserved region to
+///   the new end of memory, zeroes the pages previously occupied by it and returns the old
+///   size as seen by the program:
+///   ```text
+///   old = memory.grow(delta); if old == -1 { return -1 }
+///   old = old - reserved
+///   base = mem_ptr - GUARD * PAGE
+///   memory.copy(dst: (old + delta) * PAGE, src: base, len: reserved * PAGE)
+///   memory.fill(dst: base, 0, len: min(delta, reserved) * PAGE)
+///   mem_ptr = (old + delta + GUARD) * PAGE
+///   return old
+///   ```
+///
+/// All `memory.size` and `memory.grow` instructions on memory 0 in the existing functions are
+/// replaced by calls to these helpers. If the module has no memory, an empty one is added; a
+/// declared maximum is raised by the number of reserved pages.
 ///
 /// # Returns
 ///
-/// The `FunctionID` of the newly created helper function.
-fn afl_instrumentation_slice(
+/// The `FunctionID`s of the injected functions, which must not be instrumented.
+fn reserve_coverage_memory(
     module: &mut Module<'_>,
-    afl_prev_loc_indices: &[GlobalID],
+    map_size: i64,
+    buffers: ReservedBuffers,
     afl_mem_ptr_idx: GlobalID,
     is_memory64: bool,
-    call_count_global: Option<GlobalID>,
-) -> FunctionID {
+) -> Vec<FunctionID> {
+    let reserved = reserved_pages(map_size, buffers);
+    let page_shift = WASM_PAGE_SIZE.trailing_zeros() as i64;
+
+    match module.memories.get_mut(MemoryID(0)) {
+        Some(memory) => {
+            if let Some(maximum) = memory.ty.maximum.as_mut() {
+                let limit = if memory.ty.memory64 {
+                    u64::MAX
+                } else {
+                    1 << (32 - page_shift)
+                };
+                *maximum = (*maximum + reserved as u64).min(limit);
+            }
+        }
+        None => {
+            module.add_local_memory(MemoryType {
+                memory64: false,
+                shared: false,
+                initial: 0,
+                maximum: None,
+                page_size_log2: None,
+            });
+        }
+    }
+
+    let ptr_type = if is_memory64 {
+        DataType::I64
+    } else {
+        DataType::I32
+    };
+    let original_start = module.start;
+
+    // Start function: mem_ptr = (memory.grow(reserved) + GUARD) * PAGE
+    let mut start_builder = FunctionBuilder::new(&[], &[]);
+    let old_size = start_builder.add_local(ptr_type);
     if is_memory64 {
-        let mut func_builder = FunctionBuilder::new(&[DataType::I64], &[]);
-        let curr_location = LocalID(0);
-        let afl_local_idx = func_builder.add_local(DataType::I64);
+        start_builder
+            .i64_const(reserved)
+            .memory_grow(0)
+            .local_tee(old_size)
+            .i64_const(-1)
+            .i64_eq();
+    } else {
+        start_builder
+            .i32_const(reserved as i32)
+            .memory_grow(0)
+            .local_tee(old_size)
+            .i32_const(-1)
+            .i32_eq();
+    }
+    start_builder.if_stmt(BlockType::Empty).unreachable().end();
+    if is_memory64 {
+        start_builder
+            .local_get(old_size)
+            .i64_const(GUARD_PAGES)
+            .i64_add()
+            .i64_const(page_shift)
+            .i64_shl()
+            .global_set(afl_mem_ptr_idx);
+    } else {
+        start_builder
+            .local_get(old_size)
+            .i32_const(GUARD_PAGES as i32)
+            .i32_add()
+            .i32_const(page_shift as i32)
+            .i32_shl()
+            .global_set(afl_mem_ptr_idx);
+    }
+    if let Some(original_start) = original_start {
+        start_builder.call(original_start);
+    }
+    let start_fn = start_builder.finish_module(module);
+    module.start = Some(start_fn);
 
-        func_builder.local_get(curr_location);
-        for &prev_loc_idx in afl_prev_loc_indices {
-            func_builder.global_get(prev_loc_idx).i64_xor();
-        }
+    // memory.size replacement: memory.size - reserved
+    let mut size_builder = FunctionBuilder::new(&[], &[ptr_type]);
+    if is_memory64 {
+        size_builder.memory_size(0).i64_const(reserved).i64_sub();
+    } else {
+        size_builder
+            .memory_size(0)
+            .i32_const(reserved as i32)
+            .i32_sub();
+    }
+    let size_fn = size_builder.finish_module(module);
 
-        func_builder
-            .global_get(afl_mem_ptr_idx)
+    // memory.grow replacement, see the pseudocode above.
+    let mut grow_builder = FunctionBuilder::new(&[ptr_type], &[ptr_type]);
+    let delta = LocalID(0);
+    let old = grow_builder.add_local(ptr_type);
+    if is_memory64 {
+        grow_builder
+            .local_get(delta)
+            .memory_grow(0)
+            .local_tee(old)
+            .i64_const(-1)
+            .i64_eq()
+            .if_stmt(BlockType::Empty)
+            .i64_const(-1)
+            .return_stmt()
+            .end()
+            .local_get(old)
+            .i64_const(reserved)
+            .i64_sub()
+            .local_set(old)
+            // Move the reserved region to the new end of memory
+            .local_get(old)
+            .local_get(delta)
             .i64_add()
-            .local_tee(afl_local_idx)
+            .i64_const(page_shift)
+            .i64_shl()
+            .global_get(afl_mem_ptr_idx)
+            .i64_const(GUARD_PAGES << page_shift)
+            .i64_sub()
+            .i64_const(reserved << page_shift)
+            .memory_copy(0, 0)
+            // Zero the pages handed over to the program
+            .global_get(afl_mem_ptr_idx)
+            .i64_const(GUARD_PAGES << page_shift)
+            .i64_sub()
+            .i32_const(0)
+            .local_get(delta)
+            .i64_const(reserved)
+            .local_get(delta)
+            .i64_const(reserved)
+            .i64_lt_unsigned()
+            .select()
+            .i64_const(page_shift)
+            .i64_shl()
+            .memory_fill(0)
+            // Update the map pointer
+            .local_get(old)
+            .local_get(delta)
+            .i64_add()
+            .i64_const(GUARD_PAGES)
+            .i64_add()
+            .i64_const(page_shift)
+            .i64_shl()
+            .global_set(afl_mem_ptr_idx)
+            .local_get(old);
+    } else {
+        grow_builder
+            .local_get(delta)
+            .memory_grow(0)
+            .local_tee(old)
+            .i32_const(-1)
+            .i32_eq()
+            .if_stmt(BlockType::Empty)
+            .i32_const(-1)
+            .return_stmt()
+            .end()
+            .local_get(old)
+            .i32_const(reserved as i32)
+            .i32_sub()
+            .local_set(old)
+            // Move the reserved region to the new end of memory
+            .local_get(old)
+            .local_get(delta)
+            .i32_add()
+            .i32_const(page_shift as i32)
+            .i32_shl()
+            .global_get(afl_mem_ptr_idx)
+            .i32_const((GUARD_PAGES << page_shift) as i32)
+            .i32_sub()
+            .i32_const((reserved << page_shift) as i32)
+            .memory_copy(0, 0)
+            // Zero the pages handed over to the program
+            .global_get(afl_mem_ptr_idx)
+            .i32_const((GUARD_PAGES << page_shift) as i32)
+            .i32_sub()
+            .i32_const(0)
+            .local_get(delta)
+            .i32_const(reserved as i32)
+            .local_get(delta)
+            .i32_const(reserved as i32)
+            .i32_lt_unsigned()
+            .select()
+            .i32_const(page_shift as i32)
+            .i32_shl()
+            .memory_fill(0)
+            // Update the map pointer
+            .local_get(old)
+            .local_get(delta)
+            .i32_add()
+            .i32_const(GUARD_PAGES as i32)
+            .i32_add()
+            .i32_const(page_shift as i32)
+            .i32_shl()
+            .global_set(afl_mem_ptr_idx)
+            .local_get(old);
+    }
+    let grow_fn = grow_builder.finish_module(module);
+
+    let injected = vec![start_fn, size_fn, grow_fn];
+    for (function_index, function) in module.functions.iter_mut().enumerate() {
+        let func_id = FunctionID(function_index as u32);
+        if !matches!(function.kind(), FuncKind::Local(_)) || injected.contains(&func_id) {
+            continue;
+        }
+        let local_function = function.unwrap_local_mut();
+        let uses_memory_ops = local_function.body.instructions.get_ops().iter().any(|op| {
+            matches!(
+                op,
+                Operator::MemorySize { mem: 0 } | Operator::MemoryGrow { mem: 0 }
+            )
+        });
+        if !uses_memory_ops {
+            continue;
+        }
+        let new_instructions: Vec<_> = local_function
+            .body
+            .instructions
+            .get_ops()
+            .iter()
+            .map(|op| match op {
+                Operator::MemorySize { mem: 0 } => Operator::Call {
+                    function_index: *size_fn,
+                },
+                Operator::MemoryGrow { mem: 0 } => Operator::Call {
+                    function_index: *grow_fn,
+                },
+                _ => op.clone(),
+            })
+            .collect();
+        local_function.body.instructions = Instructions::new(
+            new_instructions.into_iter().map(|i| (i, 0)).collect(),
+            0,
+            false,
+        );
+    }
+
+    injected
+}
+
+/// Where [`instrument_branches`] places the probe of an instruction.
+enum ProbePlacement {
+    /// The probe runs right after the instruction, at the start of the block it opens.
+    After,
+    /// The probe runs right before the instruction, which leaves the current block.
+    Before,
+}
+
+/// Returns where the probe of `op` is placed, or `None` if it gets no probe.
+fn probe_placement(op: &Operator) -> Option<ProbePlacement> {
+    match op {
+        Operator::Block { .. } | Operator::Loop { .. } | Operator::If { .. } | Operator::Else => {
+            Some(ProbePlacement::After)
+        }
+        Operator::Br { .. }
+        | Operator::BrIf { .. }
+        | Operator::BrTable { .. }
+        | Operator::Return => Some(ProbePlacement::Before),
+        _ => None,
+    }
+}
+
+/// Returns the coverage probes of the local functions of `module` that are not in `skip`, as
+/// `(function, instruction index)` pairs, in the order [`instrument_branches`] places them.
+///
+/// Every function gets a probe at its first instruction, and every instruction with a
+/// [`probe_placement`] gets one. The position of a probe in the returned list is its ID when
+/// probes are numbered sequentially.
+fn probe_sites(module: &Module<'_>, skip: &[FunctionID]) -> Vec<(FunctionID, usize)> {
+    let mut sites = Vec::new();
+    for (function_index, function) in module.functions.iter().enumerate() {
+        let func_id = FunctionID(function_index as u32);
+        if let FuncKind::Local(local_function) = function.kind()
+            && !skip.contains(&func_id)
+        {
+            sites.push((func_id, 0));
+            sites.extend(
+                local_function
+                    .body
+                    .instructions
+                    .get_ops()
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, op)| probe_placement(op).is_some())
+                    .map(|(index, _)| (func_id, index)),
+            );
+        }
+    }
+    sites
+}
+
+/// Returns the coverage probes of the given (uninstrumented) Wasm bytes when instrumented
+/// with [`InstrumentationArgs::sequential_probe_ids`] and the given `filter`.
+///
+/// The probe with ID `i`, i.e. entry `i` of the coverage map, is described by entry `i` of
+/// the returned list as a `(function index, instruction offset)` pair. The instruction offset
+/// is the byte offset of the instruction the probe is placed at, relative to the first
+/// instruction of the function body (after its local declarations): the first instruction
+/// for the entry probe of the function, the `block`, `loop`, `if` or `else` the probe
+/// follows, or the branch it precedes.
+///
+/// # Panics
+///
+/// Panics if the Wasm bytes cannot be parsed.
+pub fn probe_table(wasm_bytes: &[u8], filter: &FunctionFilter) -> Vec<(u32, usize)> {
+    let module = Module::parse(wasm_bytes, false, true).expect("Failed to parse module with wirm");
+    probe_sites(&module, &filtered_functions(&module, filter))
+        .into_iter()
+        .map(|(func_id, index)| {
+            let offset = module
+                .functions
+                .unwrap_local(func_id)
+                .body
+                .instructions
+                .lookup_pc_offset_for(index)
+                .expect("Instruction offsets are recorded when parsing");
+            (*func_id, offset)
+        })
+        .collect()
+}
+
+/// How [`instrument_branches`] assigns IDs to the coverage probes.
+#[derive(Clone, Copy, Debug)]
+enum ProbeIds {
+    /// Each probe gets a random ID in `0..map_size`, drawn from a generator with the given
+    /// seed. Probes may collide. The map size is a power of two, so the XOR of IDs and their
+    /// shifted history stays within the map.
+    Random { seed: Seed, map_size: i32 },
+    /// Probes are numbered `0, 1, 2, ...` in the order of [`probe_sites`].
+    Sequential,
+}
+
+/// Instruments all local functions in the module to track code coverage.
+///
+/// This function iterates through every instruction in every function body.
+/// It inserts a call to a helper instrumentation function at the beginning of the function
+/// and before each branch-like instruction (`If`, `Else`, `Block`, `Loop`, `Br`, `BrIf`, `BrTable`).
+/// This ensures that every basic block is instrumented.
+///
+/// With `inline_probes`, no helper is injected and the helper body is inlined at every probe
+/// instead (see [`inline_probe_ops`]).
+#[allow(clippy::too_many_arguments)]
+fn instrument_branches(
+    module: &mut Module<'_>,
+    afl_prev_loc_indices: &[GlobalID],
+    afl_mem_ptr_idx: GlobalID,
+    probe_ids: ProbeIds,
+    inline_probes: bool,
+    is_memory64: bool,
+    skip_function_ids: &HashSet<FunctionID>,
+    call_count_global: Option<GlobalID>,
+) {
+    let instrumentation_function = (!inline_probes).then(|| {
+        afl_instrumentation_slice(
+            module,
+            afl_prev_loc_indices,
+            afl_mem_ptr_idx,
+            is_memory64,
+            call_count_global,
+        )
+    });
+    let ptr_type = if is_memory64 {
+        DataType::I64
+    } else {
+        DataType::I32
+    };
+
+    let mut rng = match probe_ids {
+        ProbeIds::Random { seed, map_size } => {
+            let seed = match seed {
+                Seed::Random => rand::rng().next_u32(),
+                Seed::Static(s) => s,
+            };
+            println!("The seed used for instrumentation is {seed}");
+            Some((rand::rngs::StdRng::seed_from_u64(seed as u64), map_size))
+        }
+        ProbeIds::Sequential => None,
+    };
+    let mut next_id = 0;
+
+    let mut create_instrumentation_ops = |ops: &mut Vec<Operator>, address: Option<LocalID>| {
+        let curr_location = match &mut rng {
+            Some((rng, map_size)) => rng.random_range(0..*map_size),
+            None => {
+                next_id += 1;
+                next_id - 1
+            }
+        };
+
+        let Some(instrumentation_function) = instrumentation_function else {
+            inline_probe_ops(
+                ops,
+                curr_location,
+                afl_prev_loc_indices,
+                afl_mem_ptr_idx,
+                address,
+                is_memory64,
+                call_count_global,
+            );
+            return;
+        };
+        if is_memory64 {
+            ops.push(Operator::I64Const {
+                value: curr_location as i64,
+            });
+        } else {
+            ops.push(Operator::I32Const {
+                value: curr_location as i32,
+            });
+        }
+        ops.push(Operator::Call {
+            function_index: instrumentation_function.0,
+        });
+    };
+
+    for (function_index, function) in module.functions.iter_mut().enumerate() {
+        let func_id = FunctionID(function_index as u32);
+        if matches!(function.kind(), FuncKind::Local(_))
+            && Some(func_id) != instrumentation_function
+            && !skip_function_ids.contains(&func_id)
+        {
+            let local_function = function.unwrap_local_mut();
+            // Inline probes with a history compute the map address once and keep it in a local.
+            let address = (inline_probes && !afl_prev_loc_indices.is_empty())
+                .then(|| local_function.add_local(ptr_type));
+            let mut new_instructions = Vec::with_capacity(local_function.body.num_instructions * 2);
+
+            create_instrumentation_ops(&mut new_instructions, address);
+
+            for instruction in local_function.body.instructions.get_ops() {
+                match probe_placement(instruction) {
+                    Some(ProbePlacement::After) => {
+                        new_instructions.push(instruction.clone());
+                        create_instrumentation_ops(&mut new_instructions, address);
+                    }
+                    Some(ProbePlacement::Before) => {
+                        create_instrumentation_ops(&mut new_instructions, address);
+                        new_instructions.push(instruction.clone());
+                    }
+                    None => new_instructions.push(instruction.clone()),
+                }
+            }
+            // offsets are set to zero, as we are not interested in preserving them.
+            local_function.body.instructions = Instructions::new(
+                new_instructions.iter().map(|i| (i.clone(), 0)).collect(),
+                0,
+                false,
+            );
+        }
+    }
+}
+
+/// Pushes the body of the [`afl_instrumentation_slice`] helper for `curr_location` to `ops`,
+/// for a probe that does not call the helper. Since `curr_location` is a constant, the new
+/// previous location is folded, and the map is indexed through the `address` local:
+/// ```text
+///   address = mem_ptr + (curr_location ^ prev_loc[0] ^ ... ^ prev_loc[history_size-1]);
+///   address[0]++;
+///   prev_loc[history_size-1] = prev_loc[history_size-2] >> 1;
+///   ...
+///   prev_loc[0] = <curr_location >> 1>;
+/// ```
+/// With `history_size == 1`, this is 13 instructions and there is no history to shift.
+/// Without history, as for sequential probe IDs, the location is a constant offset from
+/// `mem_ptr` and there is no `address` local:
+/// ```text
+///   global.get mem_ptr; global.get mem_ptr; i32.load8_u offset=<curr_location>
+///   i32.const 1; i32.add; i32.store8 offset=<curr_location>
+/// ```
+/// The call counter is incremented as in the helper. See [`compute_cost_per_afl_call`] for
+/// the costs.
+fn inline_probe_ops(
+    ops: &mut Vec<Operator<'_>>,
+    curr_location: i32,
+    afl_prev_loc_indices: &[GlobalID],
+    afl_mem_ptr_idx: GlobalID,
+    address: Option<LocalID>,
+    is_memory64: bool,
+    call_count_global: Option<GlobalID>,
+) {
+    let memarg = |offset: i32| MemArg {
+        offset: offset as u64,
+        align: 0,
+        memory: 0,
+        max_align: 0,
+    };
+    let constant = |value: i32| {
+        if is_memory64 {
+            Operator::I64Const {
+                value: value as i64,
+            }
+        } else {
+            Operator::I32Const { value }
+        }
+    };
+    let (xor, add, shr_u) = if is_memory64 {
+        (Operator::I64Xor, Operator::I64Add, Operator::I64ShrU)
+    } else {
+        (Operator::I32Xor, Operator::I32Add, Operator::I32ShrU)
+    };
+    let load = |offset| {
+        if is_memory64 {
+            Operator::I64Load8U {
+                memarg: memarg(offset),
+            }
+        } else {
+            Operator::I32Load8U {
+                memarg: memarg(offset),
+            }
+        }
+    };
+    let store = |offset| {
+        if is_memory64 {
+            Operator::I64Store8 {
+                memarg: memarg(offset),
+            }
+        } else {
+            Operator::I32Store8 {
+                memarg: memarg(offset),
+            }
+        }
+    };
+    let mem_ptr = Operator::GlobalGet {
+        global_index: *afl_mem_ptr_idx,
+    };
+
+    match address {
+        Some(address) => {
+            ops.push(constant(curr_location));
+            for &prev_loc_idx in afl_prev_loc_indices {
+                ops.extend([
+                    Operator::GlobalGet {
+                        global_index: *prev_loc_idx,
+                    },
+                    xor.clone(),
+                ]);
+            }
+            ops.extend([
+                mem_ptr,
+                add.clone(),
+                Operator::LocalTee {
+                    local_index: *address,
+                },
+                Operator::LocalGet {
+                    local_index: *address,
+                },
+                load(0),
+                constant(1),
+                add,
+                store(0),
+            ]);
+            for i in (1..afl_prev_loc_indices.len()).rev() {
+                ops.extend([
+                    Operator::GlobalGet {
+                        global_index: *afl_prev_loc_indices[i - 1],
+                    },
+                    constant(1),
+                    shr_u.clone(),
+                    Operator::GlobalSet {
+                        global_index: *afl_prev_loc_indices[i],
+                    },
+                ]);
+            }
+            ops.extend([
+                constant(curr_location >> 1),
+                Operator::GlobalSet {
+                    global_index: *afl_prev_loc_indices[0],
+                },
+            ]);
+        }
+        None => ops.extend([
+            mem_ptr.clone(),
+            mem_ptr,
+            load(curr_location),
+            constant(1),
+            add,
+            store(curr_location),
+        ]),
+    }
+
+    if let Some(call_count_idx) = call_count_global {
+        ops.extend([
+            Operator::GlobalGet {
+                global_index: *call_count_idx,
+            },
+            Operator::I64Const { value: 1 },
+            Operator::I64Add,
+            Operator::GlobalSet {
+                global_index: *call_count_idx,
+            },
+        ]);
+    }
+}
+
+/// Creates and injects a helper function that contains the core AFL instrumentation logic.
+///
+/// This function will be called from instrumented locations (start of functions, before branches).
+/// It implements the standard AFL coverage tracking mechanism:
+/// ```text
+///   curr_location = <COMPILE_TIME_RANDOM>;
+///   key = curr_location ^ prev_loc[0] ^ ... ^ prev_loc[history_size-1];
+///   shared_mem[key]++;
+///   prev_loc[history_size-1] = prev_loc[history_size-2] >> 1;
+///   ...
+///   prev_loc[0] = curr_location >> 1;
+/// ```
+/// The generated function takes the current location (`curr_location`) as an i32 (or i64 for wasm64)
+/// parameter and is added to the module. Without `prev_loc` globals, as for sequential probe
+/// IDs, the key is `curr_location` itself and there is no history to shift.
+///
+/// # Returns
+///
+/// The `FunctionID` of the newly created helper function.
+fn afl_instrumentation_slice(
+    module: &mut Module<'_>,
+    afl_prev_loc_indices: &[GlobalID],
+    afl_mem_ptr_idx: GlobalID,
+    is_memory64: bool,
+    call_count_global: Option<GlobalID>,
+) -> FunctionID {
+    if is_memory64 {
+        let mut func_builder = FunctionBuilder::new(&[DataType::I64], &[]);
+        let curr_location = LocalID(0);
+        let afl_local_idx = func_builder.add_local(DataType::I64);
+
+        func_builder.local_get(curr_location);
+        for &prev_loc_idx in afl_prev_loc_indices {
+            func_builder.global_get(prev_loc_idx).i64_xor();
+        }
+
+        func_builder
+            .global_get(afl_mem_ptr_idx)
+            .i64_add()
+            .local_tee(afl_local_idx)
             .local_get(afl_local_idx)
             .i64_load8_u(MemArg {
                 offset: 0,
@@ -528,11 +1580,13 @@ fn afl_instrumentation_slice(
                 .global_set(afl_prev_loc_indices[i]);
         }
 
-        func_builder
-            .local_get(curr_location)
-            .i64_const(1)
-            .i64_shr_unsigned()
-            .global_set(afl_prev_loc_indices[0]);
+        if let Some(&prev_loc_idx) = afl_prev_loc_indices.first() {
+            func_builder
+                .local_get(curr_location)
+                .i64_const(1)
+                .i64_shr_unsigned()
+                .global_set(prev_loc_idx);
+        }
 
         // Increment AFL call counter if instruction counting is enabled
         if let Some(call_count_idx) = call_count_global {
@@ -583,11 +1637,13 @@ fn afl_instrumentation_slice(
                 .global_set(afl_prev_loc_indices[i]);
         }
 
-        func_builder
-            .local_get(curr_location)
-            .i32_const(1)
-            .i32_shr_unsigned()
-            .global_set(afl_prev_loc_indices[0]);
+        if let Some(&prev_loc_idx) = afl_prev_loc_indices.first() {
+            func_builder
+                .local_get(curr_location)
+                .i32_const(1)
+                .i32_shr_unsigned()
+                .global_set(prev_loc_idx);
+        }
 
         // Increment AFL call counter if instruction counting is enabled
         if let Some(call_count_idx) = call_count_global {
@@ -602,50 +1658,425 @@ fn afl_instrumentation_slice(
     }
 }
 
-/// Computes the estimated IC instruction cost per AFL instrumentation call site.
-///
-/// Each instrumentation point consists of a **call site** (inlined at the branch)
-/// and the **helper function body** (called from the site). The total cost is the
-/// sum of both, assuming IC instruction cost = 1 per wasm opcode except `call` = 5.
+/// Injects a `canister_update <export_name>` function.
 ///
-/// ## Call site (2 wasm instructions, IC cost = 6)
-///
-/// ```text
-/// i32/i64.const <random>   ;; cost 1
-/// call $afl_helper          ;; cost 5
-/// ```
-///
-/// ## Helper function body (IC cost = 13 + 6N, where N = history_size)
-///
-/// ```text
-/// ;; XOR block: compute coverage map key          (1 + 2N instructions)
-/// local.get(curr_location)                        ;; 1
-/// N × (global.get(prev_loc[i]) + i32/i64.xor)    ;; 2N
-///
-/// ;; Address computation + load-increment-store   (8 instructions)
-/// global.get(mem_ptr)                             ;; 1
-/// i32/i64.add                                     ;; 1
-/// local.tee(afl_local)                            ;; 1
-/// local.get(afl_local)                            ;; 1
-/// i32/i64.load8_u                                 ;; 1
-/// i32/i64.const(1)                                ;; 1
-/// i32/i64.add                                     ;; 1
-/// i32/i64.store8                                  ;; 1
-///
-/// ;; History shift                                (4N instructions)
-/// (N-1) × (global.get + const(1) + shr_u + global.set)  ;; 4(N-1)
-/// local.get + const(1) + shr_u + global.set              ;; 4
-///
-/// ;; Call counter increment (only when instruction counting is enabled, +4)
-/// global.get(call_count) + i64.const(1) + i64.add + global.set(call_count)
-/// ```
+/// It replies with the `size` bytes at `offset` in the reserved region and clears them, like
+/// the coverage export does with the coverage map.
 ///
-/// ## Total per instrumentation point
+/// # Returns
 ///
-/// `6 + 13 + 6N + 4 = 23 + 6N` (IC instructions, approximate)
+/// The `FunctionID` of the injected function, which must not be instrumented.
+#[allow(clippy::too_many_arguments)]
+fn inject_buffer_export(
+    module: &mut Module<'_>,
+    export_name: &str,
+    offset: i64,
+    size: i32,
+    afl_mem_ptr_idx: GlobalID,
+    msg_reply_data_append_idx: FunctionID,
+    msg_reply_idx: FunctionID,
+    is_memory64: bool,
+) -> FunctionID {
+    let mut func_builder = FunctionBuilder::new(&[], &[]);
+
+    if is_memory64 {
+        func_builder
+            .global_get(afl_mem_ptr_idx)
+            .i64_const(offset)
+            .i64_add()
+            .i64_const(size as i64)
+            .call(msg_reply_data_append_idx)
+            .call(msg_reply_idx)
+            .global_get(afl_mem_ptr_idx)
+            .i64_const(offset)
+            .i64_add()
+            .i32_const(0)
+            .i64_const(size as i64)
+            .memory_fill(0);
+    } else {
+        func_builder
+            .global_get(afl_mem_ptr_idx)
+            .i32_const(offset as i32)
+            .i32_add()
+            .i32_const(size)
+            .call(msg_reply_data_append_idx)
+            .call(msg_reply_idx)
+            .global_get(afl_mem_ptr_idx)
+            .i32_const(offset as i32)
+            .i32_add()
+            .i32_const(0)
+            .i32_const(size)
+            .memory_fill(0);
+    }
+
+    let export_fn_id = func_builder.finish_module(module);
+    module
+        .exports
+        .add_export_func(format!("canister_update {export_name}"), export_fn_id.0);
+    export_fn_id
+}
+
+/// Injects the two helpers recording the operands of an `i32` and an `i64` comparison.
+///
+/// Both take the two operands and the site number (an i32, or i64 for wasm64) and write to
+/// the site's entry of the CmpLog buffer at `cmplog_offset` in the reserved region:
+/// ```text
+/// entry = mem_ptr + site * 16
+/// store_u64(entry + cmplog_offset, a)
+/// store_u64(entry + cmplog_offset + 8, b)
+/// store_u8(mem_ptr + site + cmplog_offset + 16 * CMPLOG_MAP_ENTRIES, width)
+/// ```
+///
+/// # Returns
+///
+/// The `FunctionID`s of the `i32` and the `i64` helper.
+fn inject_cmplog_helpers(
+    module: &mut Module<'_>,
+    cmplog_offset: i64,
+    afl_mem_ptr_idx: GlobalID,
+    is_memory64: bool,
+) -> (FunctionID, FunctionID) {
+    let ptr_type = if is_memory64 {
+        DataType::I64
+    } else {
+        DataType::I32
+    };
+    let memarg = |offset: i64| MemArg {
+        offset: offset as u64,
+        align: 0,
+        memory: 0,
+        max_align: 0,
+    };
+
+    let mut helper = |operand_type: DataType, width: i32| {
+        let mut func_builder = FunctionBuilder::new(&[operand_type, operand_type, ptr_type], &[]);
+        let (a, b, site) = (LocalID(0), LocalID(1), LocalID(2));
+        let entry = func_builder.add_local(ptr_type);
+        let extend = operand_type == DataType::I32;
+
+        func_builder.global_get(afl_mem_ptr_idx).local_get(site);
+        if is_memory64 {
+            func_builder.i64_const(4).i64_shl().i64_add();
+        } else {
+            func_builder.i32_const(4).i32_shl().i32_add();
+        }
+        func_builder.local_tee(entry).local_get(a);
+        if extend {
+            func_builder.inject(Operator::I64ExtendI32U);
+        }
+        func_builder.inject(Operator::I64Store {
+            memarg: memarg(cmplog_offset),
+        });
+        func_builder.local_get(entry).local_get(b);
+        if extend {
+            func_builder.inject(Operator::I64ExtendI32U);
+        }
+        func_builder.inject(Operator::I64Store {
+            memarg: memarg(cmplog_offset + 8),
+        });
+
+        func_builder.global_get(afl_mem_ptr_idx).local_get(site);
+        if is_memory64 {
+            func_builder.i64_add();
+        } else {
+            func_builder.i32_add();
+        }
+        func_builder
+            .i32_const(width)
+            .i32_store8(memarg(cmplog_offset + 16 * CMPLOG_MAP_ENTRIES as i64));
+        func_builder.finish_module(module)
+    };
+
+    (helper(DataType::I32, 4), helper(DataType::I64, 8))
+}
+
+/// Injects the two helpers profiling the operands of an `i32` and an `i64` comparison.
+///
+/// Both take the two operands and the site number (an i32, or i64 for wasm64) and keep the
+/// closest distance seen at the site in the value-profile map at `value_profile_offset` in
+/// the reserved region:
+/// ```text
+/// score = popcnt(!(a ^ b)) + 1
+/// entry = mem_ptr + site + value_profile_offset
+/// if load_u8(entry) < score { store_u8(entry, score) }
+/// ```
+///
+/// # Returns
+///
+/// The `FunctionID`s of the `i32` and the `i64` helper.
+fn inject_value_profile_helpers(
+    module: &mut Module<'_>,
+    value_profile_offset: i64,
+    afl_mem_ptr_idx: GlobalID,
+    is_memory64: bool,
+) -> (FunctionID, FunctionID) {
+    let ptr_type = if is_memory64 {
+        DataType::I64
+    } else {
+        DataType::I32
+    };
+    let memarg = MemArg {
+        offset: value_profile_offset as u64,
+        align: 0,
+        memory: 0,
+        max_align: 0,
+    };
+
+    let mut helper = |operand_type: DataType| {
+        let mut func_builder = FunctionBuilder::new(&[operand_type, operand_type, ptr_type], &[]);
+        let (a, b, site) = (LocalID(0), LocalID(1), LocalID(2));
+        let entry = func_builder.add_local(ptr_type);
+        let score = func_builder.add_local(DataType::I32);
+
+        func_builder.local_get(a).local_get(b);
+        if operand_type == DataType::I32 {
+            func_builder
+                .i32_xor()
+                .i32_const(-1)
+                .i32_xor()
+                .inject(Operator::I32Popcnt);
+        } else {
+            func_builder
+                .i64_xor()
+                .i64_const(-1)
+                .i64_xor()
+                .inject(Operator::I64Popcnt);
+            func_builder.i32_wrap_i64();
+        }
+        func_builder.i32_const(1).i32_add().local_set(score);
+
+        func_builder.global_get(afl_mem_ptr_idx).local_get(site);
+        if is_memory64 {
+            func_builder.i64_add();
+        } else {
+            func_builder.i32_add();
+        }
+        func_builder
+            .local_tee(entry)
+            .i32_load8_u(memarg)
+            .local_get(score)
+            .i32_lt_unsigned()
+            .if_stmt(BlockType::Empty)
+            .local_get(entry)
+            .local_get(score)
+            .i32_store8(memarg)
+            .end();
+        func_builder.finish_module(module)
+    };
+
+    (helper(DataType::I32), helper(DataType::I64))
+}
+
+/// Returns the operand type of `op` if it is a comparison traced by CmpLog: an equality
+/// comparison, or a relational comparison whose result is used by the `next` branch.
+fn traced_comparison(op: &Operator, next: Option<&Operator>) -> Option<DataType> {
+    let branches = matches!(next, Some(Operator::BrIf { .. } | Operator::If { .. }));
+    match op {
+        Operator::I32Eq | Operator::I32Ne => Some(DataType::I32),
+        Operator::I64Eq | Operator::I64Ne => Some(DataType::I64),
+        Operator::I32LtS
+        | Operator::I32LtU
+        | Operator::I32GtS
+        | Operator::I32GtU
+        | Operator::I32LeS
+        | Operator::I32LeU
+        | Operator::I32GeS
+        | Operator::I32GeU
+            if branches =>
+        {
+            Some(DataType::I32)
+        }
+        Operator::I64LtS
+        | Operator::I64LtU
+        | Operator::I64GtS
+        | Operator::I64GtU
+        | Operator::I64LeS
+        | Operator::I64LeU
+        | Operator::I64GeS
+        | Operator::I64GeU
+            if branches =>
+        {
+            Some(DataType::I64)
+        }
+        _ => None,
+    }
+}
+
+/// A pair of helpers called with the operands of every traced comparison, see
+/// [`instrument_comparisons`].
+struct ComparisonHook {
+    /// The helpers for `i32` and `i64` comparisons.
+    helpers: (FunctionID, FunctionID),
+    /// The number of entries of the buffer written by the helpers. The site numbers passed to
+    /// the helpers wrap around at it.
+    entries: usize,
+}
+
+/// Passes the operands of every traced comparison (see [`traced_comparison`]) to the helpers
+/// of the `hooks`, as returned by [`inject_cmplog_helpers`] and
+/// [`inject_value_profile_helpers`].
+///
+/// Each comparison is rewritten to:
+/// ```text
+/// local.set $b; local.tee $a; local.get $b; const <site>; call $helper_1
+/// local.get $a; local.get $b; const <site>; call $helper_2 ...
+/// local.get $a; local.get $b; <comparison>
+/// ```
+/// where `$a` and `$b` are locals added to the function. Sites are numbered in order, modulo
+/// the number of entries of each hook.
+///
+/// # Returns
+///
+/// The number of instrumented comparisons.
+fn instrument_comparisons(
+    module: &mut Module<'_>,
+    hooks: &[ComparisonHook],
+    is_memory64: bool,
+    skip_function_ids: &HashSet<FunctionID>,
+) -> usize {
+    let mut sites = 0;
+    for (function_index, function) in module.functions.iter_mut().enumerate() {
+        let func_id = FunctionID(function_index as u32);
+        if !matches!(function.kind(), FuncKind::Local(_)) || skip_function_ids.contains(&func_id) {
+            continue;
+        }
+        let local_function = function.unwrap_local_mut();
+        let ops = local_function.body.instructions.get_ops().to_vec();
+        let types: Vec<Option<DataType>> = ops
+            .iter()
+            .enumerate()
+            .map(|(i, op)| traced_comparison(op, ops.get(i + 1)))
+            .collect();
+        if types.iter().all(Option::is_none) {
+            continue;
+        }
+        let mut operand_locals = |ty: DataType| {
+            types
+                .contains(&Some(ty))
+                .then(|| (local_function.add_local(ty), local_function.add_local(ty)))
+        };
+        let locals_i32 = operand_locals(DataType::I32);
+        let locals_i64 = operand_locals(DataType::I64);
+
+        let mut new_instructions = Vec::with_capacity(ops.len());
+        for (op, ty) in ops.into_iter().zip(types) {
+            if let Some(ty) = ty {
+                let (a, b) = if ty == DataType::I32 {
+                    locals_i32.unwrap()
+                } else {
+                    locals_i64.unwrap()
+                };
+                new_instructions.push(Operator::LocalSet { local_index: *b });
+                for (i, hook) in hooks.iter().enumerate() {
+                    let helper = if ty == DataType::I32 {
+                        hook.helpers.0
+                    } else {
+                        hook.helpers.1
+                    };
+                    let site = sites % hook.entries;
+                    if i == 0 {
+                        new_instructions.push(Operator::LocalTee { local_index: *a });
+                    } else {
+                        new_instructions.push(Operator::LocalGet { local_index: *a });
+                    }
+                    new_instructions.extend([
+                        Operator::LocalGet { local_index: *b },
+                        if is_memory64 {
+                            Operator::I64Const { value: site as i64 }
+                        } else {
+                            Operator::I32Const { value: site as i32 }
+                        },
+                        Operator::Call {
+                            function_index: *helper,
+                        },
+                    ]);
+                }
+                new_instructions.extend([
+                    Operator::LocalGet { local_index: *a },
+                    Operator::LocalGet { local_index: *b },
+                ]);
+                sites += 1;
+            }
+            new_instructions.push(op);
+        }
+        local_function.body.instructions = Instructions::new(
+            new_instructions.into_iter().map(|i| (i, 0)).collect(),
+            0,
+            false,
+        );
+    }
+    sites
+}
+
+/// Computes the estimated IC instruction cost per AFL instrumentation call site.
+///
+/// Each instrumentation point consists of a **call site** (inlined at the branch)
+/// and the **helper function body** (called from the site). The total cost is the
+/// sum of both, assuming IC instruction cost = 1 per wasm opcode except `call` = 5.
+///
+/// ## Call site (2 wasm instructions, IC cost = 6)
+///
+/// ```text
+/// i32/i64.const <random>   ;; cost 1
+/// call $afl_helper          ;; cost 5
+/// ```
+///
+/// ## Helper function body (IC cost = 9 + 6N, where N = history_size)
+///
+/// ```text
+/// ;; XOR block: compute coverage map key          (1 + 2N instructions)
+/// local.get(curr_location)                        ;; 1
+/// N × (global.get(prev_loc[i]) + i32/i64.xor)    ;; 2N
+///
+/// ;; Address computation + load-increment-store   (8 instructions)
+/// global.get(mem_ptr)                             ;; 1
+/// i32/i64.add                                     ;; 1
+/// local.tee(afl_local)                            ;; 1
+/// local.get(afl_local)                            ;; 1
+/// i32/i64.load8_u                                 ;; 1
+/// i32/i64.const(1)                                ;; 1
+/// i32/i64.add                                     ;; 1
+/// i32/i64.store8                                  ;; 1
+///
+/// ;; History shift                                (4N instructions)
+/// (N-1) × (global.get + const(1) + shr_u + global.set)  ;; 4(N-1)
+/// local.get + const(1) + shr_u + global.set              ;; 4
+///
+/// ;; Call counter increment (only when instruction counting is enabled, +4)
+/// global.get(call_count) + i64.const(1) + i64.add + global.set(call_count)
+/// ```
+///
+/// ## Total per instrumentation point
+///
+/// `6 + 9 + 6N + 4 = 19 + 6N` (IC instructions, approximate)
+///
+/// Sequential probe IDs have no history, so `N = 0`.
+///
+/// ## Inline probes (IC cost = 11 + 6N, or 10 without history)
+///
+/// With `inline_probes`, there is no call site and the body is specialized for the
+/// location (see [`inline_probe_ops`]):
+///
+/// ```text
+/// ;; XOR block: the location is a constant         (1 + 2N instructions)
+/// i32/i64.const(curr_location)                    ;; 1
+/// N × (global.get(prev_loc[i]) + i32/i64.xor)    ;; 2N
+///
+/// ;; Address computation + load-increment-store   (8 instructions, as above)
+///
+/// ;; History shift: the new prev_loc[0] is folded  (4N - 2 instructions)
+/// (N-1) × (global.get + const(1) + shr_u + global.set)  ;; 4(N-1)
+/// i32/i64.const(curr_location >> 1) + global.set        ;; 2
+///
+/// ;; Call counter increment                       (+4)
+/// ```
+///
+/// That is `7 + 6N + 4 = 11 + 6N`, i.e. 17 instead of 25 for `history_size == 1`. Without
+/// history, the location is the offset of the load and the store, which leaves 6
+/// instructions and the call counter increment.
 ///
 /// The wasm32 and wasm64 code paths produce the same number of wasm instructions
-/// (just using i32 vs i64 variants), so the instruction count is `23 + 6N` for both.
+/// (just using i32 vs i64 variants), so the instruction counts are the same for both.
 ///
 /// Note: the IC applies a `WASM64_INSTRUCTION_COST_OVERHEAD` multiplier (currently 2×)
 /// when **charging cycles** for wasm64 execution (see `rs/config/src/subnet_config.rs`
@@ -656,10 +2087,16 @@ fn afl_instrumentation_slice(
 /// This is an approximation — the IC's actual cost model may assign different
 /// weights to some opcodes. For fuzzing guidance (relative ordering of inputs),
 /// an approximate discount is sufficient.
-fn compute_cost_per_afl_call(history_size: usize) -> i64 {
+fn compute_cost_per_afl_call(history_size: usize, inline_probes: bool) -> i64 {
     let n = history_size as i64;
-    // call site (6) + body (13 + 6N) + call counter increment (4)
-    23 + 6 * n
+    match (inline_probes, n) {
+        // body without history (6) + call counter increment (4)
+        (true, 0) => 10,
+        // body (7 + 6N) + call counter increment (4)
+        (true, _) => 11 + 6 * n,
+        // call site (6) + body (9 + 6N) + call counter increment (4)
+        (false, _) => 19 + 6 * n,
+    }
 }
 
 /// Fixed IC instruction cost of the wrapper function itself, up to and including the
@@ -710,6 +2147,8 @@ fn inject_method_wrappers(
                 && exp.name.starts_with("canister_update ")
                 && !exp.name.contains(COVERAGE_FN_EXPORT_NAME)
                 && !exp.name.contains(INSTRUCTION_COUNT_FN_EXPORT_NAME)
+                && !exp.name.contains(CMPLOG_FN_EXPORT_NAME)
+                && !exp.name.contains(VALUE_PROFILE_FN_EXPORT_NAME)
         })
         .map(|(idx, exp)| (idx, FunctionID(exp.index)))
         .collect();
@@ -765,14 +2204,14 @@ fn inject_method_wrappers(
 /// The scratch memory write is transient and discarded after the query returns.
 fn inject_instruction_count_export<'a>(
     module: &mut Module<'a>,
-    history_size: usize,
+    map_size: i64,
     afl_mem_ptr_idx: GlobalID,
     instruction_count_global: GlobalID,
     msg_reply_data_append_idx: FunctionID,
     msg_reply_idx: FunctionID,
     is_memory64: bool,
 ) -> Result<FunctionID> {
-    let scratch_offset = AFL_COVERAGE_MAP_SIZE as i64 * history_size as i64;
+    let scratch_offset = map_size;
 
     let mut func_builder = FunctionBuilder::new(&[], &[]);
 
@@ -1058,7 +2497,7 @@ mod tests {
                 validate_global(global.clone(), true);
             }
             let global = module.globals.get_kind(afl_mem_ptr_idx);
-            validate_global(global.clone(), false);
+            validate_global(global.clone(), true);
         }
     }
 
@@ -1201,7 +2640,7 @@ mod tests {
                             (type (;0;) (func (param i32)))
                             (memory (;0;) 1)
                             (global (;0;) (mut i32) i32.const 0)
-                            (global (;1;) i32 i32.const 0)
+                            (global (;1;) (mut i32) i32.const 0)
                             (func (;0;) (type 0) (param i32)
                                 (local i32)
                                 local.get 0
@@ -1256,7 +2695,7 @@ mod tests {
                             (memory (;0;) 1)
                             (global (;0;) (mut i32) i32.const 0)
                             (global (;1;) (mut i32) i32.const 0)
-                            (global (;2;) i32 i32.const 0)
+                            (global (;2;) (mut i32) i32.const 0)
                             (func (;0;) (type 0) (param i32)
                                 (local i32)
                                 local.get 0
@@ -1306,7 +2745,7 @@ mod tests {
         let (_, afl_mem_ptr_idx, _) = inject_globals(&mut module, history_size, false, false);
         let coverage_function = inject_afl_coverage_export(
             &mut module,
-            history_size,
+            AFL_COVERAGE_MAP_SIZE as i64 * history_size as i64,
             afl_mem_ptr_idx,
             msg_reply_data_append_idx,
             msg_reply_idx,
@@ -1321,7 +2760,7 @@ mod tests {
                     (import "ic0" "msg_reply" (func (;1;) (type 1)))
                     (memory (;0;) 1)
                     (global (;0;) (mut i32) i32.const 0)
-                    (global (;1;) i32 i32.const 0)
+                    (global (;1;) (mut i32) i32.const 0)
                     (export "canister_update __export_coverage_for_afl" (func 2))
                     (func (;2;) (type 1)
                         global.get 1
@@ -1358,7 +2797,7 @@ mod tests {
         let (_, afl_mem_ptr_idx, _) = inject_globals(&mut module, history_size, false, false);
         let coverage_function = inject_afl_coverage_export(
             &mut module,
-            history_size,
+            AFL_COVERAGE_MAP_SIZE as i64 * history_size as i64,
             afl_mem_ptr_idx,
             msg_reply_data_append_idx,
             msg_reply_idx,
@@ -1374,7 +2813,7 @@ mod tests {
                     (memory (;0;) 1)
                     (global (;0;) (mut i32) i32.const 0)
                     (global (;1;) (mut i32) i32.const 0)
-                    (global (;2;) i32 i32.const 0)
+                    (global (;2;) (mut i32) i32.const 0)
                     (export "canister_update __export_coverage_for_afl" (func 2))
                     (func (;2;) (type 1)
                         global.get 2
@@ -1405,7 +2844,11 @@ mod tests {
             &mut module,
             &afl_prev_loc_indices,
             afl_mem_ptr_idx,
-            Seed::Static(42),
+            ProbeIds::Random {
+                seed: Seed::Static(42),
+                map_size: AFL_COVERAGE_MAP_SIZE * history_size as i32,
+            },
+            false,
             false,
             &HashSet::new(),
             None,
@@ -1672,7 +3115,11 @@ mod tests {
             &mut module,
             &afl_prev_loc_indices,
             afl_mem_ptr_idx,
-            Seed::Static(42),
+            ProbeIds::Random {
+                seed: Seed::Static(42),
+                map_size: AFL_COVERAGE_MAP_SIZE * history_size as i32,
+            },
+            false,
             false,
             &HashSet::new(),
             None,
@@ -1730,6 +3177,7 @@ mod tests {
             history_size,
             seed: Seed::Random,
             instrument_instruction_count: false,
+            ..Default::default()
         });
     }
 
@@ -1770,31 +3218,34 @@ mod tests {
                     (type (;0;) (func (param i32)))
                     (type (;1;) (func (param i32 i32)))
                     (type (;2;) (func))
+                    (type (;3;) (func (result i32)))
+                    (type (;4;) (func (param i32) (result i32)))
                     (import "ic0" "msg_reply_data_append" (func (;0;) (type 1)))
                     (import "ic0" "msg_reply" (func (;1;) (type 2)))
                     (memory (;0;) 1)
                     (global (;0;) (mut i32) i32.const 0)
                     (global (;1;) (mut i32) i32.const 0)
-                    (global (;2;) i32 i32.const 0)
+                    (global (;2;) (mut i32) i32.const 0)
                     (export "memory" (memory 0))
                     (export "check_even" (func 2))
                     (export "canister_update __export_coverage_for_afl" (func 3))
+                    (start 4)
                     (func (;2;) (type 0) (param i32)
                         i32.const 17486
-                        call 4
+                        call 7
                         local.get 0
                         i32.const 2
                         i32.rem_u
                         i32.eqz
                         if ;; label = @1
                         i32.const 69016
-                        call 4
+                        call 7
                         i32.const 0
                         i32.const 1
                         i32.store
                         else
                         i32.const 32602
-                        call 4
+                        call 7
                         i32.const 0
                         i32.const 0
                         i32.store
@@ -1802,7 +3253,7 @@ mod tests {
                     )
                     (func (;3;) (type 2)
                         i32.const 71136
-                        call 4
+                        call 7
                         global.get 2
                         i32.const 131072
                         call 0
@@ -1812,7 +3263,77 @@ mod tests {
                         i32.const 131072
                         memory.fill
                     )
-                    (func (;4;) (type 0) (param i32)
+                    (func (;4;) (type 2)
+                        (local i32)
+                        i32.const 4
+                        memory.grow
+                        local.tee 0
+                        i32.const -1
+                        i32.eq
+                        if ;; label = @1
+                        unreachable
+                        end
+                        local.get 0
+                        i32.const 1
+                        i32.add
+                        i32.const 16
+                        i32.shl
+                        global.set 2
+                    )
+                    (func (;5;) (type 3) (result i32)
+                        memory.size
+                        i32.const 4
+                        i32.sub
+                    )
+                    (func (;6;) (type 4) (param i32) (result i32)
+                        (local i32)
+                        local.get 0
+                        memory.grow
+                        local.tee 1
+                        i32.const -1
+                        i32.eq
+                        if ;; label = @1
+                        i32.const -1
+                        return
+                        end
+                        local.get 1
+                        i32.const 4
+                        i32.sub
+                        local.set 1
+                        local.get 1
+                        local.get 0
+                        i32.add
+                        i32.const 16
+                        i32.shl
+                        global.get 2
+                        i32.const 65536
+                        i32.sub
+                        i32.const 262144
+                        memory.copy
+                        global.get 2
+                        i32.const 65536
+                        i32.sub
+                        i32.const 0
+                        local.get 0
+                        i32.const 4
+                        local.get 0
+                        i32.const 4
+                        i32.lt_u
+                        select
+                        i32.const 16
+                        i32.shl
+                        memory.fill
+                        local.get 1
+                        local.get 0
+                        i32.add
+                        i32.const 1
+                        i32.add
+                        i32.const 16
+                        i32.shl
+                        global.set 2
+                        local.get 1
+                    )
+                    (func (;7;) (type 0) (param i32)
                         (local i32)
                         local.get 0
                         global.get 0
@@ -1846,6 +3367,7 @@ mod tests {
             history_size,
             seed: Seed::Static(42),
             instrument_instruction_count: false,
+            ..Default::default()
         });
 
         wasm_equality(generated, expected);
@@ -1888,31 +3410,34 @@ mod tests {
                     (type (;0;) (func (param i64)))
                     (type (;1;) (func (param i64 i64)))
                     (type (;2;) (func))
+                    (type (;3;) (func (result i64)))
+                    (type (;4;) (func (param i64) (result i64)))
                     (import "ic0" "msg_reply_data_append" (func (;0;) (type 1)))
                     (import "ic0" "msg_reply" (func (;1;) (type 2)))
                     (memory (;0;) i64 1)
                     (global (;0;) (mut i64) i64.const 0)
                     (global (;1;) (mut i64) i64.const 0)
-                    (global (;2;) i64 i64.const 0)
+                    (global (;2;) (mut i64) i64.const 0)
                     (export "memory" (memory 0))
                     (export "check_even" (func 2))
                     (export "canister_update __export_coverage_for_afl" (func 3))
+                    (start 4)
                     (func (;2;) (type 0) (param i64)
                         i64.const 17486
-                        call 4
+                        call 7
                         local.get 0
                         i64.const 2
                         i64.rem_u
                         i64.eqz
                         if ;; label = @1
                         i64.const 69016
-                        call 4
+                        call 7
                         i64.const 0
                         i64.const 1
                         i64.store
                         else
                         i64.const 32602
-                        call 4
+                        call 7
                         i64.const 0
                         i64.const 0
                         i64.store
@@ -1920,7 +3445,7 @@ mod tests {
                     )
                     (func (;3;) (type 2)
                         i64.const 71136
-                        call 4
+                        call 7
                         global.get 2
                         i64.const 131072
                         call 0
@@ -1930,7 +3455,77 @@ mod tests {
                         i64.const 131072
                         memory.fill
                     )
-                    (func (;4;) (type 0) (param i64)
+                    (func (;4;) (type 2)
+                        (local i64)
+                        i64.const 4
+                        memory.grow
+                        local.tee 0
+                        i64.const -1
+                        i64.eq
+                        if ;; label = @1
+                        unreachable
+                        end
+                        local.get 0
+                        i64.const 1
+                        i64.add
+                        i64.const 16
+                        i64.shl
+                        global.set 2
+                    )
+                    (func (;5;) (type 3) (result i64)
+                        memory.size
+                        i64.const 4
+                        i64.sub
+                    )
+                    (func (;6;) (type 4) (param i64) (result i64)
+                        (local i64)
+                        local.get 0
+                        memory.grow
+                        local.tee 1
+                        i64.const -1
+                        i64.eq
+                        if ;; label = @1
+                        i64.const -1
+                        return
+                        end
+                        local.get 1
+                        i64.const 4
+                        i64.sub
+                        local.set 1
+                        local.get 1
+                        local.get 0
+                        i64.add
+                        i64.const 16
+                        i64.shl
+                        global.get 2
+                        i64.const 65536
+                        i64.sub
+                        i64.const 262144
+                        memory.copy
+                        global.get 2
+                        i64.const 65536
+                        i64.sub
+                        i32.const 0
+                        local.get 0
+                        i64.const 4
+                        local.get 0
+                        i64.const 4
+                        i64.lt_u
+                        select
+                        i64.const 16
+                        i64.shl
+                        memory.fill
+                        local.get 1
+                        local.get 0
+                        i64.add
+                        i64.const 1
+                        i64.add
+                        i64.const 16
+                        i64.shl
+                        global.set 2
+                        local.get 1
+                    )
+                    (func (;7;) (type 0) (param i64)
                         (local i64)
                         local.get 0
                         global.get 0
@@ -1964,6 +3559,7 @@ mod tests {
             history_size,
             seed: Seed::Static(42),
             instrument_instruction_count: false,
+            ..Default::default()
         });
 
         wasm_equality(generated, expected);
@@ -2014,14 +3610,144 @@ mod tests {
     }
 
     #[test]
-    fn instruction_count_instrumentation_wasm32() {
-        // A module with a canister_update export — should get a wrapper and instruction count export
+    fn reserve_coverage_memory_replaces_memory_ops() {
         let wat = wat::parse_str(
             r#"
-            (module
-                (type (;0;) (func))
-                (import "ic0" "msg_reply" (func (;0;) (type 0)))
-                (memory (;0;) 1)
+                (module
+                    (memory (;0;) 1)
+                    (func (;0;) (result i32)
+                        i32.const 1
+                        memory.grow
+                        memory.size
+                        i32.add
+                    )
+                )
+            "#,
+        )
+        .unwrap();
+
+        let mut module = Module::parse(&wat, false, false).unwrap();
+        let (_, afl_mem_ptr_idx, _) = inject_globals(&mut module, 2, false, false);
+        let injected = reserve_coverage_memory(
+            &mut module,
+            AFL_COVERAGE_MAP_SIZE as i64 * 2,
+            ReservedBuffers::default(),
+            afl_mem_ptr_idx,
+            false,
+        );
+        let (start_fn, size_fn, grow_fn) = (injected[0], injected[1], injected[2]);
+
+        assert_eq!(module.start, Some(start_fn));
+        let instructions = module
+            .functions
+            .get_fn_by_id(FunctionID(0))
+            .unwrap()
+            .unwrap_local()
+            .body
+            .instructions
+            .get_ops();
+        assert_eq!(
+            instructions,
+            &[
+                Operator::I32Const { value: 1 },
+                Operator::Call {
+                    function_index: *grow_fn
+                },
+                Operator::Call {
+                    function_index: *size_fn
+                },
+                Operator::I32Add,
+                Operator::End,
+            ]
+        );
+
+        validate_wasm(&module.encode()).unwrap();
+    }
+
+    #[test]
+    fn reserve_coverage_memory_keeps_limits_and_start() {
+        let wat = wat::parse_str(
+            r#"
+                (module
+                    (memory (;0;) 1 2)
+                    (start 0)
+                    (func (;0;))
+                )
+            "#,
+        )
+        .unwrap();
+
+        let history_size = 8;
+        let map_size = AFL_COVERAGE_MAP_SIZE as i64 * history_size as i64;
+        let mut module = Module::parse(&wat, false, false).unwrap();
+        let (_, afl_mem_ptr_idx, _) = inject_globals(&mut module, history_size, false, false);
+        let injected = reserve_coverage_memory(
+            &mut module,
+            map_size,
+            ReservedBuffers::default(),
+            afl_mem_ptr_idx,
+            false,
+        );
+
+        // 1 guard page + 8 map pages + 1 page for the scratch area
+        assert_eq!(reserved_pages(map_size, ReservedBuffers::default()), 10);
+        let memory = module.memories.get_mem_by_id(MemoryID(0)).unwrap();
+        assert_eq!(memory.ty.initial, 1);
+        assert_eq!(memory.ty.maximum, Some(2 + 10));
+
+        // The original start function runs after the region is reserved.
+        assert_eq!(module.start, Some(injected[0]));
+        let start_ops = module
+            .functions
+            .get_fn_by_id(injected[0])
+            .unwrap()
+            .unwrap_local()
+            .body
+            .instructions
+            .get_ops();
+        assert_eq!(
+            &start_ops[start_ops.len() - 2..],
+            &[Operator::Call { function_index: 0 }, Operator::End]
+        );
+
+        validate_wasm(&module.encode()).unwrap();
+    }
+
+    #[test]
+    fn reserve_coverage_memory_without_memory() {
+        let wat = wat::parse_str(
+            r#"
+                (module)
+            "#,
+        )
+        .unwrap();
+
+        let mut module = Module::parse(&wat, false, false).unwrap();
+        let (_, afl_mem_ptr_idx, _) = inject_globals(&mut module, 1, false, false);
+        reserve_coverage_memory(
+            &mut module,
+            AFL_COVERAGE_MAP_SIZE as i64,
+            ReservedBuffers::default(),
+            afl_mem_ptr_idx,
+            false,
+        );
+
+        let memory = module.memories.get_mem_by_id(MemoryID(0)).unwrap();
+        assert_eq!(memory.ty.initial, 0);
+        assert!(!memory.ty.memory64);
+
+        validate_wasm(&module.encode()).unwrap();
+    }
+
+    #[test]
+    fn instruction_count_instrumentation_wasm32() {
+        // A module with a canister_update export — should get a wrapper and instruction count export
+        let wat = wat::parse_str(
+            r#"
+            (module
+                (type (;0;) (func))
+                (import "ic0" "msg_reply" (func (;0;) (type 0)))
+                (memory (;0;) 1)
                 (export "memory" (memory 0))
                 (export "canister_update my_method" (func 1))
                 (func (;1;) (type 0)
@@ -2037,6 +3763,7 @@ mod tests {
             history_size: 1,
             seed: Seed::Static(42),
             instrument_instruction_count: true,
+            ..Default::default()
         });
 
         // Verify the instrumented module is valid
@@ -2103,6 +3830,7 @@ mod tests {
             history_size: 1,
             seed: Seed::Static(42),
             instrument_instruction_count: true,
+            ..Default::default()
         });
 
         validate_wasm(&generated).unwrap();
@@ -2118,14 +3846,841 @@ mod tests {
     }
 
     #[test]
-    fn compute_cost_per_afl_call_values() {
-        // history_size=1: 23 + 6*1 = 29
-        assert_eq!(compute_cost_per_afl_call(1), 29);
-        // history_size=2: 23 + 6*2 = 35
-        assert_eq!(compute_cost_per_afl_call(2), 35);
-        // history_size=4: 23 + 6*4 = 47
-        assert_eq!(compute_cost_per_afl_call(4), 47);
-        // history_size=8: 23 + 6*8 = 71
-        assert_eq!(compute_cost_per_afl_call(8), 71);
+    fn instrument_comparisons_traced_sites() {
+        let wat = wat::parse_str(
+            r#"
+                (module
+                    (import "env" "cmp32" (func (;0;) (param i32 i32 i32)))
+                    (import "env" "cmp64" (func (;1;) (param i64 i64 i32)))
+                    (func (;2;) (param i32 i64) (result i32)
+                        block
+                            local.get 1
+                            i64.const 5
+                            i64.lt_u
+                            br_if 0
+                        end
+                        local.get 0
+                        i32.const 1
+                        i32.lt_s
+                        drop
+                        local.get 0
+                        i32.const 4660
+                        i32.eq
+                    )
+                )
+            "#,
+        )
+        .unwrap();
+
+        let mut module = Module::parse(&wat, false, false).unwrap();
+        let sites = instrument_comparisons(
+            &mut module,
+            &[ComparisonHook {
+                helpers: (FunctionID(0), FunctionID(1)),
+                entries: CMPLOG_MAP_ENTRIES as usize,
+            }],
+            false,
+            &HashSet::new(),
+        );
+        // The `lt_s` is not followed by a branch, so only the `lt_u` and the `eq` are traced.
+        assert_eq!(sites, 2);
+
+        let instructions = module
+            .functions
+            .get_fn_by_id(FunctionID(2))
+            .unwrap()
+            .unwrap_local()
+            .body
+            .instructions
+            .get_ops();
+        // The two i32 operand locals come first, after the parameters.
+        assert_eq!(
+            &instructions[instructions.len() - 11..],
+            &[
+                Operator::LocalGet { local_index: 0 },
+                Operator::I32Const { value: 4660 },
+                Operator::LocalSet { local_index: 3 },
+                Operator::LocalTee { local_index: 2 },
+                Operator::LocalGet { local_index: 3 },
+                Operator::I32Const { value: 1 },
+                Operator::Call { function_index: 0 },
+                Operator::LocalGet { local_index: 2 },
+                Operator::LocalGet { local_index: 3 },
+                Operator::I32Eq,
+                Operator::End,
+            ]
+        );
+
+        validate_wasm(&module.encode()).unwrap();
+    }
+
+    #[test]
+    fn instrument_comparisons_two_hooks() {
+        let wat = wat::parse_str(
+            r#"
+                (module
+                    (import "env" "cmp32" (func (;0;) (param i32 i32 i32)))
+                    (import "env" "cmp64" (func (;1;) (param i64 i64 i32)))
+                    (import "env" "profile32" (func (;2;) (param i32 i32 i32)))
+                    (import "env" "profile64" (func (;3;) (param i64 i64 i32)))
+                    (func (;4;) (param i64) (result i32)
+                        local.get 0
+                        i64.const 5
+                        i64.eq
+                    )
+                )
+            "#,
+        )
+        .unwrap();
+
+        let mut module = Module::parse(&wat, false, false).unwrap();
+        let hooks = [
+            ComparisonHook {
+                helpers: (FunctionID(0), FunctionID(1)),
+                entries: CMPLOG_MAP_ENTRIES as usize,
+            },
+            ComparisonHook {
+                helpers: (FunctionID(2), FunctionID(3)),
+                entries: VALUE_PROFILE_MAP_SIZE as usize,
+            },
+        ];
+        assert_eq!(
+            instrument_comparisons(&mut module, &hooks, false, &HashSet::new()),
+            1
+        );
+
+        let instructions = module
+            .functions
+            .get_fn_by_id(FunctionID(4))
+            .unwrap()
+            .unwrap_local()
+            .body
+            .instructions
+            .get_ops();
+        assert_eq!(
+            instructions,
+            &[
+                Operator::LocalGet { local_index: 0 },
+                Operator::I64Const { value: 5 },
+                Operator::LocalSet { local_index: 2 },
+                Operator::LocalTee { local_index: 1 },
+                Operator::LocalGet { local_index: 2 },
+                Operator::I32Const { value: 0 },
+                Operator::Call { function_index: 1 },
+                Operator::LocalGet { local_index: 1 },
+                Operator::LocalGet { local_index: 2 },
+                Operator::I32Const { value: 0 },
+                Operator::Call { function_index: 3 },
+                Operator::LocalGet { local_index: 1 },
+                Operator::LocalGet { local_index: 2 },
+                Operator::I64Eq,
+                Operator::End,
+            ]
+        );
+
+        validate_wasm(&module.encode()).unwrap();
+    }
+
+    #[test]
+    fn value_profile_helpers_keep_the_max_score() {
+        let wat = wat::parse_str("(module (memory 1) (global (mut i32) (i32.const 0)))").unwrap();
+        let mut module = Module::parse(&wat, false, false).unwrap();
+        let (helper32, helper64) =
+            inject_value_profile_helpers(&mut module, 100, GlobalID(0), false);
+
+        let memarg = MemArg {
+            offset: 100,
+            align: 0,
+            memory: 0,
+            max_align: 0,
+        };
+        // score = popcnt(!(a ^ b)) + 1, stored at the site if it is larger.
+        let keep_max = [
+            Operator::I32Const { value: 1 },
+            Operator::I32Add,
+            Operator::LocalSet { local_index: 4 },
+            Operator::GlobalGet { global_index: 0 },
+            Operator::LocalGet { local_index: 2 },
+            Operator::I32Add,
+            Operator::LocalTee { local_index: 3 },
+            Operator::I32Load8U { memarg },
+            Operator::LocalGet { local_index: 4 },
+            Operator::I32LtU,
+            Operator::If {
+                blockty: wirm::wasmparser::BlockType::Empty,
+            },
+            Operator::LocalGet { local_index: 3 },
+            Operator::LocalGet { local_index: 4 },
+            Operator::I32Store8 { memarg },
+            Operator::End,
+            Operator::End,
+        ];
+        let ops = |id: FunctionID| {
+            module
+                .functions
+                .unwrap_local(id)
+                .body
+                .instructions
+                .get_ops()
+        };
+        let score32 = [
+            Operator::LocalGet { local_index: 0 },
+            Operator::LocalGet { local_index: 1 },
+            Operator::I32Xor,
+            Operator::I32Const { value: -1 },
+            Operator::I32Xor,
+            Operator::I32Popcnt,
+        ];
+        assert_eq!(ops(helper32), [&score32[..], &keep_max].concat());
+        let score64 = [
+            Operator::LocalGet { local_index: 0 },
+            Operator::LocalGet { local_index: 1 },
+            Operator::I64Xor,
+            Operator::I64Const { value: -1 },
+            Operator::I64Xor,
+            Operator::I64Popcnt,
+            Operator::I32WrapI64,
+        ];
+        assert_eq!(ops(helper64), [&score64[..], &keep_max].concat());
+
+        validate_wasm(&module.encode()).unwrap();
+    }
+
+    #[test]
+    fn reserved_buffers_layout() {
+        let buffers = ReservedBuffers {
+            cmplog: true,
+            value_profile: true,
+        };
+        let map_size = AFL_COVERAGE_MAP_SIZE as i64 * 2;
+        let map_end = map_size + SCRATCH_SIZE;
+        assert_eq!(buffers.cmplog_offset(map_size), map_end);
+        assert_eq!(
+            buffers.value_profile_offset(map_size),
+            map_end + CMPLOG_MAP_SIZE as i64
+        );
+        assert_eq!(
+            buffers.end(map_size),
+            map_end + CMPLOG_MAP_SIZE as i64 + VALUE_PROFILE_MAP_SIZE as i64
+        );
+        assert_eq!(reserved_pages(map_size, buffers), GUARD_PAGES + 3);
+
+        let value_profile_only = ReservedBuffers {
+            cmplog: false,
+            value_profile: true,
+        };
+        assert_eq!(value_profile_only.value_profile_offset(map_size), map_end);
+        assert_eq!(
+            reserved_pages(map_size, ReservedBuffers::default()),
+            GUARD_PAGES + 3
+        );
+    }
+
+    #[test]
+    fn value_profile_instrumentation_wasm32() {
+        let wat = wat::parse_str(
+            r#"
+            (module
+                (type (;0;) (func))
+                (import "ic0" "msg_reply" (func (;0;) (type 0)))
+                (memory (;0;) 1)
+                (export "memory" (memory 0))
+                (export "canister_update my_method" (func 1))
+                (func (;1;) (type 0)
+                    i32.const 0
+                    i64.load
+                    i64.const 1234567
+                    i64.lt_u
+                    br_if 0
+                    call 0
+                )
+            )
+            "#,
+        )
+        .unwrap();
+
+        let generated = instrument_wasm_for_fuzzing(InstrumentationArgs {
+            wasm_bytes: wat,
+            history_size: 1,
+            seed: Seed::Static(42),
+            instrument_instruction_count: true,
+            instrument_cmplog: true,
+            instrument_value_profile: true,
+            ..Default::default()
+        });
+
+        validate_wasm(&generated).unwrap();
+        let module = Module::parse(&generated, false, false).unwrap();
+        for export_name in [CMPLOG_FN_EXPORT_NAME, VALUE_PROFILE_FN_EXPORT_NAME] {
+            assert!(
+                module
+                    .exports
+                    .get_by_name(format!("canister_update {export_name}"))
+                    .is_some(),
+                "Missing {export_name} export"
+            );
+        }
+        // The exports are not wrapped for instruction counting.
+        assert_eq!(
+            module
+                .exports
+                .iter()
+                .filter(|e| e.name.starts_with("canister_update "))
+                .count(),
+            4
+        );
+    }
+
+    #[test]
+    fn cmplog_instrumentation_wasm32() {
+        let wat = wat::parse_str(
+            r#"
+            (module
+                (type (;0;) (func))
+                (import "ic0" "msg_reply" (func (;0;) (type 0)))
+                (memory (;0;) 1)
+                (export "memory" (memory 0))
+                (export "canister_update my_method" (func 1))
+                (func (;1;) (type 0)
+                    i32.const 0
+                    i32.load
+                    i32.const 42
+                    i32.ne
+                    if
+                        call 0
+                    end
+                )
+            )
+            "#,
+        )
+        .unwrap();
+
+        let generated = instrument_wasm_for_fuzzing(InstrumentationArgs {
+            wasm_bytes: wat,
+            history_size: 2,
+            seed: Seed::Static(42),
+            instrument_instruction_count: false,
+            instrument_cmplog: true,
+            ..Default::default()
+        });
+
+        validate_wasm(&generated).unwrap();
+        let module = Module::parse(&generated, false, false).unwrap();
+        assert!(
+            module
+                .exports
+                .get_by_name(format!("canister_update {CMPLOG_FN_EXPORT_NAME}"))
+                .is_some(),
+            "Missing CmpLog export"
+        );
+    }
+
+    #[test]
+    fn function_filter_patterns() {
+        assert!(matches_pattern(
+            "candid::*",
+            "candid::de::IDLDeserialize::new"
+        ));
+        assert!(matches_pattern("*::fmt::*", "core::fmt::write"));
+        assert!(matches_pattern("*", ""));
+        assert!(matches_pattern("a*b*c", "aXbYbZc"));
+        assert!(matches_pattern("exact", "exact"));
+        assert!(!matches_pattern(
+            "candid::*",
+            "<candid::Nat as core::fmt::Debug>::fmt"
+        ));
+        assert!(!matches_pattern("a*b*c", "aXbYbZ"));
+        assert!(!matches_pattern("exact", "exactly"));
+
+        let filter = FunctionFilter {
+            include: vec!["app::*".to_string(), "candid::*".to_string()],
+            exclude: vec!["candid::*".to_string()],
+        };
+        assert!(filter.instruments(Some("app::handle")));
+        assert!(!filter.instruments(Some("candid::decode")));
+        assert!(!filter.instruments(Some("dlmalloc::malloc")));
+        assert!(!filter.instruments(None));
+
+        let exclude_only = FunctionFilter {
+            include: vec![],
+            exclude: vec!["dlmalloc::*".to_string()],
+        };
+        assert!(exclude_only.instruments(None));
+        assert!(!exclude_only.instruments(Some("dlmalloc::malloc")));
+        assert!(FunctionFilter::default().is_empty());
+    }
+
+    #[test]
+    fn function_filter_skips_functions() {
+        let wat = wat::parse_str(
+            r#"
+            (module
+                (memory (;0;) 1)
+                (func $app::handle (result i32)
+                    i32.const 1
+                    if (result i32)
+                        call $candid::decode
+                    else
+                        i32.const 0
+                    end
+                )
+                (func $candid::decode (result i32)
+                    i32.const 7
+                    i32.const 7
+                    i32.eq
+                )
+            )
+            "#,
+        )
+        .unwrap();
+        let filter = FunctionFilter {
+            include: vec![],
+            exclude: vec!["candid::*".to_string()],
+        };
+
+        assert_eq!(
+            skipped_functions(&wat, &filter),
+            vec![(1, Some("candid::decode".to_string()))]
+        );
+
+        let generated = instrument_wasm_for_fuzzing(InstrumentationArgs {
+            wasm_bytes: wat,
+            history_size: 1,
+            seed: Seed::Static(42),
+            instrument_instruction_count: false,
+            instrument_cmplog: true,
+            function_filter: filter,
+            ..Default::default()
+        });
+        validate_wasm(&generated).unwrap();
+
+        let module = Module::parse(&generated, false, false).unwrap();
+        let ops = |name: &str| {
+            let id = module.functions.get_local_fid_by_name(name).unwrap();
+            module
+                .functions
+                .unwrap_local(id)
+                .body
+                .instructions
+                .get_ops()
+                .to_vec()
+        };
+        assert_eq!(
+            ops("candid::decode"),
+            [
+                Operator::I32Const { value: 7 },
+                Operator::I32Const { value: 7 },
+                Operator::I32Eq,
+                Operator::End,
+            ]
+        );
+        assert!(
+            ops("app::handle").len() > 7,
+            "app::handle is not instrumented"
+        );
+    }
+
+    #[test]
+    fn sequential_probe_ids_and_table() {
+        let wat = wat::parse_str(
+            r#"
+            (module
+                (memory (;0;) 1)
+                (func $a (param i32)
+                    block
+                        local.get 0
+                        br_if 0
+                    end
+                )
+                (func $skipped
+                    i32.const 9
+                    drop
+                )
+                (func $b)
+            )
+            "#,
+        )
+        .unwrap();
+        let filter = FunctionFilter {
+            include: vec![],
+            exclude: vec!["skipped".to_string()],
+        };
+
+        // In `a`, `block` is at offset 0, `local.get` at 2 and `br_if` at 4.
+        assert_eq!(
+            probe_table(&wat, &filter),
+            vec![(0, 0), (0, 0), (0, 4), (2, 0)]
+        );
+
+        let generated = instrument_wasm_for_fuzzing(InstrumentationArgs {
+            wasm_bytes: wat,
+            history_size: 8,
+            seed: Seed::Random,
+            sequential_probe_ids: true,
+            instrument_instruction_count: false,
+            function_filter: filter,
+            ..Default::default()
+        });
+        validate_wasm(&generated).unwrap();
+
+        let module = Module::parse(&generated, false, false).unwrap();
+        let constants = |id: FunctionID| {
+            module
+                .functions
+                .unwrap_local(id)
+                .body
+                .instructions
+                .get_ops()
+                .iter()
+                .filter_map(|op| match op {
+                    Operator::I32Const { value } => Some(*value),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        };
+        let id = |name: &str| module.functions.get_local_fid_by_name(name).unwrap();
+        assert_eq!(constants(id("a")), [0, 1, 2]);
+        assert_eq!(constants(id("skipped")), [9]);
+        assert_eq!(constants(id("b")), [3]);
+
+        // The coverage map has one byte per probe.
+        let export = module
+            .exports
+            .get_func_by_name(format!("canister_update {COVERAGE_FN_EXPORT_NAME}"))
+            .unwrap();
+        assert_eq!(constants(export), [4, 0, 4]);
+    }
+
+    #[test]
+    fn custom_map_size() {
+        let wat = wat::parse_str(
+            r#"
+            (module
+                (memory (;0;) 1)
+                (func $a (param i32)
+                    loop
+                        local.get 0
+                        br_if 0
+                    end
+                )
+            )
+            "#,
+        )
+        .unwrap();
+
+        let generated = instrument_wasm_for_fuzzing(InstrumentationArgs {
+            wasm_bytes: wat,
+            history_size: 2,
+            seed: Seed::Static(42),
+            map_size: Some(4096),
+            instrument_instruction_count: false,
+            ..Default::default()
+        });
+        validate_wasm(&generated).unwrap();
+
+        let module = Module::parse(&generated, false, false).unwrap();
+        let constants = |id: FunctionID| {
+            module
+                .functions
+                .unwrap_local(id)
+                .body
+                .instructions
+                .get_ops()
+                .iter()
+                .filter_map(|op| match op {
+                    Operator::I32Const { value } => Some(*value),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        };
+        let ids = constants(module.functions.get_local_fid_by_name("a").unwrap());
+        assert_eq!(ids.len(), 3);
+        assert!(ids.iter().all(|id| (0..4096).contains(id)), "{ids:?}");
+
+        // After its own probe, the export replies with the map and clears it.
+        let export = module
+            .exports
+            .get_func_by_name(format!("canister_update {COVERAGE_FN_EXPORT_NAME}"))
+            .unwrap();
+        assert_eq!(constants(export)[1..], [4096, 0, 4096]);
+    }
+
+    #[should_panic(expected = "power of two")]
+    #[test]
+    fn instrumentation_panic_map_size_not_power_of_two() {
+        let _ = instrument_wasm_for_fuzzing(InstrumentationArgs {
+            wasm_bytes: wat::parse_str("(module)").unwrap(),
+            history_size: 1,
+            seed: Seed::Random,
+            map_size: Some(3000),
+            instrument_instruction_count: false,
+            ..Default::default()
+        });
+    }
+
+    #[should_panic(expected = "sequential probe IDs")]
+    #[test]
+    fn instrumentation_panic_map_size_with_sequential_probe_ids() {
+        let _ = instrument_wasm_for_fuzzing(InstrumentationArgs {
+            wasm_bytes: wat::parse_str("(module)").unwrap(),
+            map_size: Some(4096),
+            sequential_probe_ids: true,
+            ..Default::default()
+        });
+    }
+
+    #[test]
+    fn inline_probes_history_1() {
+        let wat = wat::parse_str(
+            r#"
+                (module
+                    (memory (;0;) 1)
+                    (func (;0;))
+                )
+            "#,
+        )
+        .unwrap();
+
+        let mut module = Module::parse(&wat, false, false).unwrap();
+        let (afl_prev_loc_indices, afl_mem_ptr_idx, _) =
+            inject_globals(&mut module, 1, false, false);
+        instrument_branches(
+            &mut module,
+            &afl_prev_loc_indices,
+            afl_mem_ptr_idx,
+            ProbeIds::Random {
+                seed: Seed::Static(42),
+                map_size: AFL_COVERAGE_MAP_SIZE,
+            },
+            true,
+            false,
+            &HashSet::new(),
+            None,
+        );
+
+        // No helper is injected, and the function gets a local for the map address.
+        assert_eq!(module.functions.iter().count(), 1);
+        let function = module.functions.unwrap_local(FunctionID(0));
+        let ops = function.body.instructions.get_ops();
+        let Operator::I32Const { value: location } = ops[0] else {
+            panic!("The probe does not start with its location: {ops:?}");
+        };
+        let memarg = MemArg {
+            offset: 0,
+            align: 0,
+            memory: 0,
+            max_align: 0,
+        };
+        assert_eq!(
+            ops,
+            [
+                Operator::I32Const { value: location },
+                Operator::GlobalGet { global_index: 0 },
+                Operator::I32Xor,
+                Operator::GlobalGet { global_index: 1 },
+                Operator::I32Add,
+                Operator::LocalTee { local_index: 0 },
+                Operator::LocalGet { local_index: 0 },
+                Operator::I32Load8U { memarg },
+                Operator::I32Const { value: 1 },
+                Operator::I32Add,
+                Operator::I32Store8 { memarg },
+                Operator::I32Const {
+                    value: location >> 1
+                },
+                Operator::GlobalSet { global_index: 0 },
+                Operator::End,
+            ]
+        );
+        validate_wasm(&module.encode()).unwrap();
+    }
+
+    #[test]
+    fn inline_probes_sequential() {
+        let wat = wat::parse_str(
+            r#"
+            (module
+                (memory (;0;) 1)
+                (func $a
+                    block
+                        i32.const 0
+                        br_if 0
+                    end
+                )
+                (export "canister_update a" (func $a))
+            )
+            "#,
+        )
+        .unwrap();
+
+        let generated = instrument_wasm_for_fuzzing(InstrumentationArgs {
+            wasm_bytes: wat,
+            history_size: 8,
+            seed: Seed::Random,
+            inline_probes: true,
+            sequential_probe_ids: true,
+            instrument_instruction_count: true,
+            ..Default::default()
+        });
+        validate_wasm(&generated).unwrap();
+
+        // Each probe increments the byte at its ID, and the call counter.
+        let module = Module::parse(&generated, false, false).unwrap();
+        let id = module.functions.get_local_fid_by_name("a").unwrap();
+        let ops = module
+            .functions
+            .unwrap_local(id)
+            .body
+            .instructions
+            .get_ops();
+        assert!(!ops.iter().any(|op| matches!(op, Operator::Call { .. })));
+        let offsets: Vec<u64> = ops
+            .iter()
+            .filter_map(|op| match op {
+                Operator::I32Store8 { memarg } => Some(memarg.offset),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(offsets, [0, 1, 2]);
+        assert_eq!(
+            ops.iter()
+                .filter(|op| matches!(op, Operator::I64Add))
+                .count(),
+            3
+        );
+    }
+
+    /// Returns the IC cost of the single probe of an empty function, counting the ops it
+    /// executes, including those of the helper it calls: 5 for a `call`, 1 for any other op.
+    fn probe_cost(history_size: usize, inline_probes: bool, is_memory64: bool) -> i64 {
+        let wat = if is_memory64 {
+            "(module (memory i64 1) (func))"
+        } else {
+            "(module (memory 1) (func))"
+        };
+        let wat = wat::parse_str(wat).unwrap();
+        let mut module = Module::parse(&wat, false, false).unwrap();
+        let (afl_prev_loc_indices, afl_mem_ptr_idx, instruction_count_globals) =
+            inject_globals(&mut module, history_size, is_memory64, true);
+        let probe_ids = if history_size == 0 {
+            ProbeIds::Sequential
+        } else {
+            ProbeIds::Random {
+                seed: Seed::Static(42),
+                map_size: AFL_COVERAGE_MAP_SIZE,
+            }
+        };
+        instrument_branches(
+            &mut module,
+            &afl_prev_loc_indices,
+            afl_mem_ptr_idx,
+            probe_ids,
+            inline_probes,
+            is_memory64,
+            &HashSet::new(),
+            instruction_count_globals.map(|(_, call_count)| call_count),
+        );
+
+        // The final `end` of a function body is not charged.
+        fn cost(module: &Module, id: FunctionID) -> i64 {
+            let ops = module
+                .functions
+                .unwrap_local(id)
+                .body
+                .instructions
+                .get_ops();
+            assert_eq!(ops.last(), Some(&Operator::End));
+            ops[..ops.len() - 1]
+                .iter()
+                .map(|op| match op {
+                    Operator::Call { function_index } => {
+                        5 + cost(module, FunctionID(*function_index))
+                    }
+                    _ => 1,
+                })
+                .sum()
+        }
+        cost(&module, FunctionID(0))
+    }
+
+    #[test]
+    fn compute_cost_per_afl_call_matches_emitted_probes() {
+        for history_size in [0, 1, 2, 4, 8] {
+            for inline_probes in [false, true] {
+                for is_memory64 in [false, true] {
+                    assert_eq!(
+                        probe_cost(history_size, inline_probes, is_memory64),
+                        compute_cost_per_afl_call(history_size, inline_probes),
+                        "history_size {history_size}, inline_probes {inline_probes}, \
+                         memory64 {is_memory64}"
+                    );
+                }
+            }
+        }
+        assert_eq!(compute_cost_per_afl_call(1, false), 25);
+        assert_eq!(compute_cost_per_afl_call(1, true), 17);
+    }
+
+    #[test]
+    fn extract_dictionary_comparisons_and_strings() {
+        let wat = wat::parse_str(
+            r#"
+            (module
+                (memory (;0;) 1)
+                (func (;0;) (param i32 i64) (result i32)
+                    local.get 0
+                    i32.const 206
+                    i32.ne
+                    drop
+                    i32.const 3084
+                    local.get 0
+                    i32.lt_u
+                    drop
+                    local.get 1
+                    i64.const 0x1122334455
+                    i64.eq
+                    drop
+                    local.get 0
+                    i32.const -2
+                    i32.eq
+                    drop
+                    local.get 0
+                    i32.const 1
+                    i32.eq
+                    drop
+                    i32.const 42
+                    local.get 0
+                    i32.add
+                )
+                (data (i32.const 0) "transfer\00ok\00Insufficient funds")
+            )
+            "#,
+        )
+        .unwrap();
+
+        let tokens = extract_dictionary(&wat);
+        let expected: Vec<Vec<u8>> = vec![
+            vec![206],
+            vec![0x0c, 0x0c],
+            vec![0x55, 0x44, 0x33, 0x22, 0x11, 0, 0, 0],
+            vec![0xfe, 0xff, 0xff, 0xff],
+            b"transfer".to_vec(),
+            b"Insufficient funds".to_vec(),
+        ];
+        assert_eq!(tokens, expected);
+    }
+
+    #[test]
+    fn encode_constant_values() {
+        assert_eq!(encode_constant(0, 4), None);
+        assert_eq!(encode_constant(1, 8), None);
+        assert_eq!(encode_constant(u32::MAX as u64, 4), None);
+        assert_eq!(encode_constant(u64::MAX, 8), None);
+        assert_eq!(encode_constant(0x1234, 4), Some(vec![0x34, 0x12]));
+        assert_eq!(
+            encode_constant(0x12_3456, 4),
+            Some(vec![0x56, 0x34, 0x12, 0])
+        );
+        assert_eq!(encode_constant(u32::MAX as u64, 8), Some(vec![0xff; 4]));
     }
 }
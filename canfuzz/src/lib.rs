@@ -22,10 +22,8 @@
 //!
 //! // 2. Implement the fuzzing logic.
 //! impl FuzzerOrchestrator for MyFuzzer {
-//!     fn init(&mut self) {
-//!         // Setup PocketIc and install canisters automatically.
-//!         self.as_mut().setup_canisters();
-//!     }
+//!     // `init` defaults to `setup_canisters()`, which creates PocketIc,
+//!     // instruments the coverage canister and installs all canisters.
 //!
 //!     fn corpus_dir(&self) -> PathBuf {
 //!         PathBuf::from("./corpus")
@@ -71,7 +69,10 @@
 //! For a complete example, see the `examples/` directory in the project repository.
 pub mod fuzzer;
 pub mod instrumentation;
+pub mod minimize;
+mod monitor;
 pub mod orchestrator;
+pub mod stats;
 pub mod util;
 
 mod constants;
@@ -0,0 +1,341 @@
+//! Test-case minimization.
+//!
+//! These functions shrink an input while a caller-provided predicate, typically "the input
+//! still crashes with the same signature", keeps holding. They are used by
+//! [`FuzzerOrchestrator::minimize_input`](crate::orchestrator::FuzzerOrchestrator::minimize_input).
+//!
+//! - [`minimize_candid`] shrinks Candid-encoded arguments structurally: it drops vector
+//!   elements, sets optional values to `None` and makes numbers and texts smaller.
+//! - [`minimize_bytes`] removes chunks of bytes, from half the input down to single bytes.
+//!
+//! [`select_covering_subset`] is the corpus counterpart, used by
+//! [`FuzzerOrchestrator::minimize_corpus`](crate::orchestrator::FuzzerOrchestrator::minimize_corpus):
+//! it picks a small subset of corpus entries that covers the same edges as the whole corpus.
+
+use candid::types::Type;
+use candid::types::value::{IDLField, VariantValue};
+use candid::{IDLArgs, IDLValue, Int, Nat, TypeEnv};
+use num_traits::{PrimInt, Zero};
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+/// Removes chunks of `bytes` as long as `reproduces` holds for the result.
+///
+/// Chunks of half the input length are tried first, then ever smaller chunks down to single
+/// bytes. Returns the smallest input found.
+pub fn minimize_bytes(mut bytes: Vec<u8>, mut reproduces: impl FnMut(&[u8]) -> bool) -> Vec<u8> {
+    let mut chunk = (bytes.len() / 2).max(1);
+    loop {
+        let mut start = 0;
+        while start < bytes.len() {
+            let end = (start + chunk).min(bytes.len());
+            let candidate = [&bytes[..start], &bytes[end..]].concat();
+            if reproduces(&candidate) {
+                bytes = candidate;
+            } else {
+                start += chunk;
+            }
+        }
+        if chunk == 1 {
+            return bytes;
+        }
+        chunk /= 2;
+    }
+}
+
+/// Shrinks Candid-encoded arguments with the given types as long as `reproduces` holds.
+///
+/// At each step, the first smaller variant of the arguments (see [`shrink_value`]) for which
+/// `reproduces` holds is kept, until no variant reproduces. Returns `bytes` unchanged if they
+/// cannot be decoded with `arg_types`.
+pub fn minimize_candid(
+    bytes: Vec<u8>,
+    env: &TypeEnv,
+    arg_types: &[Type],
+    mut reproduces: impl FnMut(&[u8]) -> bool,
+) -> Vec<u8> {
+    let Ok(mut args) = IDLArgs::from_bytes_with_types(&bytes, env, arg_types) else {
+        return bytes;
+    };
+    let mut bytes = bytes;
+    'shrink: loop {
+        for (index, arg) in args.args.iter().enumerate() {
+            for smaller in shrink_value(arg) {
+                let mut candidate = args.clone();
+                candidate.args[index] = smaller;
+                let Ok(candidate_bytes) = candidate.to_bytes_with_types(env, arg_types) else {
+                    continue;
+                };
+                if reproduces(&candidate_bytes) {
+                    args = candidate;
+                    bytes = candidate_bytes;
+                    continue 'shrink;
+                }
+            }
+        }
+        return bytes;
+    }
+}
+
+/// Returns the variants of `value` that are one step smaller, the most aggressive first.
+///
+/// Vectors, blobs and texts are emptied, halved or lose one element. Optional values become
+/// `None`, numbers become zero or are halved and `true` becomes `false`. The elements of
+/// vectors, records, variants and optional values are shrunk recursively.
+pub fn shrink_value(value: &IDLValue) -> Vec<IDLValue> {
+    let mut smaller = Vec::new();
+    match value {
+        IDLValue::Bool(true) => smaller.push(IDLValue::Bool(false)),
+        IDLValue::Text(s) if !s.is_empty() => {
+            smaller.push(IDLValue::Text(String::new()));
+            let half: String = s.chars().take(s.chars().count() / 2).collect();
+            if !half.is_empty() {
+                smaller.push(IDLValue::Text(half));
+            }
+        }
+        IDLValue::Opt(inner) => {
+            smaller.push(IDLValue::None);
+            smaller.extend(
+                shrink_value(inner)
+                    .into_iter()
+                    .map(|v| IDLValue::Opt(Box::new(v))),
+            );
+        }
+        IDLValue::Vec(items) if !items.is_empty() => {
+            smaller.push(IDLValue::Vec(vec![]));
+            if items.len() > 2 {
+                smaller.push(IDLValue::Vec(items[..items.len() / 2].to_vec()));
+            }
+            for i in 0..items.len() {
+                let mut fewer = items.clone();
+                fewer.remove(i);
+                smaller.push(IDLValue::Vec(fewer));
+            }
+            for (i, item) in items.iter().enumerate() {
+                for item in shrink_value(item) {
+                    let mut shrunk = items.clone();
+                    shrunk[i] = item;
+                    smaller.push(IDLValue::Vec(shrunk));
+                }
+            }
+        }
+        IDLValue::Blob(b) if !b.is_empty() => {
+            smaller.push(IDLValue::Blob(vec![]));
+            if b.len() > 2 {
+                smaller.push(IDLValue::Blob(b[..b.len() / 2].to_vec()));
+            }
+            smaller.push(IDLValue::Blob(b[..b.len() - 1].to_vec()));
+        }
+        IDLValue::Record(fields) => {
+            for (i, field) in fields.iter().enumerate() {
+                for val in shrink_value(&field.val) {
+                    let mut shrunk = fields.clone();
+                    shrunk[i] = IDLField {
+                        id: field.id.clone(),
+                        val,
+                    };
+                    smaller.push(IDLValue::Record(shrunk));
+                }
+            }
+        }
+        IDLValue::Variant(VariantValue(field, index)) => {
+            for val in shrink_value(&field.val) {
+                smaller.push(IDLValue::Variant(VariantValue(
+                    Box::new(IDLField {
+                        id: field.id.clone(),
+                        val,
+                    }),
+                    *index,
+                )));
+            }
+        }
+        IDLValue::Nat(n) if !n.0.is_zero() => {
+            smaller.push(IDLValue::Nat(Nat::from(0u8)));
+            let half = Nat(&n.0 / 2u8);
+            if !half.0.is_zero() {
+                smaller.push(IDLValue::Nat(half));
+            }
+        }
+        IDLValue::Int(i) if !i.0.is_zero() => {
+            smaller.push(IDLValue::Int(Int::from(0)));
+            let half = Int(&i.0 / 2);
+            if !half.0.is_zero() {
+                smaller.push(IDLValue::Int(half));
+            }
+        }
+        IDLValue::Nat8(n) => smaller.extend(shrink_primitive(*n).into_iter().map(IDLValue::Nat8)),
+        IDLValue::Nat16(n) => smaller.extend(shrink_primitive(*n).into_iter().map(IDLValue::Nat16)),
+        IDLValue::Nat32(n) => smaller.extend(shrink_primitive(*n).into_iter().map(IDLValue::Nat32)),
+        IDLValue::Nat64(n) => smaller.extend(shrink_primitive(*n).into_iter().map(IDLValue::Nat64)),
+        IDLValue::Int8(i) => smaller.extend(shrink_primitive(*i).into_iter().map(IDLValue::Int8)),
+        IDLValue::Int16(i) => smaller.extend(shrink_primitive(*i).into_iter().map(IDLValue::Int16)),
+        IDLValue::Int32(i) => smaller.extend(shrink_primitive(*i).into_iter().map(IDLValue::Int32)),
+        IDLValue::Int64(i) => smaller.extend(shrink_primitive(*i).into_iter().map(IDLValue::Int64)),
+        IDLValue::Float32(f) if *f != 0.0 => smaller.push(IDLValue::Float32(0.0)),
+        IDLValue::Float64(f) if *f != 0.0 => smaller.push(IDLValue::Float64(0.0)),
+        _ => {}
+    }
+    smaller
+}
+
+/// Returns zero and half of `value`, if they are different from `value`.
+fn shrink_primitive<T: PrimInt>(value: T) -> Vec<T> {
+    if value.is_zero() {
+        return vec![];
+    }
+    let half = value / (T::one() + T::one());
+    if half.is_zero() {
+        vec![T::zero()]
+    } else {
+        vec![T::zero(), half]
+    }
+}
+
+/// An edge of the coverage map with the AFL bucket of its hit count, as in `afl-cmin`.
+pub type EdgeTuple = (usize, u8);
+
+/// Returns the edges hit in a coverage map, with their hit counts classified into AFL's
+/// buckets (1, 2, 3, 4-7, 8-15, 16-31, 32-127, 128+).
+pub fn edge_tuples(map: &[u8]) -> Vec<EdgeTuple> {
+    map.iter()
+        .enumerate()
+        .filter(|(_, count)| **count != 0)
+        .map(|(index, count)| {
+            let bucket = match count {
+                1 => 1,
+                2 => 2,
+                3 => 3,
+                4..=7 => 4,
+                8..=15 => 5,
+                16..=31 => 6,
+                32..=127 => 7,
+                _ => 8,
+            };
+            (index, bucket)
+        })
+        .collect()
+}
+
+/// Selects a subset of entries that covers all the edge tuples covered by `entries`.
+///
+/// Each entry is given as its edge tuples and a cost, e.g. its length and instruction count;
+/// entries with a lower cost are preferred. As in `afl-cmin`, the tuples are processed from
+/// the rarest to the most common, and a tuple that is not covered yet adds its cheapest entry
+/// to the subset. Returns the indices of the selected entries, in ascending order.
+pub fn select_covering_subset<C: Ord>(entries: &[(Vec<EdgeTuple>, C)]) -> Vec<usize> {
+    // For every tuple: the number of entries covering it and the cheapest of them.
+    let mut tuples: HashMap<EdgeTuple, (usize, usize)> = HashMap::new();
+    for (index, (edges, cost)) in entries.iter().enumerate() {
+        for tuple in edges {
+            let (count, best) = tuples.entry(*tuple).or_insert((0, index));
+            *count += 1;
+            if *cost < entries[*best].1 {
+                *best = index;
+            }
+        }
+    }
+
+    let mut by_rarity: Vec<_> = tuples.into_iter().collect();
+    by_rarity.sort_unstable_by_key(|(tuple, (count, _))| (*count, *tuple));
+
+    let mut covered = HashSet::new();
+    let mut selected = BTreeSet::new();
+    for (tuple, (_, best)) in by_rarity {
+        if covered.contains(&tuple) {
+            continue;
+        }
+        selected.insert(best);
+        covered.extend(entries[best].0.iter().copied());
+    }
+    selected.into_iter().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use candid::types::TypeInner;
+
+    #[test]
+    fn test_minimize_bytes() {
+        let input = b"aaaaXbbbbbbbbYcc".to_vec();
+        let minimized = minimize_bytes(input, |b| b.contains(&b'X') && b.contains(&b'Y'));
+        assert_eq!(minimized, b"XY");
+    }
+
+    #[test]
+    fn test_minimize_bytes_keeps_non_reproducing_input() {
+        let minimized = minimize_bytes(b"abc".to_vec(), |_| false);
+        assert_eq!(minimized, b"abc");
+    }
+
+    #[test]
+    fn test_edge_tuples() {
+        assert_eq!(
+            edge_tuples(&[0, 1, 0, 5, 200]),
+            vec![(1, 1), (3, 4), (4, 8)]
+        );
+    }
+
+    #[test]
+    fn test_select_covering_subset() {
+        let entries = vec![
+            // Covers everything, but is expensive.
+            (vec![(0, 1), (1, 1), (2, 1)], 100),
+            (vec![(0, 1), (1, 1)], 10),
+            (vec![(2, 1)], 5),
+            // Redundant.
+            (vec![(1, 1)], 50),
+        ];
+        assert_eq!(select_covering_subset(&entries), vec![1, 2]);
+    }
+
+    #[test]
+    fn test_shrink_primitive() {
+        assert_eq!(shrink_primitive(10u8), vec![0, 5]);
+        assert_eq!(shrink_primitive(1u8), vec![0]);
+        assert!(shrink_primitive(0u8).is_empty());
+        assert_eq!(shrink_primitive(-7i32), vec![0, -3]);
+    }
+
+    #[test]
+    fn test_shrink_opt() {
+        let value = IDLValue::Opt(Box::new(IDLValue::Bool(true)));
+        assert_eq!(
+            shrink_value(&value),
+            vec![
+                IDLValue::None,
+                IDLValue::Opt(Box::new(IDLValue::Bool(false)))
+            ]
+        );
+    }
+
+    #[test]
+    fn test_minimize_candid() {
+        let env = TypeEnv::new();
+        let arg_types = vec![
+            TypeInner::Vec(TypeInner::Nat64.into()).into(),
+            TypeInner::Opt(TypeInner::Text.into()).into(),
+        ];
+        let args = IDLArgs::new(&[
+            IDLValue::Vec(vec![
+                IDLValue::Nat64(3),
+                IDLValue::Nat64(1000),
+                IDLValue::Nat64(12),
+            ]),
+            IDLValue::Opt(Box::new(IDLValue::Text("hello".to_string()))),
+        ]);
+        let bytes = args.to_bytes_with_types(&env, &arg_types).unwrap();
+
+        // Reproduces as long as the vector contains a number of at least 100.
+        let minimized = minimize_candid(bytes, &env, &arg_types, |bytes| {
+            let args = IDLArgs::from_bytes_with_types(bytes, &env, &arg_types).unwrap();
+            matches!(&args.args[0], IDLValue::Vec(items)
+                if items.iter().any(|v| matches!(v, IDLValue::Nat64(n) if *n >= 100)))
+        });
+
+        let minimized = IDLArgs::from_bytes_with_types(&minimized, &env, &arg_types).unwrap();
+        assert_eq!(
+            minimized.args,
+            vec![IDLValue::Vec(vec![IDLValue::Nat64(125)]), IDLValue::None]
+        );
+    }
+}
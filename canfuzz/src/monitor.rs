@@ -0,0 +1,384 @@
+//! The monitor that shows the progress of a campaign.
+//!
+//! [`FuzzerMonitor`] prints a line per event, as `libafl`'s `SimpleMonitor` does, or, when the
+//! campaign is built with [`FuzzerBuilder::with_tui`](crate::fuzzer::FuzzerBuilder::with_tui)
+//! and the `tui` feature is enabled, draws a terminal UI with the session, the progress and
+//! the latest crash buckets. The terminal UI falls back to plain output when stdout is not a
+//! terminal, e.g. in CI.
+
+use crate::libafl::Error;
+use crate::libafl::monitors::stats::ClientStatsManager;
+use crate::libafl::monitors::{Monitor, SimpleMonitor};
+use crate::libafl_bolts::ClientId;
+use std::path::PathBuf;
+
+/// The name of the user stat with the canisters of a worker and their IDs, fired once per
+/// worker when the terminal UI is enabled.
+pub(crate) const CANISTERS_STAT_NAME: &str = "canisters";
+
+/// The name of the user stat with the maximum instruction count, fired by
+/// [`InstructionCountFeedback`](crate::custom::feedback::instruction_count::InstructionCountFeedback).
+pub(crate) const MAX_INSTRUCTIONS_STAT_NAME: &str = "max_instructions";
+
+/// The session details shown by the terminal UI.
+#[derive(Debug, Clone)]
+#[cfg_attr(not(feature = "tui"), allow(dead_code))]
+pub(crate) struct MonitorInfo {
+    pub name: String,
+    pub input_dir: PathBuf,
+    pub crashes_dir: PathBuf,
+    pub rng_seed: u64,
+    /// The file stdout is redirected to while the terminal UI is shown.
+    pub log_file: PathBuf,
+    /// The number of instrumented probes, if all coverage canisters use sequential probe IDs.
+    /// Random probe IDs count hashed edges in the map, whose number is not known upfront.
+    pub probes: Option<usize>,
+}
+
+/// The monitor of a campaign: plain output or the terminal UI.
+#[derive(Clone)]
+pub(crate) enum FuzzerMonitor {
+    Simple(SimpleMonitor<fn(&str)>),
+    #[cfg(feature = "tui")]
+    Tui(tui::TuiMonitor),
+}
+
+impl FuzzerMonitor {
+    /// Creates the terminal UI if `tui` is set, the `tui` feature is enabled and stdout is a
+    /// terminal, and a monitor printing to stdout otherwise.
+    pub fn new(tui: bool, info: MonitorInfo) -> Self {
+        if tui {
+            #[cfg(feature = "tui")]
+            {
+                use std::io::IsTerminal;
+                if std::io::stdout().is_terminal() {
+                    match tui::TuiMonitor::new(info) {
+                        Ok(monitor) => return FuzzerMonitor::Tui(monitor),
+                        Err(e) => println!("Failed to start the terminal UI: {e}"),
+                    }
+                } else {
+                    println!(
+                        "stdout is not a terminal, using plain output instead of the terminal UI"
+                    );
+                }
+            }
+            #[cfg(not(feature = "tui"))]
+            {
+                let _ = info;
+                println!(
+                    "canfuzz was built without the `tui` feature, using plain output instead of the terminal UI"
+                );
+            }
+        }
+        FuzzerMonitor::Simple(SimpleMonitor::new(print_line))
+    }
+
+    /// Returns `true` if the terminal UI is shown.
+    pub fn is_tui(&self) -> bool {
+        !matches!(self, FuzzerMonitor::Simple(_))
+    }
+}
+
+impl Monitor for FuzzerMonitor {
+    fn display(
+        &mut self,
+        client_stats_manager: &mut ClientStatsManager,
+        event_msg: &str,
+        sender_id: ClientId,
+    ) -> Result<(), Error> {
+        match self {
+            FuzzerMonitor::Simple(monitor) => {
+                monitor.display(client_stats_manager, event_msg, sender_id)
+            }
+            #[cfg(feature = "tui")]
+            FuzzerMonitor::Tui(monitor) => monitor.display(client_stats_manager),
+        }
+    }
+}
+
+fn print_line(line: &str) {
+    println!("{line}");
+}
+
+/// Restores the terminal if the terminal UI is shown, before the process exits without
+/// dropping the monitor, e.g. on Ctrl+C or a panic.
+pub(crate) fn restore_terminal() {
+    #[cfg(feature = "tui")]
+    tui::restore_terminal();
+}
+
+#[cfg(feature = "tui")]
+mod tui {
+    use super::{CANISTERS_STAT_NAME, MAX_INSTRUCTIONS_STAT_NAME, MonitorInfo};
+    use crate::custom::feedback::crash_report::CrashReport;
+    use crate::libafl::Error;
+    use crate::libafl::monitors::stats::{ClientStatsManager, UserStatsValue};
+    use crate::libafl_bolts::os::{dup, dup2};
+    use ratatui::Terminal;
+    use ratatui::backend::CrosstermBackend;
+    use ratatui::crossterm::cursor::{Hide, Show};
+    use ratatui::crossterm::execute;
+    use ratatui::crossterm::terminal::{EnterAlternateScreen, LeaveAlternateScreen};
+    use ratatui::layout::{Constraint, Layout};
+    use ratatui::text::Line;
+    use ratatui::widgets::{Block, Paragraph};
+    use std::cell::RefCell;
+    use std::collections::HashSet;
+    use std::fs::{self, File};
+    use std::io;
+    use std::os::fd::{AsRawFd, FromRawFd, RawFd};
+    use std::rc::Rc;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::time::{Duration, Instant};
+
+    /// How often the terminal UI is redrawn at most.
+    const DRAW_INTERVAL: Duration = Duration::from_millis(100);
+    /// How often the crash reports are reloaded.
+    const CRASH_SCAN_INTERVAL: Duration = Duration::from_secs(1);
+    /// The number of crash buckets listed.
+    const MAX_CRASHES: usize = 10;
+    /// The name of the user stat with the ratio of covered entries of the coverage map, fired
+    /// by the map feedback.
+    const COVERAGE_STAT_NAME: &str = "coverage_map";
+
+    /// The ID of the process that shows the UI, 0 if it is not shown. Workers forked from
+    /// that process inherit the monitor, but must not restore the terminal.
+    static OWNER: AtomicU32 = AtomicU32::new(0);
+
+    /// Returns `true`, once, if the UI is shown by the current process.
+    fn release() -> bool {
+        let pid = std::process::id();
+        OWNER
+            .compare_exchange(pid, 0, Ordering::SeqCst, Ordering::SeqCst)
+            .is_ok()
+    }
+
+    /// A terminal UI drawn with `ratatui` on the terminal stdout was connected to.
+    ///
+    /// The broker, and every worker forked from it, write their output to the log file
+    /// instead, so it does not interfere with the UI.
+    #[derive(Clone)]
+    pub(crate) struct TuiMonitor {
+        inner: Rc<RefCell<Inner>>,
+    }
+
+    struct Inner {
+        terminal: Terminal<CrosstermBackend<File>>,
+        /// A duplicate of the original stdout, restored when the UI is closed.
+        stdout_fd: RawFd,
+        info: MonitorInfo,
+        last_draw: Option<Instant>,
+        last_crash_scan: Option<Instant>,
+        /// The reports of the latest saved crashes, newest first.
+        crashes: Vec<CrashReport>,
+    }
+
+    impl TuiMonitor {
+        pub(crate) fn new(info: MonitorInfo) -> Result<Self, Error> {
+            let log = File::create(&info.log_file)?;
+            let stdout = io::stdout().as_raw_fd();
+            // SAFETY: `stdout` is open, and the duplicates stay open until the UI is closed.
+            let (stdout_fd, mut terminal) = unsafe {
+                let stdout_fd = dup(stdout)?;
+                let tty = File::from_raw_fd(dup(stdout)?);
+                dup2(log.as_raw_fd(), stdout)?;
+                (stdout_fd, tty)
+            };
+            execute!(terminal, EnterAlternateScreen, Hide)?;
+            OWNER.store(std::process::id(), Ordering::SeqCst);
+            let mut terminal = Terminal::new(CrosstermBackend::new(terminal))?;
+            terminal.clear()?;
+            Ok(Self {
+                inner: Rc::new(RefCell::new(Inner {
+                    terminal,
+                    stdout_fd,
+                    info,
+                    last_draw: None,
+                    last_crash_scan: None,
+                    crashes: Vec::new(),
+                })),
+            })
+        }
+
+        pub(crate) fn display(
+            &mut self,
+            client_stats_manager: &mut ClientStatsManager,
+        ) -> Result<(), Error> {
+            let mut inner = self.inner.borrow_mut();
+            if inner.last_draw.is_some_and(|t| t.elapsed() < DRAW_INTERVAL) {
+                return Ok(());
+            }
+            inner.last_draw = Some(Instant::now());
+            if inner
+                .last_crash_scan
+                .is_none_or(|t| t.elapsed() >= CRASH_SCAN_INTERVAL)
+            {
+                inner.last_crash_scan = Some(Instant::now());
+                inner.crashes = latest_crashes(&inner.info);
+            }
+            let session = session_lines(&inner.info, client_stats_manager);
+            let progress = progress_lines(client_stats_manager, inner.info.probes);
+            let crashes = crash_lines(&inner.crashes);
+            let title = format!(" canfuzz: {} ", inner.info.name);
+            inner.terminal.draw(|f| {
+                let [session_area, progress_area, crashes_area] = Layout::vertical([
+                    Constraint::Length(session.len() as u16 + 2),
+                    Constraint::Length(progress.len() as u16 + 2),
+                    Constraint::Min(3),
+                ])
+                .areas(f.area());
+                f.render_widget(
+                    Paragraph::new(session).block(Block::bordered().title(title)),
+                    session_area,
+                );
+                f.render_widget(
+                    Paragraph::new(progress).block(Block::bordered().title(" Progress ")),
+                    progress_area,
+                );
+                f.render_widget(
+                    Paragraph::new(crashes)
+                        .block(Block::bordered().title(" Latest crash buckets ")),
+                    crashes_area,
+                );
+            })?;
+            Ok(())
+        }
+    }
+
+    impl Drop for Inner {
+        fn drop(&mut self) {
+            if !release() {
+                return;
+            }
+            let _ = execute!(self.terminal.backend_mut(), LeaveAlternateScreen, Show);
+            // SAFETY: `stdout_fd` was duplicated in `TuiMonitor::new` and is only closed here.
+            unsafe {
+                let _ = dup2(self.stdout_fd, io::stdout().as_raw_fd());
+                drop(File::from_raw_fd(self.stdout_fd));
+            }
+        }
+    }
+
+    pub(crate) fn restore_terminal() {
+        // stdout is redirected to the log file, so leave the alternate screen through stderr.
+        if release() {
+            let _ = execute!(io::stderr(), LeaveAlternateScreen, Show);
+        }
+    }
+
+    fn session_lines(info: &MonitorInfo, stats: &ClientStatsManager) -> Vec<Line<'static>> {
+        let canisters = stats
+            .client_stats()
+            .values()
+            .find_map(|client| client.get_user_stats(CANISTERS_STAT_NAME))
+            .map_or_else(|| "(starting)".to_string(), |s| s.to_string());
+        vec![
+            Line::from(format!("canisters:  {canisters}")),
+            Line::from(format!("input:      {}", info.input_dir.display())),
+            Line::from(format!("crashes:    {}", info.crashes_dir.display())),
+            Line::from(format!("log:        {}", info.log_file.display())),
+            Line::from(format!("rng seed:   {}", info.rng_seed)),
+        ]
+    }
+
+    fn progress_lines(stats: &mut ClientStatsManager, probes: Option<usize>) -> Vec<Line<'static>> {
+        let coverage = stats
+            .client_stats()
+            .values()
+            .filter_map(
+                |client| match client.get_user_stats(COVERAGE_STAT_NAME)?.value() {
+                    UserStatsValue::Ratio(covered, total) if *total > 0 => Some((*covered, *total)),
+                    _ => None,
+                },
+            )
+            .max_by(|(a, n), (b, m)| (*a as f64 / *n as f64).total_cmp(&(*b as f64 / *m as f64)));
+        let aggregated = |name: &str| {
+            stats
+                .aggregated()
+                .get(name)
+                .map_or_else(|| "-".to_string(), ToString::to_string)
+        };
+        // Each worker has its own buckets, but a bucket has the same stat name in every worker.
+        let buckets = stats
+            .client_stats()
+            .values()
+            .flat_map(|client| client.user_stats().keys())
+            .filter(|name| name.starts_with("bucket_"))
+            .collect::<HashSet<_>>()
+            .len();
+        let max_instructions = aggregated(MAX_INSTRUCTIONS_STAT_NAME);
+        let global = stats.global_stats();
+        vec![
+            Line::from(format!(
+                "run time:   {}   workers: {}",
+                global.run_time_pretty, global.client_stats_count
+            )),
+            Line::from(format!(
+                "executions: {} ({}/s)",
+                global.total_execs, global.execs_per_sec_pretty
+            )),
+            Line::from(format!(
+                "coverage:   {}",
+                coverage.map_or_else(
+                    || "-".to_string(),
+                    |(covered, total)| { coverage_summary(covered, total, probes) }
+                )
+            )),
+            Line::from(format!(
+                "corpus:     {}   objectives: {}   crash buckets: {buckets}",
+                global.corpus_size, global.objective_size
+            )),
+            Line::from(format!("max instructions: {max_instructions}")),
+        ]
+    }
+
+    /// Describes the coverage: the share of the instrumented `probes` that are covered if it is
+    /// known, and otherwise the share of the `total` entries of the coverage map that are
+    /// covered by hashed edges.
+    fn coverage_summary(covered: u64, total: u64, probes: Option<usize>) -> String {
+        match probes {
+            Some(probes) if probes > 0 => format!(
+                "{:.2}% of the instrumented probes ({covered}/{probes})",
+                covered as f64 * 100.0 / probes as f64
+            ),
+            _ => format!(
+                "{covered} hashed edges, filling {:.2}% of the map ({total} entries)",
+                covered as f64 * 100.0 / total as f64
+            ),
+        }
+    }
+
+    fn crash_lines(crashes: &[CrashReport]) -> Vec<Line<'static>> {
+        if crashes.is_empty() {
+            return vec![Line::from("no crashes yet")];
+        }
+        crashes
+            .iter()
+            .map(|report| {
+                // RFC 3339, e.g. `2024-06-10T14:03:12.123+02:00`: keep the time of day.
+                let time = report.timestamp.get(11..19).unwrap_or(&report.timestamp);
+                Line::from(format!(
+                    "{time}  {:<8} {:<20} {}",
+                    report.exit_kind,
+                    report.method.as_deref().unwrap_or("-"),
+                    report.signature.as_deref().unwrap_or("-")
+                ))
+            })
+            .collect()
+    }
+
+    /// Loads the newest crash reports of the crashes directory.
+    fn latest_crashes(info: &MonitorInfo) -> Vec<CrashReport> {
+        let mut reports: Vec<CrashReport> = fs::read_dir(&info.crashes_dir)
+            .into_iter()
+            .flatten()
+            .filter_map(|e| e.ok())
+            .map(|e| e.path())
+            .filter(|p| p.extension().is_some_and(|ext| ext == "json"))
+            .filter_map(|p| serde_json::from_slice(&fs::read(p).ok()?).ok())
+            .collect();
+        reports.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
+        reports.truncate(MAX_CRASHES);
+        reports
+    }
+}
@@ -13,17 +13,28 @@
 //! count is broken, a detailed log line is printed and the input is saved to disk.
 //! If [`InstructionConfig::max_instruction_count`] is set, inputs that exceed the threshold
 //! are treated as crashes.
+//!
+//! The trait is generic over the input type, which defaults to `BytesInput`. Any type
+//! implementing [`FuzzerInput`] can be used instead, e.g.
+//! [`CallSequenceInput`](crate::custom::input::call_sequence::CallSequenceInput) for
+//! sequences of calls to different methods.
 
 use candid::Principal;
 use chrono::Local;
 use ic_management_canister_types::CanisterId;
-use libafl::feedback_or;
 use libafl::feedbacks::{ExitKindFeedback, TimeoutFeedback};
+use libafl::{feedback_and_fast, feedback_or};
 use pocket_ic::PocketIc;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::cell::{Cell, RefCell};
 use std::fs::{self, File};
 use std::io::{Read, Write as IoWrite};
-use std::path::PathBuf;
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 /// Diagnostic information about the current fuzzing session.
 /// Stored in a global [`OnceLock`] so that panic hooks and Ctrl+C handlers can print
@@ -34,6 +45,8 @@ struct SessionInfo {
     input_dir: PathBuf,
     crashes_dir: PathBuf,
     rng_seed: u64,
+    /// Whether the terminal UI is shown, see [`FuzzerBuilder::with_tui`](crate::fuzzer::FuzzerBuilder::with_tui).
+    tui: bool,
 }
 
 static SESSION_INFO: std::sync::OnceLock<SessionInfo> = std::sync::OnceLock::new();
@@ -46,40 +59,76 @@ fn print_session_info() {
         eprintln!("  Seed corpus:  {}", info.corpus_dir.display());
         eprintln!("  Input dir:    {}", info.input_dir.display());
         eprintln!("  Crashes dir:  {}", info.crashes_dir.display());
-        eprintln!("  RNG seed:     {}", info.rng_seed);
+        eprintln!(
+            "  RNG seed:     {} (set {RNG_SEED_ENV_VAR} to reuse it)",
+            info.rng_seed
+        );
         eprintln!("#############################");
     }
 }
 
+use crate::custom::feedback::crash_report::{
+    CrashReportFeedback, ObjectiveCounts, clear_last_call, last_call,
+};
+use crate::custom::feedback::crash_triage::{
+    CrashSignature, CrashTriageConfig, CrashTriageFeedback, OkLogic,
+};
 use crate::custom::feedback::oom_exit_kind::OomLogic;
-use crate::custom::mutator::candid::{CandidParserMutator, CandidTypeDefArgs};
+use crate::custom::input::FuzzerInput;
+use crate::custom::mutator::candid::{CandidTypeDefArgs, method_arg_types};
 use crate::libafl::{
-    Evaluator,
-    corpus::CachedOnDiskCorpus,
-    events::SimpleEventManager,
+    Error, Evaluator, HasMetadata, HasNamedMetadata,
+    corpus::{CachedOnDiskCorpus, Corpus, HasCurrentCorpusId},
+    events::{
+        ClientDescription, Event, EventConfig, EventFirer, EventWithStats, Launcher,
+        LlmpRestartingEventManager, SimpleEventManager,
+    },
     executors::{ExitKind, inprocess::InProcessExecutor},
-    feedbacks::{CrashFeedback, map::AflMapFeedback},
+    feedbacks::{
+        CrashFeedback,
+        map::{AflMapFeedback, MapFeedbackMetadata, MaxMapFeedback},
+    },
     fuzzer::{Fuzzer, StdFuzzer},
     inputs::BytesInput,
-    mutators::{HavocScheduledMutator, havoc_mutations},
+    monitors::stats::{AggregatorOps, UserStats, UserStatsValue},
+    mutators::Tokens,
     observers::{
         CanTrack,
+        cmp::StdCmpObserver,
         map::{StdMapObserver, hitcount_map::HitcountsMapObserver},
     },
     schedulers::{
         IndexesLenTimeMinimizerScheduler, StdWeightedScheduler, powersched::PowerSchedule,
     },
-    stages::{AflStatsStage, CalibrationStage, StdPowerMutationalStage},
-    state::StdState,
+    stages::{
+        AflStatsStage, CalibrationStage, ClosureStage, IfStage, StdMutationalStage,
+        StdPowerMutationalStage, TracingStage,
+    },
+    state::{HasCorpus, HasExecutions, HasRand, HasSolutions, StdState, Stoppable},
 };
+use crate::minimize::{edge_tuples, minimize_bytes, minimize_candid, select_covering_subset};
+use crate::monitor::{CANISTERS_STAT_NAME, FuzzerMonitor, MonitorInfo, restore_terminal};
+use crate::stats::{CampaignStats, StatsWriter};
+use std::collections::HashSet;
 
-use crate::libafl::monitors::SimpleMonitor;
-// use libafl::monitors::tui::{ui::TuiUI, TuiMonitor};
-use crate::libafl_bolts::{current_nanos, rands::StdRand, tuples::tuple_list};
+use crate::libafl_bolts::{
+    Named, current_nanos,
+    ownedref::OwnedRefMut,
+    rands::{Rand, StdRand},
+    shmem::{ShMemProvider, StdShMemProvider},
+    tuples::tuple_list,
+};
 
-use crate::constants::{COVERAGE_FN_EXPORT_NAME, INSTRUCTION_COUNT_FN_EXPORT_NAME};
-use crate::custom::observer::instruction_count::INSTRUCTION_MAP;
-use crate::fuzzer::FuzzerState;
+use crate::constants::INSTRUCTION_COUNT_FN_EXPORT_NAME;
+use crate::custom::mutator::i2s::InputToStateMutator;
+use crate::custom::observer::cmplog::CMPLOG_OBSERVER_NAME;
+use crate::custom::observer::instruction_count::{INSTRUCTION_MAP, InstructionCountMap};
+use crate::fuzzer::{CanisterType, FuzzerState, RNG_SEED_ENV_VAR, StopConditions};
+use crate::instrumentation::{Seed, probe_table, skipped_functions};
+use crate::util::read_canister_bytes;
+
+/// The `libafl` state of a fuzzer for inputs of type `I`.
+pub type FuzzerStdState<I> = StdState<CachedOnDiskCorpus<I>, I, StdRand, CachedOnDiskCorpus<I>>;
 
 /// Configuration for instruction count maximization.
 ///
@@ -94,80 +143,155 @@ pub struct InstructionConfig {
     pub max_instruction_count: Option<u64>,
 }
 
+/// The condition that ended a campaign, see [`StopConditions`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StopReason {
+    /// The time budget was used up.
+    MaxDuration,
+    /// The execution budget was used up.
+    MaxExecs,
+    /// No new edge was covered for the configured duration.
+    NoNewCoverage,
+    /// A crash, timeout or OOM was saved.
+    Crash,
+}
+
+impl std::fmt::Display for StopReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StopReason::MaxDuration => write!(f, "time budget reached"),
+            StopReason::MaxExecs => write!(f, "execution budget reached"),
+            StopReason::NoNewCoverage => write!(f, "no new coverage"),
+            StopReason::Crash => write!(f, "crash found"),
+        }
+    }
+}
+
+/// The outcome of a campaign, returned by [`FuzzerOrchestrator::run`] when a stop condition
+/// is met.
+///
+/// The counts cover the whole campaign, including a resumed one. In parallel mode, the
+/// executions of the workers are added up, `edges` is the largest coverage of a worker and
+/// `stop_reason` is the reason of the first worker.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RunSummary {
+    /// The condition that ended the campaign.
+    pub stop_reason: Option<StopReason>,
+    /// The number of executions of the harness.
+    pub execs: u64,
+    /// The number of covered edges of the coverage map.
+    pub edges: usize,
+    /// The number of saved crashes, timeouts and OOMs.
+    pub crashes: usize,
+    /// The number of entries in the corpus.
+    pub corpus_size: usize,
+    /// The wall-clock duration of the run.
+    pub duration: Duration,
+}
+
+impl std::fmt::Display for RunSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let reason = self
+            .stop_reason
+            .map_or_else(|| "stopped".to_string(), |r| r.to_string());
+        write!(
+            f,
+            "Campaign finished ({reason}) after {:.1}s: {} executions, {} edges, {} corpus entries, {} crashes",
+            self.duration.as_secs_f64(),
+            self.execs,
+            self.edges,
+            self.corpus_size,
+            self.crashes
+        )
+    }
+}
+
 /// A trait that defines the necessary components for a canister fuzzing target.
 ///
 /// Implementors of this trait provide the specific logic for setting up the environment,
 /// executing a test case against one or more canisters, and cleaning up afterwards.
-pub trait FuzzerOrchestrator: AsRef<FuzzerState> + AsMut<FuzzerState> {
+///
+/// The type parameter `I` is the input type, `BytesInput` by default. It determines the
+/// default mutator, and the format of the seed corpus files and of the saved inputs
+/// (see [`FuzzerInput`]).
+pub trait FuzzerOrchestrator<I: FuzzerInput = BytesInput>:
+    AsRef<FuzzerState> + AsMut<FuzzerState>
+{
     /// Performs one-time initialization at the start of the fuzzing campaign.
     /// This is where canisters are typically installed.
-    fn init(&mut self);
+    ///
+    /// The default implementation calls [`FuzzerState::setup_canisters`], which installs
+    /// all registered canisters and instruments the coverage canisters.
+    fn init(&mut self) {
+        self.as_mut().setup_canisters();
+    }
 
     /// Sets up the environment before each execution of a test case.
-    /// This could involve resetting canister state to a clean snapshot.
+    ///
+    /// Canisters built with a [`ResetStrategy`](crate::fuzzer::ResetStrategy) are already
+    /// reset when this is called, so this is only needed for custom preparation.
     fn setup(&self) {}
 
     /// Executes a single fuzzing input against the target canister(s).
     ///
     /// # Arguments
     ///
-    /// * `input` - The `BytesInput` generated by the fuzzer.
+    /// * `input` - The input generated by the fuzzer.
     ///
     /// # Returns
     ///
     /// * `ExitKind` - Indicates the outcome of the execution (e.g., `Ok`, `Crash`).
-    fn execute(&self, input: BytesInput) -> ExitKind;
+    fn execute(&self, input: I) -> ExitKind;
+
+    /// Returns the mutator applied to the inputs.
+    ///
+    /// By default, this is [`FuzzerInput::default_mutator`] configured with
+    /// [`get_candid_args`](Self::get_candid_args). Override it to configure a custom mutator,
+    /// e.g. a [`CallSequenceMutator`](crate::custom::mutator::call_sequence::CallSequenceMutator)
+    /// restricted to some methods and callers.
+    fn mutator(&self) -> I::Mutator {
+        I::default_mutator(Self::get_candid_args())
+    }
 
     /// Returns a thread-safe reference to the `PocketIc` instance.
     fn get_state_machine(&self) -> Arc<PocketIc> {
         self.as_ref().get_state_machine()
     }
 
-    /// Returns the `CanisterId` of the canister that has been instrumented for coverage.
+    /// Returns the `CanisterId` of the main canister instrumented for coverage, i.e. the first
+    /// registered coverage canister.
     fn get_coverage_canister_id(&self) -> CanisterId {
         self.as_ref().get_coverage_canister_id()
     }
 
-    /// Creates and returns the path to a new timestamped directory for storing input items.
+    /// Creates and returns the path to the directory for storing input items.
     ///
-    /// The directory is structured as `$OUT_DIR/artifacts/<fuzzer_name>/<timestamp>/input`,
-    /// where `$OUT_DIR` is the build script output directory (e.g., `target/debug/build/.../out`),
-    /// `<fuzzer_name>` is the name provided to `FuzzerState::new`, and `<timestamp>` is based
-    /// on the current time.
+    /// The directory is `<campaign_dir>/input`, where the campaign directory is
+    /// `<artifacts_dir>/<fuzzer_name>/<timestamp>` (see [`FuzzerState::campaign_dir`] and
+    /// [`FuzzerState::artifacts_dir`]). When resuming a campaign (see
+    /// [`FuzzerBuilder::with_resume`](crate::fuzzer::FuzzerBuilder::with_resume)), the
+    /// directory of that campaign is reused.
     ///
     /// # Panics
     ///
-    /// Panics if the `OUT_DIR` environment variable is not set or if the directory cannot be created.
+    /// Panics if the directory cannot be created.
     fn input_dir(&self) -> PathBuf {
-        let out_dir = std::env::var("OUT_DIR").expect("OUT_DIR is not set");
-        let input_dir = PathBuf::from(out_dir)
-            .join("artifacts")
-            .join(self.as_ref().name())
-            .join(Local::now().format("%Y%m%d_%H%M").to_string())
-            .join("input");
+        let input_dir = self.as_ref().campaign_dir().join("input");
         fs::create_dir_all(&input_dir)
             .unwrap_or_else(|e| panic!("Failed to create input directory {input_dir:?}: {e}"));
         println!("Input directory: {input_dir:?}");
         input_dir
     }
 
-    /// Creates and returns the path to a new timestamped directory for storing crashes.
+    /// Creates and returns the path to the directory for storing crashes.
     ///
-    /// The directory is structured as `$OUT_DIR/artifacts/<fuzzer_name>/<timestamp>/crashes`,
-    /// where `$OUT_DIR` is the build script output directory (e.g., `target/debug/build/.../out`),
-    /// `<fuzzer_name>` is the name provided to `FuzzerState::new`, and `<timestamp>` is based
-    /// on the current time.
+    /// The directory is `<campaign_dir>/crashes`, next to the [`input_dir`](Self::input_dir).
     ///
     /// # Panics
     ///
-    /// Panics if the `OUT_DIR` environment variable is not set or if the directory cannot be created.
+    /// Panics if the directory cannot be created.
     fn crashes_dir(&self) -> PathBuf {
-        let out_dir = std::env::var("OUT_DIR").expect("OUT_DIR is not set");
-        let crashes_dir = PathBuf::from(out_dir)
-            .join("artifacts")
-            .join(self.as_ref().name())
-            .join(Local::now().format("%Y%m%d_%H%M").to_string())
-            .join("crashes");
+        let crashes_dir = self.as_ref().campaign_dir().join("crashes");
         fs::create_dir_all(&crashes_dir)
             .unwrap_or_else(|e| panic!("Failed to create crashes directory {crashes_dir:?}: {e}"));
         println!("Crashes directory: {crashes_dir:?}");
@@ -179,31 +303,37 @@ pub trait FuzzerOrchestrator: AsRef<FuzzerState> + AsMut<FuzzerState> {
     /// This directory should contain initial valid inputs to kickstart the fuzzing process.
     fn corpus_dir(&self) -> PathBuf;
 
-    /// Fetches the coverage map from the instrumented canister and updates the global `COVERAGE_MAP`.
+    /// Fetches the coverage maps from the instrumented canisters and updates the fuzzer's
+    /// [`CoverageMap`](crate::fuzzer::CoverageMap).
     ///
-    /// It makes a update call to the `__export_coverage_for_afl` function on the coverage canister.
-    /// If the update fails, the coverage map is not updated.
-    #[allow(static_mut_refs)]
+    /// It makes an update call to the `__export_coverage_for_afl` function on every coverage
+    /// canister (see [`FuzzerState::update_coverage_map`]).
+    /// If an update fails, that canister's part of the coverage map is not updated.
     fn set_coverage_map(&self) {
-        let test = self.get_state_machine();
-        let result = test.update_call(
-            self.get_coverage_canister_id(),
-            Principal::anonymous(),
-            COVERAGE_FN_EXPORT_NAME,
-            vec![],
-        );
-        if let Ok(result) = result {
-            unsafe { crate::instrumentation::COVERAGE_MAP.copy_from_slice(&result) };
-        }
+        self.as_ref().update_coverage_map();
     }
 
-    /// Provides a mutable reference to the global `COVERAGE_MAP`.
-    fn get_coverage_map(&self) -> &'static mut [u8] {
-        unsafe { crate::instrumentation::COVERAGE_MAP }
+    /// Returns the coverage map as a mutable byte slice.
+    #[deprecated(
+        note = "use `FuzzerState::coverage_map` or `FuzzerState::coverage_map_mut` instead"
+    )]
+    fn get_coverage_map(&mut self) -> &mut [u8] {
+        self.as_mut().coverage_map_mut().as_mut_slice()
+    }
+
+    /// Fetches the comparisons traced by the CmpLog instrumentation and stores them in the
+    /// CmpLog map of the [`FuzzerState`], for the tracing stage of the fuzzing loop.
+    ///
+    /// It makes an update call to the `__export_cmplog_for_afl` function on every coverage
+    /// canister built [`with_cmplog`](crate::fuzzer::CanisterBuilder::with_cmplog)
+    /// (see [`FuzzerState::update_cmplog_map`]).
+    fn set_cmplog_map(&self) {
+        self.as_ref().update_cmplog_map();
     }
 
-    /// Provides configuration for the `CandidParserMutator`.
+    /// Provides the Candid definition used by the default mutator.
     ///
+    /// For `BytesInput`, this configures the `CandidParserMutator`.
     /// By default, this returns `None`, which disables the Candid-aware mutator.
     /// To enable it, override this method in your fuzzer implementation to return
     /// `Some(CandidTypeDefArgs { ... })`. You will need to provide the path to the
@@ -225,32 +355,50 @@ pub trait FuzzerOrchestrator: AsRef<FuzzerState> + AsMut<FuzzerState> {
         InstructionConfig::default()
     }
 
+    /// Returns the configuration of the crash triage.
+    ///
+    /// Crashing inputs are grouped into buckets by their
+    /// [`CrashSignature`](crate::custom::feedback::crash_triage::CrashSignature). By default,
+    /// only the first input of each bucket is saved to the crashes directory and the signature
+    /// does not include the covered edges. Override this to change either.
+    fn crash_triage_config() -> CrashTriageConfig {
+        CrashTriageConfig::default()
+    }
+
+    /// Fetches the instruction count of the last call from the main coverage canister.
+    ///
+    /// It makes a query call to the `__export_instruction_count_for_afl` function. Returns
+    /// `None` if the call fails, e.g. because the canister is not instrumented for instruction
+    /// counting.
+    fn get_instruction_count(&self) -> Option<u64> {
+        let result = self
+            .get_state_machine()
+            .query_call(
+                self.get_coverage_canister_id(),
+                Principal::anonymous(),
+                INSTRUCTION_COUNT_FN_EXPORT_NAME,
+                vec![],
+            )
+            .ok()?;
+        Some(u64::from_le_bytes(result.get(0..8)?.try_into().unwrap()))
+    }
+
     /// Fetches the instruction count from the instrumented canister and updates the global `INSTRUCTION_MAP`.
     ///
-    /// It makes a query call to the `__export_instruction_count_for_afl` function on the coverage canister.
+    /// It makes a query call to the `__export_instruction_count_for_afl` function on the main coverage canister.
     /// If the instruction count exceeds the previous maximum, the input is marked as interesting.
     /// Returns `true` if the instruction count exceeded the configured
     /// [`InstructionConfig::max_instruction_count`] threshold (i.e. should be treated as a crash).
     #[allow(static_mut_refs)]
-    fn set_instruction_count(&self, input: &BytesInput) -> bool {
-        let test = self.get_state_machine();
-        let result = test.query_call(
-            self.get_coverage_canister_id(),
-            Principal::anonymous(),
-            INSTRUCTION_COUNT_FN_EXPORT_NAME,
-            vec![],
-        );
-        if let Ok(result) = result
-            && result.len() >= 8
-        {
-            let instructions = u64::from_le_bytes(result[0..8].try_into().unwrap());
+    fn set_instruction_count(&self, input: &I) -> bool {
+        if let Some(instructions) = self.get_instruction_count() {
             let mut map = unsafe { INSTRUCTION_MAP.borrow_mut() };
             if instructions > map.max_instructions {
                 let prev = map.max_instructions;
                 map.increased = true;
                 map.max_instructions = instructions;
 
-                let input_bytes: Vec<u8> = input.clone().into();
+                let input_bytes = input.to_bytes();
                 let input_len = input_bytes.len();
                 let timestamp = Local::now().format("%Y-%m-%d %H:%M:%S");
 
@@ -302,131 +450,200 @@ pub trait FuzzerOrchestrator: AsRef<FuzzerState> + AsMut<FuzzerState> {
     /// The main entry point for running a fuzzing campaign.
     ///
     /// This function orchestrates the entire fuzzing process:
-    /// 1. Calls `self.init()` for one-time setup.
-    /// 2. Defines a `harness` closure that wraps `self.execute()` and updates the coverage map
-    ///    (and optionally the instruction count).
-    /// 3. Sets up `libafl` components:
-    ///    - A `HitcountsMapObserver` to monitor the `COVERAGE_MAP`.
+    /// 1. Creates the artifact directories and prints the session summary.
+    /// 2. Calls `self.init()` for one-time setup, allocates the coverage map and takes the
+    ///    snapshots of canisters reset with [`ResetStrategy::Snapshot`](crate::fuzzer::ResetStrategy::Snapshot).
+    /// 3. Defines a `harness` closure that resets the canisters, wraps `self.execute()` and
+    ///    updates the coverage map (and optionally the instruction count).
+    /// 4. Sets up `libafl` components:
+    ///    - A `HitcountsMapObserver` to monitor the fuzzer's coverage map.
     ///    - `AflMapFeedback` for coverage-guided feedback and `CrashFeedback` for finding crashes.
+    ///    - A `CrashTriageFeedback` that groups the crashes into buckets and, by default, keeps
+    ///      one input per bucket (see [`crash_triage_config`](Self::crash_triage_config)).
+    ///    - A `CrashReportFeedback` that writes a JSON report next to every saved crash.
     ///    - Optionally, `InstructionCountObserver` and `InstructionCountFeedback` when
     ///      [`instruction_config`](Self::instruction_config) has `enabled: true`.
-    ///    - A `StdState` to hold the fuzzer's state (corpus, solutions, etc.).
-    ///    - A `SimpleEventManager` with a `SimpleMonitor` for logging.
-    ///    - A `QueueScheduler` to decide which input to fuzz next.
+    ///    - A `StdState` to hold the fuzzer's state (corpus, solutions, etc.). When resuming
+    ///      a campaign, the state saved by the previous run is restored instead.
+    ///    - A `SimpleEventManager` with a monitor that prints a line per event, or shows the
+    ///      terminal UI if the state was built [`with_tui`](crate::fuzzer::FuzzerBuilder::with_tui).
+    ///    - An `IndexesLenTimeMinimizerScheduler` over a `StdWeightedScheduler` with the FAST
+    ///      power schedule to decide which input to fuzz next, favoring short and fast inputs
+    ///      that cover rare edges.
     ///    - An `InProcessExecutor` to run the harness.
-    /// 4. Loads the initial seed corpus from the directory provided by `corpus_dir()`.
-    /// 5. Configures mutational stages, including a `HavocScheduledMutator`.
-    /// 6. Starts the main fuzzing loop.
+    /// 5. Unless the state was restored, loads the initial seed corpus from the directory
+    ///    provided by `corpus_dir()`, decoding each file with [`FuzzerInput::from_bytes`]. If
+    ///    the directory is empty, the corpus starts from [`FuzzerInput::initial_input`].
+    /// 6. Configures a power mutational stage with the mutator returned by [`mutator`](Self::mutator),
+    ///    followed by stages that write the statistics and save the state every minute. If a
+    ///    coverage canister is built [`with_cmplog`](crate::fuzzer::CanisterBuilder::with_cmplog),
+    ///    every new corpus entry is first traced once and mutated by an input-to-state stage.
+    /// 7. Starts the main fuzzing loop.
+    ///
+    /// Every minute, the `libafl` state is saved next to the input directory (as `fuzzer_state`,
+    /// or `fuzzer_state_<worker>` in parallel mode), so that the campaign can be continued with
+    /// [`FuzzerBuilder::with_resume`](crate::fuzzer::FuzzerBuilder::with_resume). The
+    /// instrumentation seeds of the coverage canisters are saved as well. Statistics are
+    /// written to `plot_data.csv` and `fuzzer_stats` in the same directory (see [`crate::stats`]).
+    ///
+    /// If the `FuzzerState` was built with [`FuzzerBuilder::with_workers`](crate::fuzzer::FuzzerBuilder::with_workers)
+    /// or [`FuzzerBuilder::with_cores`](crate::fuzzer::FuzzerBuilder::with_cores), steps 2-7 run in one
+    /// forked worker process per core instead, each with its own `PocketIc` instance. The workers
+    /// share the artifact directories and exchange new inputs through a `libafl` `Launcher`
+    /// (LLMP broker) using an `LlmpRestartingEventManager`, which also restarts a worker
+    /// from its last saved state if it crashes.
+    ///
+    /// The campaign runs until it is interrupted, unless stop conditions are set on the
+    /// [`FuzzerBuilder`](crate::fuzzer::FuzzerBuilder) (e.g.
+    /// [`with_max_duration`](crate::fuzzer::FuzzerBuilder::with_max_duration)). When one is
+    /// met, the state is saved, a [`RunSummary`] is printed and returned. In parallel mode,
+    /// `run` returns once every worker has stopped.
     #[allow(static_mut_refs)]
-    fn run(&mut self) {
-        self.init();
+    fn run(&mut self) -> RunSummary {
+        let rng_seed = self.as_ref().rng_seed().unwrap_or_else(current_nanos);
+        if let Some(resume) = self.as_ref().resume() {
+            println!("Resuming campaign {resume:?}");
+        }
+        let input_dir = self.input_dir();
+        let crashes_dir = self.crashes_dir();
+        let corpus_dir = self.corpus_dir();
+        let probe_tables = probe_tables(self.as_mut());
+        // With sequential probe IDs only, every map entry is a probe, so the terminal UI can
+        // show the share of the instrumented probes that are covered.
+        let probes = (!self.as_ref().hashed_coverage())
+            .then(|| probe_tables.iter().map(|(_, table)| table.len()).sum());
 
-        let inst_config = Self::instruction_config();
+        // The terminal UI redirects stdout, so it is started before anything else is printed.
+        let monitor = FuzzerMonitor::new(
+            self.as_ref().tui(),
+            MonitorInfo {
+                name: self.as_ref().name().to_string(),
+                input_dir: input_dir.clone(),
+                crashes_dir: crashes_dir.clone(),
+                rng_seed,
+                log_file: input_dir.with_file_name("fuzzer.log"),
+                probes,
+            },
+        );
 
-        let mut harness = |input: &BytesInput| {
-            self.setup();
-            let result = self.execute(input.clone());
-            self.set_coverage_map();
-            if inst_config.enabled && self.set_instruction_count(input) {
-                return ExitKind::Crash;
-            }
-            result
-        };
+        // Store session info for diagnostic output on exit.
+        let _ = SESSION_INFO.set(SessionInfo {
+            name: self.as_ref().name().to_string(),
+            corpus_dir,
+            input_dir,
+            crashes_dir,
+            rng_seed,
+            tui: monitor.is_tui(),
+        });
+        let session = SESSION_INFO.get().unwrap();
+        fix_instrumentation_seeds(
+            self.as_mut(),
+            &session.input_dir.with_file_name("instrumentation_seeds"),
+            session.rng_seed,
+        );
+        write_skipped_functions(
+            self.as_mut(),
+            &session.input_dir.with_file_name("skipped_functions"),
+        );
+        write_probe_table(
+            &probe_tables,
+            &session.input_dir.with_file_name("probe_table"),
+        );
 
-        let hitcount_map_observer = HitcountsMapObserver::new(unsafe {
-            StdMapObserver::new("coverage_map", self.get_coverage_map())
-        })
-        .track_indices();
+        // Print session info at startup so the user can see artifact paths. The terminal UI
+        // shows them instead.
+        if !session.tui {
+            print_session_info();
+        }
 
-        // AflMapFeedback must be created before the observer is moved into a tuple.
-        let afl_map_feedback = AflMapFeedback::new(&hitcount_map_observer);
+        // Install a panic hook that prints session info before the default handler.
+        let default_hook = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            restore_terminal();
+            print_session_info();
+            default_hook(info);
+        }));
 
-        let candid_enabled = Self::get_candid_args().is_some();
+        let Some(parallel) = self.as_ref().parallel_config().cloned() else {
+            // Install a Ctrl+C handler that prints session info before exiting.
+            let _ = ctrlc::set_handler(move || {
+                restore_terminal();
+                print_session_info();
+                std::process::exit(130);
+            });
 
-        // The observer/feedback/stage types differ at compile time depending on
-        // configuration, so we branch into different macro invocations here.
-        // Each combination of (instruction count, candid mutator) needs its own
-        // call because `tuple_list!` is a compile-time construct.
-        match (inst_config.enabled, candid_enabled) {
-            (true, true) => {
-                use crate::custom::feedback::instruction_count::InstructionCountFeedback;
-                use crate::custom::observer::instruction_count::INSTRUCTION_COUNT_OBSERVER_NAME;
-                use crate::libafl::observers::RefCellValueObserver;
-                use crate::libafl_bolts::ownedref::OwnedRef;
-                use std::ptr::addr_of;
-
-                let instruction_count_observer = unsafe {
-                    RefCellValueObserver::new(
-                        INSTRUCTION_COUNT_OBSERVER_NAME,
-                        OwnedRef::from_ptr(addr_of!(INSTRUCTION_MAP)),
-                    )
-                };
-                let candid_mutator = CandidParserMutator::new(Self::get_candid_args());
-
-                let feedback =
-                    feedback_or!(afl_map_feedback.clone(), InstructionCountFeedback::new());
-                run_fuzzing_loop!(
-                    self,
-                    &mut harness,
-                    hitcount_map_observer,
-                    (instruction_count_observer),
-                    (StdPowerMutationalStage::new(candid_mutator)),
-                    afl_map_feedback,
-                    feedback
-                );
-            }
-            (true, false) => {
-                use crate::custom::feedback::instruction_count::InstructionCountFeedback;
-                use crate::custom::observer::instruction_count::INSTRUCTION_COUNT_OBSERVER_NAME;
-                use crate::libafl::observers::RefCellValueObserver;
-                use crate::libafl_bolts::ownedref::OwnedRef;
-                use std::ptr::addr_of;
-
-                let instruction_count_observer = unsafe {
-                    RefCellValueObserver::new(
-                        INSTRUCTION_COUNT_OBSERVER_NAME,
-                        OwnedRef::from_ptr(addr_of!(INSTRUCTION_MAP)),
-                    )
-                };
-
-                let feedback =
-                    feedback_or!(afl_map_feedback.clone(), InstructionCountFeedback::new());
-                run_fuzzing_loop!(
-                    self,
-                    &mut harness,
-                    hitcount_map_observer,
-                    (instruction_count_observer),
-                    (),
-                    afl_map_feedback,
-                    feedback
-                );
-            }
-            (false, true) => {
-                let candid_mutator = CandidParserMutator::new(Self::get_candid_args());
-                let feedback = afl_map_feedback.clone();
-                run_fuzzing_loop!(
-                    self,
-                    &mut harness,
-                    hitcount_map_observer,
-                    (),
-                    (StdPowerMutationalStage::new(candid_mutator)),
-                    afl_map_feedback,
-                    feedback
-                );
-            }
-            (false, false) => {
-                let feedback = afl_map_feedback.clone();
-                run_fuzzing_loop!(
-                    self,
-                    &mut harness,
-                    hitcount_map_observer,
-                    (),
-                    (),
-                    afl_map_feedback,
-                    feedback
-                );
-            }
+            let summary = run_fuzzing_worker!(
+                self,
+                I,
+                None,
+                SimpleEventManager::new(monitor),
+                session,
+                session.rng_seed,
+                WorkerFiles::new(session, None)
+            );
+            println!("{summary}");
+            print_session_info();
+            return summary;
+        };
+
+        println!(
+            "Launching {} fuzzing workers on cores {:?} (broker port {})",
+            parallel.cores.ids.len(),
+            parallel.cores.ids,
+            parallel.broker_port
+        );
+
+        let shmem_provider = StdShMemProvider::new().expect("Failed to initialize shared memory");
+        let run_client = |state: Option<_>,
+                          mgr: LlmpRestartingEventManager<_, _, _, _, _>,
+                          client: ClientDescription| {
+            // Workers are forked from this process, so each one initializes its own
+            // `PocketIc` instance and uses a distinct RNG seed.
+            let summary = run_fuzzing_worker!(
+                self,
+                I,
+                state,
+                mgr,
+                session,
+                session.rng_seed.wrapping_add(client.id() as u64),
+                WorkerFiles::new(session, Some(client.id()))
+            );
+            let json = serde_json::to_vec_pretty(&summary)
+                .map_err(|e| Error::serialize(format!("Failed to serialize the summary: {e}")))?;
+            fs::write(WorkerFiles::new(session, Some(client.id())).summary, json)?;
+            Ok(())
+        };
+        remove_worker_summaries(session.input_dir.parent().unwrap());
+
+        // Every worker instruments and installs its canisters independently, so inputs
+        // received from other workers must be re-executed to get locally valid coverage.
+        let started = Instant::now();
+        let launcher_pid = std::process::id();
+        let result = Launcher::builder()
+            .shmem_provider(shmem_provider)
+            .configuration(EventConfig::AlwaysUnique)
+            .monitor(monitor)
+            .run_client(run_client)
+            .cores(&parallel.cores)
+            .broker_port(parallel.broker_port)
+            .build()
+            .launch();
+        // The worker processes, and the processes respawning them, return from `launch`
+        // too once they stop. Only the broker process returns from `run`.
+        if std::process::id() != launcher_pid {
+            std::process::exit(0);
         }
+        match result {
+            Ok(()) | Err(Error::ShuttingDown) => {}
+            Err(e) => panic!("Failed to run the fuzzing workers: {e}"),
+        }
+        let mut summary = collect_worker_summaries(session.input_dir.parent().unwrap());
+        summary.corpus_size = corpus_entries(&session.input_dir).len();
+        summary.crashes = corpus_entries(&session.crashes_dir).len();
+        summary.duration = started.elapsed();
+        println!("{summary}");
+        print_session_info();
+        summary
     }
 
     /// Executes a single input against the orchestrator's harness.
@@ -437,81 +654,812 @@ pub trait FuzzerOrchestrator: AsRef<FuzzerState> + AsMut<FuzzerState> {
     ///
     /// # Arguments
     ///
-    /// * `bytes` - The raw byte vector of the input to be tested.
+    /// * `bytes` - The input to be tested, serialized as by [`FuzzerInput::to_bytes`]
+    ///   (the raw bytes for `BytesInput`).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `bytes` cannot be deserialized into an input.
     fn test_one_input(&mut self, bytes: Vec<u8>) {
+        let input = I::from_bytes(&bytes).expect("Failed to deserialize the input");
         self.init();
         self.setup();
-        let result = self.execute(BytesInput::new(bytes));
+        let result = self.execute(input);
         println!("Execution result: {result:?}");
     }
+
+    /// Shrinks a crashing input and writes the result next to it, as `<file>.min`.
+    ///
+    /// The canisters are initialized as for a fuzzing campaign, then the input is executed
+    /// repeatedly with `setup`/`execute`, keeping each smaller variant that crashes with the
+    /// same [`CrashSignature`](crate::custom::feedback::crash_triage::CrashSignature) (without
+    /// the covered edges). If [`get_candid_args`](Self::get_candid_args) is configured, the
+    /// Candid-encoded arguments are first shrunk structurally with
+    /// [`minimize_candid`](crate::minimize::minimize_candid). Then bytes are removed with
+    /// [`minimize_bytes`](crate::minimize::minimize_bytes), skipping the variants that cannot
+    /// be deserialized with [`FuzzerInput::from_bytes`].
+    ///
+    /// Inputs treated as crashes because of [`InstructionConfig::max_instruction_count`] are
+    /// not supported. Returns the path of the minimized input. The `.min` files are not counted
+    /// as crashes, e.g. by [`RunSummary::crashes`] or
+    /// [`StopConditions::stop_on_first_crash`](crate::fuzzer::StopConditions::stop_on_first_crash).
+    ///
+    /// # Panics
+    ///
+    /// Panics if the file cannot be read or written, cannot be deserialized, or does not crash.
+    fn minimize_input(&mut self, crash_file: &Path) -> PathBuf {
+        let bytes = fs::read(crash_file)
+            .unwrap_or_else(|e| panic!("Failed to read {}: {e}", crash_file.display()));
+        I::from_bytes(&bytes).expect("Failed to deserialize the input");
+
+        prepare_replay(self);
+
+        let mut executions = 0;
+        let mut signature_of = |bytes: &[u8]| {
+            let input = I::from_bytes(bytes).ok()?;
+            executions += 1;
+            let exit_kind = replay(self, input);
+            (exit_kind != ExitKind::Ok)
+                .then(|| CrashSignature::new(&exit_kind, last_call().reject.as_ref(), None))
+        };
+
+        let signature = signature_of(&bytes).expect("The input does not crash");
+        println!(
+            "Minimizing {} ({} bytes): {signature}",
+            crash_file.display(),
+            bytes.len()
+        );
+
+        let mut minimized = bytes.clone();
+        if let Some(candid_args) = Self::get_candid_args() {
+            let (env, arg_types) = method_arg_types(&candid_args);
+            minimized = minimize_candid(minimized, &env, &arg_types, |b| {
+                signature_of(b).as_ref() == Some(&signature)
+            });
+        }
+        minimized = minimize_bytes(minimized, |b| signature_of(b).as_ref() == Some(&signature));
+
+        let mut file_name = crash_file.file_name().unwrap_or_default().to_os_string();
+        file_name.push(".min");
+        let minimized_file = crash_file.with_file_name(file_name);
+        fs::write(&minimized_file, &minimized)
+            .unwrap_or_else(|e| panic!("Failed to write {}: {e}", minimized_file.display()));
+        println!(
+            "Minimized to {} bytes in {executions} executions: {}",
+            minimized.len(),
+            minimized_file.display()
+        );
+        minimized_file
+    }
+
+    /// Writes a smallest subset of the corpus in `src` that covers the same edges to `dst`.
+    ///
+    /// The canisters are initialized as for a fuzzing campaign, then every entry of `src` is
+    /// replayed and the edges it hits are recorded with their hit count buckets, as in
+    /// `afl-cmin`. The subset is selected with
+    /// [`select_covering_subset`](crate::minimize::select_covering_subset), preferring
+    /// short inputs and, if [`instruction_config`](Self::instruction_config) is enabled, inputs
+    /// with low instruction counts. The selected entries are copied to `dst` with their file
+    /// names, so the output of a campaign (its `input` directory) can be folded back into the
+    /// seed corpus.
+    ///
+    /// Entries that cannot be deserialized with [`FuzzerInput::from_bytes`] or that crash are
+    /// skipped. Returns the paths of the copied entries.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `src` cannot be read, or `dst` cannot be created or written.
+    fn minimize_corpus(&mut self, src: &Path, dst: &Path) -> Vec<PathBuf> {
+        let paths = corpus_entries(src);
+        prepare_replay(self);
+
+        let count_instructions = Self::instruction_config().enabled;
+        let mut replayed = Vec::new();
+        let mut entries = Vec::new();
+        for path in paths {
+            let bytes = fs::read(&path)
+                .unwrap_or_else(|e| panic!("Failed to read {}: {e}", path.display()));
+            let input = match I::from_bytes(&bytes) {
+                Ok(input) => input,
+                Err(e) => {
+                    println!("Skipping {} that is not a valid input: {e}", path.display());
+                    continue;
+                }
+            };
+            let exit_kind = replay(self, input);
+            if exit_kind != ExitKind::Ok {
+                println!("Skipping {} that exits with {exit_kind:?}", path.display());
+                continue;
+            }
+            let edges = edge_tuples(&self.as_ref().coverage_map().to_vec());
+            let instructions = if count_instructions {
+                self.get_instruction_count().unwrap_or(0)
+            } else {
+                0
+            };
+            entries.push((edges, (bytes.len(), instructions)));
+            replayed.push(path);
+        }
+
+        let selected = select_covering_subset(&entries);
+        fs::create_dir_all(dst)
+            .unwrap_or_else(|e| panic!("Failed to create {}: {e}", dst.display()));
+        let copied: Vec<PathBuf> = selected
+            .into_iter()
+            .map(|index| {
+                let src_path = &replayed[index];
+                let dst_path = dst.join(src_path.file_name().unwrap());
+                fs::copy(src_path, &dst_path).unwrap_or_else(|e| {
+                    panic!(
+                        "Failed to copy {} to {}: {e}",
+                        src_path.display(),
+                        dst_path.display()
+                    )
+                });
+                dst_path
+            })
+            .collect();
+        let edges: HashSet<_> = entries.iter().flat_map(|(edges, _)| edges).collect();
+        println!(
+            "Kept {} of {} inputs, covering {} edge tuples: {}",
+            copied.len(),
+            replayed.len(),
+            edges.len(),
+            dst.display()
+        );
+        copied
+    }
+}
+
+/// How often a fuzzing worker saves its state, to resume the campaign later.
+const STATE_SAVE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// The files of a fuzzing worker, next to the input directory. In parallel mode, their names
+/// are suffixed with the ID of the worker.
+struct WorkerFiles {
+    /// The saved `libafl` state, see [`save_fuzzer_state`].
+    state: PathBuf,
+    /// The time series of the statistics, see [`crate::stats`].
+    plot_data: PathBuf,
+    /// The latest statistics, in the AFL `fuzzer_stats` format.
+    fuzzer_stats: PathBuf,
+    /// The [`RunSummary`] of the worker, only written in parallel mode.
+    summary: PathBuf,
+}
+
+impl WorkerFiles {
+    fn new(session: &SessionInfo, worker: Option<usize>) -> Self {
+        let suffix = worker.map(|id| format!("_{id}")).unwrap_or_default();
+        let file = |name: &str| session.input_dir.with_file_name(name);
+        Self {
+            state: file(&format!("fuzzer_state{suffix}")),
+            plot_data: file(&format!("plot_data{suffix}.csv")),
+            fuzzer_stats: file(&format!("fuzzer_stats{suffix}")),
+            summary: file(&format!("run_summary{suffix}.json")),
+        }
+    }
+}
+
+/// Samples the statistics of a fuzzing worker and writes them with `writer`.
+#[allow(static_mut_refs)]
+fn write_stats<I: FuzzerInput>(
+    writer: &mut StatsWriter,
+    state: &FuzzerStdState<I>,
+    map_name: &str,
+) -> Result<(), Error> {
+    let stats = CampaignStats {
+        execs: *state.executions(),
+        corpus_size: state.corpus().count(),
+        edges: covered_edges(state, map_name),
+        objectives: state
+            .metadata::<ObjectiveCounts>()
+            .copied()
+            .unwrap_or_default(),
+        max_instructions: unsafe { INSTRUCTION_MAP.borrow().max_instructions },
+        ..CampaignStats::default()
+    };
+    writer.write(stats)?;
+    Ok(())
 }
 
-/// Macro to avoid duplicating the fuzzing loop for different observer/feedback/stage
-/// type tuples. The observer tuple, feedback composition, and mutation stages differ
-/// depending on configuration (instruction count, Candid mutator), but the rest of
-/// the loop (state, executor, corpus loading) is identical.
+/// Gives every coverage canister with a [`Seed::Random`] a fixed seed derived from `rng_seed`
+/// and writes the seeds to `seeds_file`, one `<canister name> <seed>` per line.
 ///
+/// When resuming a campaign, the seeds written by that campaign are used instead, so the
+/// canisters are instrumented with the same edge IDs and the restored coverage history stays
+/// valid. This runs before the workers are started, so they all share the same seeds.
+fn fix_instrumentation_seeds(state: &mut FuzzerState, seeds_file: &Path, rng_seed: u64) {
+    let mut rng = StdRand::with_seed(rng_seed);
+    let saved: Vec<(String, u32)> = if state.resume().is_some() {
+        fs::read_to_string(seeds_file)
+            .unwrap_or_default()
+            .lines()
+            .filter_map(|line| {
+                let (name, seed) = line.rsplit_once(' ')?;
+                Some((name.to_string(), seed.parse().ok()?))
+            })
+            .collect()
+    } else {
+        Vec::new()
+    };
+
+    let mut lines = String::new();
+    for info in state
+        .get_iter_mut_canister_info()
+        .filter(|info| info.ty == CanisterType::Coverage)
+    {
+        if let Some((_, seed)) = saved.iter().find(|(name, _)| *name == info.name) {
+            info.seed = Seed::Static(*seed);
+        } else if let Seed::Random = info.seed {
+            info.seed = Seed::Static(rng.next() as u32);
+        }
+        if let Seed::Static(seed) = info.seed {
+            lines.push_str(&format!("{} {seed}\n", info.name));
+        }
+    }
+    if let Err(e) = fs::write(seeds_file, lines) {
+        println!(
+            "Failed to save the instrumentation seeds to {}: {e}",
+            seeds_file.display()
+        );
+    }
+}
+
+/// Writes the functions that the [`FunctionFilter`](crate::instrumentation::FunctionFilter)
+/// of each coverage canister excludes from instrumentation to `report_file`, as
+/// `<canister name> func[<index>] <function name>` lines.
+///
+/// Nothing is written if no canister has a function filter.
+fn write_skipped_functions(state: &mut FuzzerState, report_file: &Path) {
+    let mut lines = String::new();
+    let mut filtered = false;
+    for info in state
+        .get_iter_mut_canister_info()
+        .filter(|info| info.ty == CanisterType::Coverage && !info.function_filter.is_empty())
+    {
+        filtered = true;
+        let wasm_bytes = read_canister_bytes(info.wasm_path.clone());
+        let skipped = skipped_functions(&wasm_bytes, &info.function_filter);
+        println!(
+            "Skipping {} function(s) of '{}' excluded by its function filter",
+            skipped.len(),
+            info.name
+        );
+        for (index, name) in skipped {
+            let name = name.as_deref().unwrap_or("<unnamed>");
+            lines.push_str(&format!("{} func[{index}] {name}\n", info.name));
+        }
+    }
+    if !filtered {
+        return;
+    }
+    match fs::write(report_file, lines) {
+        Ok(()) => println!(
+            "The skipped functions are listed in {}",
+            report_file.display()
+        ),
+        Err(e) => println!(
+            "Failed to save the skipped functions to {}: {e}",
+            report_file.display()
+        ),
+    }
+}
+
+/// Returns the name and the probe table of each coverage canister instrumented with
+/// sequential probe IDs (see [`probe_table`](crate::instrumentation::probe_table)).
+fn probe_tables(state: &mut FuzzerState) -> Vec<(String, Vec<(u32, usize)>)> {
+    state
+        .get_iter_mut_canister_info()
+        .filter(|info| info.ty == CanisterType::Coverage && info.sequential_probe_ids)
+        .map(|info| {
+            let wasm_bytes = read_canister_bytes(info.wasm_path.clone());
+            let table = probe_table(&wasm_bytes, &info.function_filter);
+            (info.name.clone(), table)
+        })
+        .collect()
+}
+
+/// Writes the `probe_tables` to `report_file`, as
+/// `<canister name> <probe id> func[<index>] <instruction offset>` lines, so every entry of
+/// the coverage map can be traced back to code.
+///
+/// Nothing is written if no canister uses sequential probe IDs.
+fn write_probe_table(probe_tables: &[(String, Vec<(u32, usize)>)], report_file: &Path) {
+    if probe_tables.is_empty() {
+        return;
+    }
+    let mut lines = String::new();
+    for (name, table) in probe_tables {
+        for (id, (index, offset)) in table.iter().enumerate() {
+            lines.push_str(&format!("{name} {id} func[{index}] {offset:#x}\n"));
+        }
+    }
+    match fs::write(report_file, lines) {
+        Ok(()) => println!(
+            "The coverage probes are listed in {}",
+            report_file.display()
+        ),
+        Err(e) => println!(
+            "Failed to save the probe table to {}: {e}",
+            report_file.display()
+        ),
+    }
+}
+
+/// Saves the state of a fuzzing worker to `path`, including the maximum instruction count.
+#[allow(static_mut_refs)]
+fn save_fuzzer_state<I: FuzzerInput>(
+    state: &mut FuzzerStdState<I>,
+    path: &Path,
+) -> Result<(), Error> {
+    state.add_metadata(unsafe { *INSTRUCTION_MAP.borrow() });
+    let bytes = postcard::to_allocvec(&*state)
+        .map_err(|e| Error::serialize(format!("Failed to serialize the fuzzer state: {e}")))?;
+    // Write to a temporary file first, so an interrupted save never corrupts the state.
+    let tmp_path = path.with_extension("tmp");
+    fs::write(&tmp_path, bytes)?;
+    fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
+/// Loads a state saved by [`save_fuzzer_state`] and restores the maximum instruction count.
+/// Returns `None` if there is no saved state.
+///
+/// # Panics
+///
+/// Panics if the saved state cannot be deserialized.
+#[allow(static_mut_refs)]
+fn load_fuzzer_state<I: FuzzerInput>(path: &Path) -> Option<FuzzerStdState<I>> {
+    let Ok(bytes) = fs::read(path) else {
+        println!(
+            "No saved fuzzer state in {}, starting from the seed corpus",
+            path.display()
+        );
+        return None;
+    };
+    let state: FuzzerStdState<I> = postcard::from_bytes(&bytes)
+        .unwrap_or_else(|e| panic!("Failed to load the fuzzer state {}: {e}", path.display()));
+    if let Ok(map) = state.metadata::<InstructionCountMap>() {
+        unsafe { *INSTRUCTION_MAP.borrow_mut() = *map };
+    }
+    println!(
+        "Restored the fuzzer state from {} ({} corpus entries, {} executions)",
+        path.display(),
+        state.corpus().count(),
+        state.executions()
+    );
+    Some(state)
+}
+
+/// Checks the [`StopConditions`] of a fuzzing worker once per fuzzing iteration.
+struct StopMonitor {
+    conditions: StopConditions,
+    started: Instant,
+    initial_execs: u64,
+    edges: usize,
+    last_new_coverage: Instant,
+    /// The crashes directory is shared by the workers, so a crash saved by any worker
+    /// stops all of them.
+    crashes_dir: PathBuf,
+    initial_crashes: usize,
+    last_crash_check: Instant,
+}
+
+impl StopMonitor {
+    /// How often the crashes directory is listed with `stop_on_first_crash`.
+    const CRASH_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+    fn new(conditions: StopConditions, crashes_dir: PathBuf, initial_execs: u64) -> Self {
+        let now = Instant::now();
+        let initial_crashes = if conditions.stop_on_first_crash {
+            corpus_entries(&crashes_dir).len()
+        } else {
+            0
+        };
+        Self {
+            conditions,
+            started: now,
+            initial_execs,
+            edges: 0,
+            last_new_coverage: now,
+            crashes_dir,
+            initial_crashes,
+            last_crash_check: now,
+        }
+    }
+
+    /// Returns the condition that is met, given the total executions and covered edges.
+    fn check(&mut self, execs: u64, edges: usize) -> Option<StopReason> {
+        let now = Instant::now();
+        if edges > self.edges {
+            self.edges = edges;
+            self.last_new_coverage = now;
+        }
+        let conditions = &self.conditions;
+        if conditions
+            .max_duration
+            .is_some_and(|d| now - self.started >= d)
+        {
+            return Some(StopReason::MaxDuration);
+        }
+        if conditions
+            .max_execs
+            .is_some_and(|n| execs.saturating_sub(self.initial_execs) >= n)
+        {
+            return Some(StopReason::MaxExecs);
+        }
+        if conditions
+            .max_time_without_coverage
+            .is_some_and(|d| now - self.last_new_coverage >= d)
+        {
+            return Some(StopReason::NoNewCoverage);
+        }
+        if conditions.stop_on_first_crash
+            && now - self.last_crash_check >= Self::CRASH_CHECK_INTERVAL
+        {
+            self.last_crash_check = now;
+            if corpus_entries(&self.crashes_dir).len() > self.initial_crashes {
+                return Some(StopReason::Crash);
+            }
+        }
+        None
+    }
+}
+
+/// Returns the number of edges covered so far, as tracked by the map feedback `map_name`.
+fn covered_edges<I: FuzzerInput>(state: &FuzzerStdState<I>, map_name: &str) -> usize {
+    state
+        .named_metadata_map()
+        .get::<MapFeedbackMetadata<u8>>(map_name)
+        .map_or(0, |m| m.num_covered_map_indexes)
+}
+
+/// The ratio of covered entries of the coverage map above which a fuzzing worker warns that
+/// the map is filling up.
+const MAP_FILL_WARNING_RATIO: f64 = 0.5;
+
+/// Returns the ratio of covered entries of the map tracked by the map feedback `map_name`.
+fn map_fill_ratio<I: FuzzerInput>(state: &FuzzerStdState<I>, map_name: &str) -> Option<f64> {
+    state
+        .named_metadata_map()
+        .get::<MapFeedbackMetadata<u8>>(map_name)
+        .filter(|m| !m.history_map.is_empty())
+        .map(|m| m.num_covered_map_indexes as f64 / m.history_map.len() as f64)
+}
+
+/// Removes the summaries written by the workers of a previous run of the campaign.
+fn remove_worker_summaries(campaign_dir: &Path) {
+    for path in worker_summaries(campaign_dir) {
+        let _ = fs::remove_file(path);
+    }
+}
+
+/// Returns the summary files written by the workers, sorted by worker.
+fn worker_summaries(campaign_dir: &Path) -> Vec<PathBuf> {
+    let mut paths: Vec<PathBuf> = fs::read_dir(campaign_dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| {
+            p.file_name()
+                .and_then(|n| n.to_str())
+                .is_some_and(|n| n.starts_with("run_summary_") && n.ends_with(".json"))
+        })
+        .collect();
+    paths.sort();
+    paths
+}
+
+/// Combines the summaries written by the workers into the summary of the campaign.
+/// The corpus size, crashes and duration are left for the caller to fill in.
+fn collect_worker_summaries(campaign_dir: &Path) -> RunSummary {
+    let mut summary = RunSummary::default();
+    for path in worker_summaries(campaign_dir) {
+        let worker: RunSummary = match fs::read(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|bytes| serde_json::from_slice(&bytes).map_err(|e| e.to_string()))
+        {
+            Ok(worker) => worker,
+            Err(e) => {
+                println!("Skipping the worker summary {}: {e}", path.display());
+                continue;
+            }
+        };
+        summary.stop_reason = summary.stop_reason.or(worker.stop_reason);
+        summary.execs += worker.execs;
+        summary.edges = summary.edges.max(worker.edges);
+    }
+    summary
+}
+
+/// Returns the files of a corpus directory that may be inputs.
+///
+/// Hidden files, such as the `.gitignore` or the metadata and lock files written by `libafl`,
+/// the JSON crash reports, the minimized crashes written by
+/// [`FuzzerOrchestrator::minimize_input`] and the instruction count log are skipped.
+fn corpus_entries(dir: &Path) -> Vec<PathBuf> {
+    let mut entries: Vec<PathBuf> = fs::read_dir(dir)
+        .unwrap_or_else(|e| panic!("Failed to read the corpus directory {}: {e}", dir.display()))
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.is_file())
+        .filter(|p| {
+            p.file_name().and_then(|n| n.to_str()).is_some_and(|name| {
+                !name.starts_with('.')
+                    && !name.ends_with(".json")
+                    && !name.ends_with(".min")
+                    && name != "instruction_log.txt"
+            })
+        })
+        .collect();
+    entries.sort();
+    entries
+}
+
+/// Initializes the canisters for replaying inputs outside of the fuzzing loop, as a fuzzing
+/// worker does.
+fn prepare_replay<I: FuzzerInput, O: FuzzerOrchestrator<I> + ?Sized>(orchestrator: &mut O) {
+    orchestrator.init();
+    orchestrator.as_mut().init_coverage_map();
+    orchestrator.as_mut().prepare_reset();
+}
+
+/// Executes an input as the harness of the fuzzing loop does, without instruction counting,
+/// and updates the coverage map.
+fn replay<I: FuzzerInput, O: FuzzerOrchestrator<I> + ?Sized>(
+    orchestrator: &O,
+    input: I,
+) -> ExitKind {
+    orchestrator.as_ref().reset_canisters();
+    clear_last_call();
+    orchestrator.setup();
+    let exit_kind = orchestrator.execute(input);
+    orchestrator.set_coverage_map();
+    exit_kind
+}
+
+/// Runs a single fuzzing worker: initializes the canisters, builds the observers, feedbacks
+/// and stages for the current configuration and enters [`run_fuzzing_loop!`].
+///
+/// `$input` is the input type, `$state` the worker state restored by the event manager
+/// after a restart (or `None`), `$mgr` the event manager, `$session` the [`SessionInfo`]
+/// with the shared artifact directories, `$rng_seed` the seed of this worker and
+/// `$files` the [`WorkerFiles`] the worker state and statistics are saved to. When resuming
+/// a campaign, the state is loaded from `$files.state` if the event manager did not restore
+/// one.
+///
+/// Evaluates to the [`RunSummary`] of the worker once a stop condition is met.
+#[macro_export]
+macro_rules! run_fuzzing_worker {
+    ($self:expr, $input:ty, $state:expr, $mgr:expr, $session:expr, $rng_seed:expr, $files:expr) => {{
+        $self.init();
+        $self.as_mut().init_coverage_map();
+        $self.as_mut().prepare_reset();
+
+        let files: WorkerFiles = $files;
+        let restored_state: Option<FuzzerStdState<$input>> = match $state {
+            Some(state) => Some(state),
+            None if $self.as_ref().resume().is_some() => load_fuzzer_state(&files.state),
+            None => None,
+        };
+
+        let inst_config = Self::instruction_config();
+        let stop_conditions = $self.as_ref().stop_conditions().clone();
+        let canisters = $self
+            .as_ref()
+            .get_canister_names()
+            .iter()
+            .map(|name| format!("{name} ({})", $self.as_ref().get_canister_id_by_name(name)))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let tokens = $self.as_ref().dictionary().clone();
+        let warn_on_full_map = $self.as_ref().hashed_coverage();
+
+        let mut harness = |input: &$input| {
+            $self.as_ref().reset_canisters();
+            $crate::custom::feedback::crash_report::clear_last_call();
+            $self.setup();
+            let result = $self.execute(input.clone());
+            $self.set_coverage_map();
+            if inst_config.enabled && $self.set_instruction_count(input) {
+                return ExitKind::Crash;
+            }
+            result
+        };
+
+        // Reruns an input for the CmpLog tracing stage, collecting the comparisons it makes.
+        // The CmpLog buffers are not cleared first, so sites the input does not reach may
+        // still hold operands of earlier executions. They only add candidate replacements.
+        let cmplog_enabled = $self.as_ref().cmplog_enabled();
+        let mut tracing_harness = |input: &$input| {
+            $self.as_ref().reset_canisters();
+            $crate::custom::feedback::crash_report::clear_last_call();
+            $self.setup();
+            let result = $self.execute(input.clone());
+            $self.set_coverage_map();
+            $self.set_cmplog_map();
+            result
+        };
+
+        // The tracer has its own executor, so the comparisons are only fetched when tracing.
+        let cmplog_observer = unsafe {
+            StdCmpObserver::new(
+                CMPLOG_OBSERVER_NAME,
+                OwnedRefMut::from_mut_ptr($self.as_ref().cmplog_map_ptr()),
+                true,
+            )
+        };
+
+        let coverage_map = $self.as_ref().coverage_map();
+        let hitcount_map_observer = HitcountsMapObserver::new(unsafe {
+            StdMapObserver::from_mut_ptr(
+                "coverage_map",
+                coverage_map.as_mut_ptr(),
+                coverage_map.len(),
+            )
+        })
+        .track_indices();
+
+        // AflMapFeedback must be created before the observer is moved into a tuple.
+        let afl_map_feedback = AflMapFeedback::new(&hitcount_map_observer);
+
+        // Inputs that bring the operands of a comparison closer are interesting. The map is
+        // empty, and the feedback never interesting, if no canister is built with value profiling.
+        let value_profile_map = $self.as_ref().value_profile_map();
+        let value_profile_observer = unsafe {
+            StdMapObserver::from_mut_ptr(
+                "value_profile_map",
+                value_profile_map.as_mut_ptr(),
+                value_profile_map.len(),
+            )
+        };
+        let value_profile_feedback = MaxMapFeedback::new(&value_profile_observer);
+
+        let mutator = $self.mutator();
+
+        // The observer/feedback types differ at compile time depending on
+        // configuration, so we branch into different macro invocations here.
+        // Each configuration needs its own call because `tuple_list!` is a
+        // compile-time construct.
+        if inst_config.enabled {
+            use std::ptr::addr_of;
+            use $crate::custom::feedback::instruction_count::InstructionCountFeedback;
+            use $crate::custom::observer::instruction_count::INSTRUCTION_COUNT_OBSERVER_NAME;
+            use $crate::libafl::observers::RefCellValueObserver;
+            use $crate::libafl_bolts::ownedref::OwnedRef;
+
+            let instruction_count_observer = unsafe {
+                RefCellValueObserver::new(
+                    INSTRUCTION_COUNT_OBSERVER_NAME,
+                    OwnedRef::from_ptr(addr_of!(INSTRUCTION_MAP)),
+                )
+            };
+
+            let feedback = feedback_or!(
+                afl_map_feedback.clone(),
+                value_profile_feedback,
+                InstructionCountFeedback::new()
+            );
+            run_fuzzing_loop!(
+                &mut harness,
+                $input,
+                restored_state,
+                $mgr,
+                $session,
+                $rng_seed,
+                files,
+                stop_conditions,
+                canisters,
+                tokens,
+                warn_on_full_map,
+                (&mut tracing_harness, cmplog_observer, cmplog_enabled),
+                hitcount_map_observer,
+                (value_profile_observer, instruction_count_observer),
+                mutator,
+                afl_map_feedback,
+                feedback
+            )
+        } else {
+            let feedback = feedback_or!(afl_map_feedback.clone(), value_profile_feedback);
+            run_fuzzing_loop!(
+                &mut harness,
+                $input,
+                restored_state,
+                $mgr,
+                $session,
+                $rng_seed,
+                files,
+                stop_conditions,
+                canisters,
+                tokens,
+                warn_on_full_map,
+                (&mut tracing_harness, cmplog_observer, cmplog_enabled),
+                hitcount_map_observer,
+                (value_profile_observer),
+                mutator,
+                afl_map_feedback,
+                feedback
+            )
+        }
+    }};
+}
+
+/// Macro to avoid duplicating the fuzzing loop for different observer/feedback
+/// type tuples. The observer tuple and feedback composition differ depending on
+/// configuration (instruction count), but the rest of the loop (state, executor,
+/// corpus loading, stages) is identical.
+///
+/// `$state` is a previously saved worker state. If it is `None`, a fresh state is created
+/// and the seed corpus is loaded; otherwise fuzzing resumes from the saved corpus.
+/// The state is saved to `$files.state` every [`STATE_SAVE_INTERVAL`] and the statistics to
+/// `$files.plot_data` and `$files.fuzzer_stats` every [`STATS_INTERVAL`](crate::stats::STATS_INTERVAL).
+/// Both are saved again when one of the `$stop_conditions` is met, after which the loop
+/// evaluates to a [`RunSummary`].
 /// `$map_observer` is the owned hitcount map observer. It is borrowed by the scheduler
 /// constructors, then moved into the observer tuple alongside any `$extra_observers`.
 /// `$afl_map_feedback` must be an already-constructed `AflMapFeedback` (created from
 /// the hitcount observer before the observer is moved into the tuple).
-/// `$extra_stages` are inserted before the havoc mutator stage (e.g. Candid mutator).
+/// `$mutator` is the mutator of the power mutational stage, for inputs of type `$input`.
+/// `$canisters` describes the canisters of the worker, for the terminal UI.
+/// `$tokens` is the dictionary of the canisters, stored in the state for the token mutations.
+/// If `$warn_on_full_map` is set, a warning is printed once when more than
+/// [`MAP_FILL_WARNING_RATIO`] of the coverage map is covered.
+/// `$tracing_harness` reruns an input and fills the CmpLog map read by `$cmplog_observer`.
+/// If `$cmplog` is set, it runs in a tracing stage whose comparisons feed an input-to-state
+/// mutation stage, once for every corpus entry, the first time it is scheduled.
 #[macro_export]
 macro_rules! run_fuzzing_loop {
-    ($self:expr, $harness:expr, $map_observer:expr, ($($extra_observer:expr),*), ($($extra_stage:expr),*), $afl_map_feedback:expr, $feedback:expr) => {{
+    ($harness:expr, $input:ty, $state:expr, $mgr:expr, $session:expr, $rng_seed:expr, $files:expr, $stop_conditions:expr, $canisters:expr, $tokens:expr, $warn_on_full_map:expr, ($tracing_harness:expr, $cmplog_observer:expr, $cmplog:expr), $map_observer:expr, ($($extra_observer:expr),*), $mutator:expr, $afl_map_feedback:expr, $feedback:expr) => {{
+        let started = Instant::now();
         let map_observer = $map_observer;
         let afl_map_feedback = $afl_map_feedback;
-        let mut feedback = $feedback;
+        let map_name = afl_map_feedback.name().clone();
+        // Crashing inputs that are not saved as solutions, because their crash bucket already
+        // has a representative, must not end up in the corpus either.
+        let ok_feedback: ExitKindFeedback<OkLogic> = ExitKindFeedback::new();
+        let mut feedback = feedback_and_fast!(ok_feedback, $feedback);
         let calibration_stage = CalibrationStage::new(&afl_map_feedback);
 
         let crash_feedback = CrashFeedback::new();
         let timeout_feedback = TimeoutFeedback::new();
         let oom_feedback: ExitKindFeedback<OomLogic> = ExitKindFeedback::new();
-        let mut objective = feedback_or!(crash_feedback, timeout_feedback, oom_feedback);
+        let triage_feedback = CrashTriageFeedback::new(&map_observer, Self::crash_triage_config());
+        let session: &SessionInfo = $session;
+        let report_feedback = CrashReportFeedback::new(
+            session.crashes_dir.clone(),
+            $rng_seed,
+            Self::get_candid_args().map(|args| args.method),
+            Self::instruction_config().enabled,
+        );
+        let mut objective = feedback_and_fast!(
+            feedback_or!(crash_feedback, timeout_feedback, oom_feedback),
+            triage_feedback,
+            report_feedback
+        );
 
         let stats_stage = AflStatsStage::builder()
             .map_feedback(&afl_map_feedback)
             .build()
             .unwrap();
 
-        let rng_seed = current_nanos();
-        let input_dir = $self.input_dir();
-        let crashes_dir = $self.crashes_dir();
-        let corpus_dir = $self.corpus_dir();
-
-        // Store session info for diagnostic output on exit.
-        let _ = SESSION_INFO.set(SessionInfo {
-            name: $self.as_ref().name().to_string(),
-            corpus_dir: corpus_dir.clone(),
-            input_dir: input_dir.clone(),
-            crashes_dir: crashes_dir.clone(),
-            rng_seed,
-        });
-
-        // Print session info at startup so the user can see artifact paths.
-        print_session_info();
-
-        // Install a panic hook that prints session info before the default handler.
-        let default_hook = std::panic::take_hook();
-        std::panic::set_hook(Box::new(move |info| {
-            print_session_info();
-            default_hook(info);
-        }));
-
-        // Install a Ctrl+C handler that prints session info before exiting.
-        let _ = ctrlc::set_handler(move || {
-            print_session_info();
-            std::process::exit(130);
-        });
-
-        let mut state = StdState::new(
-            StdRand::with_seed(rng_seed),
-            CachedOnDiskCorpus::new(input_dir, 512).unwrap(),
-            CachedOnDiskCorpus::new(crashes_dir, 512).unwrap(),
-            &mut feedback,
-            &mut objective,
-        )
-        .unwrap();
+        let mut mgr = $mgr;
+        let (mut state, restored): (FuzzerStdState<$input>, bool) = match $state {
+            Some(state) => (state, true),
+            None => (
+                StdState::new(
+                    StdRand::with_seed($rng_seed),
+                    CachedOnDiskCorpus::new(&session.input_dir, 512).unwrap(),
+                    CachedOnDiskCorpus::new(&session.crashes_dir, 512).unwrap(),
+                    &mut feedback,
+                    &mut objective,
+                )
+                .unwrap(),
+                false,
+            ),
+        };
+        // Replaces the dictionary of a restored state, the canisters may have changed since.
+        state.add_metadata::<Tokens>($tokens);
 
         // AFL++-style weighted scheduler with FAST power schedule, wrapped in a
         // corpus minimizer that favors short + fast inputs covering rare edges.
@@ -522,8 +1470,6 @@ macro_rules! run_fuzzing_loop {
         );
         let scheduler = IndexesLenTimeMinimizerScheduler::new(&map_observer, weighted);
 
-        let mon = SimpleMonitor::new(|s| println!("{s}"));
-        let mut mgr = SimpleEventManager::new(mon);
         let mut fuzzer = StdFuzzer::new(scheduler, feedback, objective);
 
         let observers = tuple_list!(map_observer $(, $extra_observer)*);
@@ -531,46 +1477,177 @@ macro_rules! run_fuzzing_loop {
             InProcessExecutor::new($harness, observers, &mut fuzzer, &mut state, &mut mgr)
                 .expect("Failed to create the Executor");
 
-        // Load initial inputs from the corpus directory, skipping non-input files.
-        fn is_corpus_entry(name: &str) -> bool {
-            name != "instruction_log.txt" && name != ".gitignore"
-        }
-        let corpus_entries: Vec<_> = fs::read_dir(&corpus_dir)
-            .unwrap()
-            .filter_map(|e| e.ok())
-            .map(|e| e.path())
-            .filter(|p| p.file_name().and_then(|n| n.to_str()).is_some_and(is_corpus_entry))
-            .collect();
-        if corpus_entries.is_empty() {
-            use rand::RngCore;
-            let mut rng = rand::rng();
-            let len = (rng.next_u32() % 1024 + 1) as usize;
-            let mut buf = vec![0u8; len];
-            rng.fill_bytes(&mut buf);
-            println!("Corpus was empty — using a randomly generated seed ({len} bytes)");
-            fuzzer.evaluate_input(&mut state, &mut executor, &mut mgr, &BytesInput::new(buf)).unwrap();
+        // The terminal UI shows the canisters of the workers.
+        if session.tui {
+            let executions = *state.executions();
+            mgr.fire(
+                &mut state,
+                EventWithStats::with_current_time(
+                    Event::UpdateUserStats {
+                        name: Cow::Borrowed(CANISTERS_STAT_NAME),
+                        value: UserStats::new(
+                            UserStatsValue::String(Cow::Owned($canisters)),
+                            AggregatorOps::None,
+                        ),
+                        phantom: PhantomData,
+                    },
+                    executions,
+                ),
+            )
+            .expect("Failed to report the canisters");
         }
-        for p in &corpus_entries {
-            let mut f = File::open(p).unwrap();
-            let mut buffer = Vec::new();
-            f.read_to_end(&mut buffer).unwrap();
-            fuzzer.evaluate_input(&mut state, &mut executor, &mut mgr, &BytesInput::new(buffer)).unwrap();
+
+        let mut stop_monitor = StopMonitor::new(
+            $stop_conditions,
+            session.crashes_dir.clone(),
+            *state.executions(),
+        );
+
+        // Load initial inputs from the corpus directory, skipping non-input files.
+        // A restored state already contains the corpus built so far.
+        if !restored {
+            let corpus_entries = corpus_entries(&session.corpus_dir);
+            let mutator = &$mutator;
+            if corpus_entries.is_empty() {
+                let seed = state.rand_mut().next();
+                let input = <$input as FuzzerInput>::initial_input(mutator, seed);
+                println!("Corpus was empty — using a randomly generated seed ({} bytes)", input.to_bytes().len());
+                fuzzer.add_input(&mut state, &mut executor, &mut mgr, input).unwrap();
+            }
+            for p in &corpus_entries {
+                let mut f = File::open(p).unwrap();
+                let mut buffer = Vec::new();
+                f.read_to_end(&mut buffer).unwrap();
+                match <$input as FuzzerInput>::from_bytes(&buffer) {
+                    Ok(input) => {
+                        fuzzer.evaluate_input(&mut state, &mut executor, &mut mgr, &input).unwrap();
+                    }
+                    Err(e) => println!("Skipping corpus entry {} that is not a valid input: {e}", p.display()),
+                }
+            }
         }
 
-        // Power-aware mutation stages: mutation count per corpus entry is scaled
+        // Power-aware mutation stage: mutation count per corpus entry is scaled
         // by its score (bitmap size, exec time, rarity) instead of random 1-128.
-        let havoc_mutator = HavocScheduledMutator::new(havoc_mutations());
+        let files: WorkerFiles = $files;
+        let saved_state_file = files.state.clone();
+        let mut last_save = Instant::now();
+        let save_stage = ClosureStage::new(
+            move |_fuzzer: &mut _, _executor: &mut _, state: &mut FuzzerStdState<$input>, _mgr: &mut _| {
+                if last_save.elapsed() >= STATE_SAVE_INTERVAL {
+                    save_fuzzer_state(state, &saved_state_file)?;
+                    last_save = Instant::now();
+                }
+                Ok(())
+            },
+        );
+
+        let stats_writer = Rc::new(RefCell::new(StatsWriter::new(
+            files.plot_data.clone(),
+            files.fuzzer_stats.clone(),
+            *state.executions(),
+        )));
+        let stage_stats_writer = Rc::clone(&stats_writer);
+        let stats_map_name = map_name.clone();
+        let export_stage = ClosureStage::new(
+            move |_fuzzer: &mut _, _executor: &mut _, state: &mut FuzzerStdState<$input>, _mgr: &mut _| {
+                let mut writer = stage_stats_writer.borrow_mut();
+                if writer.is_due() {
+                    write_stats(&mut writer, state, &stats_map_name)?;
+                }
+                Ok(())
+            },
+        );
+
+        // Requesting a stop makes `fuzz_loop` return `Error::ShuttingDown` at the end of
+        // the iteration, after notifying the event manager.
+        let stop_reason: Rc<Cell<Option<StopReason>>> = Rc::new(Cell::new(None));
+        let stage_stop_reason = Rc::clone(&stop_reason);
+        let stage_map_name = map_name.clone();
+        let stop_stage = ClosureStage::new(
+            move |_fuzzer: &mut _, _executor: &mut _, state: &mut FuzzerStdState<$input>, _mgr: &mut _| {
+                let edges = covered_edges(state, &stage_map_name);
+                if let Some(reason) = stop_monitor.check(*state.executions(), edges) {
+                    println!("Stopping the campaign: {reason}");
+                    stage_stop_reason.set(Some(reason));
+                    state.request_stop();
+                }
+                Ok(())
+            },
+        );
+
+        // Warns once if random probe IDs fill up the map, as new edges then often go unnoticed.
+        let mut warn_on_full_map = $warn_on_full_map;
+        let fill_map_name = map_name.clone();
+        let map_fill_stage = ClosureStage::new(
+            move |_fuzzer: &mut _, _executor: &mut _, state: &mut FuzzerStdState<$input>, _mgr: &mut _| {
+                if warn_on_full_map
+                    && let Some(ratio) = map_fill_ratio(state, &fill_map_name)
+                    && ratio >= MAP_FILL_WARNING_RATIO
+                {
+                    println!(
+                        "Warning: {:.0}% of the coverage map is covered, so new edges often collide with covered ones. Consider a larger map with `CanisterBuilder::with_map_size`.",
+                        ratio * 100.0
+                    );
+                    warn_on_full_map = false;
+                }
+                Ok(())
+            },
+        );
+
+        let tracer_executor = InProcessExecutor::new(
+            $tracing_harness,
+            tuple_list!($cmplog_observer),
+            &mut fuzzer,
+            &mut state,
+            &mut mgr,
+        )
+        .expect("Failed to create the tracing Executor");
+        // Like AFL++, every corpus entry is traced once, instead of paying for the trace and
+        // the export of the comparisons on every iteration.
+        let cmplog = $cmplog;
+        let mut traced = HashSet::new();
+        let cmplog_stage = IfStage::new(
+            move |_fuzzer: &mut _, _executor: &mut _, state: &mut FuzzerStdState<$input>, _mgr: &mut _| {
+                Ok(cmplog && state.current_corpus_id()?.is_some_and(|id| traced.insert(id)))
+            },
+            tuple_list!(
+                TracingStage::new(tracer_executor),
+                StdMutationalStage::new(InputToStateMutator::new())
+            ),
+        );
+
         let mut stages = tuple_list!(
             calibration_stage,
-            $($extra_stage,)*
-            StdPowerMutationalStage::new(havoc_mutator),
-            stats_stage
+            cmplog_stage,
+            StdPowerMutationalStage::new($mutator),
+            stats_stage,
+            save_stage,
+            export_stage,
+            map_fill_stage,
+            stop_stage
         );
 
-        fuzzer
-            .fuzz_loop(&mut stages, &mut executor, &mut state, &mut mgr)
-            .expect("Error in the fuzzing loop");
+        match fuzzer.fuzz_loop(&mut stages, &mut executor, &mut state, &mut mgr) {
+            Ok(()) | Err(Error::ShuttingDown) => {}
+            Err(e) => panic!("Error in the fuzzing loop: {e}"),
+        }
+        if let Err(e) = save_fuzzer_state(&mut state, &files.state) {
+            println!("Failed to save the fuzzer state to {}: {e}", files.state.display());
+        }
+        if let Err(e) = write_stats(&mut stats_writer.borrow_mut(), &state, &map_name) {
+            println!("Failed to write the statistics to {}: {e}", files.fuzzer_stats.display());
+        }
+        RunSummary {
+            stop_reason: stop_reason.get(),
+            execs: *state.executions(),
+            edges: covered_edges(&state, &map_name),
+            crashes: state.solutions().count(),
+            corpus_size: state.corpus().count(),
+            duration: started.elapsed(),
+        }
     }};
 }
-// Required for the macro to be usable within trait methods above.
+// Required for the macros to be usable within trait methods above.
 use run_fuzzing_loop;
+use run_fuzzing_worker;
@@ -0,0 +1,195 @@
+//! Machine-readable statistics of a fuzzing campaign.
+//!
+//! Every fuzzing worker appends a row to a CSV time series (`plot_data.csv`) every
+//! [`STATS_INTERVAL`] and rewrites an AFL-style `fuzzer_stats` file with the latest values.
+//! Both are written a last time when the campaign stops, so `fuzzer_stats` holds the final
+//! statistics. The files are stored in the campaign directory, next to the corpus; in parallel
+//! mode, the files of worker `n` are suffixed with `_<n>`.
+
+use crate::custom::feedback::crash_report::ObjectiveCounts;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// How often a fuzzing worker writes its statistics.
+pub const STATS_INTERVAL: Duration = Duration::from_secs(5);
+
+/// The columns of the CSV time series.
+pub const CSV_HEADER: &str = "unix_time,run_time,execs,execs_per_sec,corpus_size,edges,crashes,timeouts,ooms,max_instructions";
+
+/// A sample of the statistics of a fuzzing worker.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CampaignStats {
+    /// The time of the sample, in seconds since the Unix epoch.
+    pub unix_time: u64,
+    /// The time since the worker started, in seconds.
+    pub run_time: u64,
+    /// The number of executions of the harness, including those of a resumed campaign.
+    pub execs: u64,
+    /// The executions per second since the previous sample.
+    pub execs_per_sec: f64,
+    /// The number of entries in the corpus.
+    pub corpus_size: usize,
+    /// The number of covered edges of the coverage map.
+    pub edges: usize,
+    /// The saved objectives, by exit kind.
+    pub objectives: ObjectiveCounts,
+    /// The maximum instruction count, or 0 if instruction counting is disabled.
+    pub max_instructions: u64,
+}
+
+impl CampaignStats {
+    /// Formats the sample as a row of the CSV time series, see [`CSV_HEADER`].
+    pub fn to_csv_row(&self) -> String {
+        format!(
+            "{},{},{},{:.2},{},{},{},{},{},{}",
+            self.unix_time,
+            self.run_time,
+            self.execs,
+            self.execs_per_sec,
+            self.corpus_size,
+            self.edges,
+            self.objectives.crashes,
+            self.objectives.timeouts,
+            self.objectives.ooms,
+            self.max_instructions
+        )
+    }
+
+    /// Formats the sample as an AFL-style `fuzzer_stats` file, with one `key : value` per
+    /// line. The keys follow AFL++ where there is an equivalent (timeouts are `saved_hangs`).
+    pub fn to_fuzzer_stats(&self, start_time: u64, avg_execs_per_sec: f64) -> String {
+        let fields: [(&str, String); 11] = [
+            ("start_time", start_time.to_string()),
+            ("last_update", self.unix_time.to_string()),
+            ("run_time", self.run_time.to_string()),
+            ("execs_done", self.execs.to_string()),
+            ("execs_per_sec", format!("{avg_execs_per_sec:.2}")),
+            ("corpus_count", self.corpus_size.to_string()),
+            ("edges_found", self.edges.to_string()),
+            ("saved_crashes", self.objectives.crashes.to_string()),
+            ("saved_hangs", self.objectives.timeouts.to_string()),
+            ("saved_ooms", self.objectives.ooms.to_string()),
+            ("max_instructions", self.max_instructions.to_string()),
+        ];
+        fields
+            .iter()
+            .map(|(key, value)| format!("{key:<17}: {value}\n"))
+            .collect()
+    }
+}
+
+/// Writes the statistics files of a fuzzing worker.
+#[derive(Debug)]
+pub struct StatsWriter {
+    plot_file: PathBuf,
+    stats_file: PathBuf,
+    started: Instant,
+    start_time: u64,
+    initial_execs: u64,
+    /// The time and executions of the previous sample.
+    last: (Instant, u64),
+}
+
+impl StatsWriter {
+    /// Creates a writer for the time series `plot_file` and the `stats_file`.
+    ///
+    /// `initial_execs` is the number of executions when the worker starts, non-zero when
+    /// resuming a campaign. When resuming, new rows are appended to the existing time series.
+    pub fn new(plot_file: PathBuf, stats_file: PathBuf, initial_execs: u64) -> Self {
+        let now = Instant::now();
+        Self {
+            plot_file,
+            stats_file,
+            started: now,
+            start_time: unix_time(),
+            initial_execs,
+            last: (now, initial_execs),
+        }
+    }
+
+    /// Returns `true` if [`STATS_INTERVAL`] has passed since the previous sample.
+    pub fn is_due(&self) -> bool {
+        self.last.0.elapsed() >= STATS_INTERVAL
+    }
+
+    /// Completes `stats` with the time and execution rate, appends it to the time series and
+    /// rewrites the stats file.
+    pub fn write(&mut self, mut stats: CampaignStats) -> io::Result<()> {
+        let now = Instant::now();
+        stats.unix_time = unix_time();
+        stats.run_time = (now - self.started).as_secs();
+        stats.execs_per_sec = rate(stats.execs - self.last.1, now - self.last.0);
+        self.last = (now, stats.execs);
+
+        let new_file = !self.plot_file.exists();
+        let mut plot = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.plot_file)?;
+        if new_file {
+            writeln!(plot, "{CSV_HEADER}")?;
+        }
+        writeln!(plot, "{}", stats.to_csv_row())?;
+
+        let avg = rate(stats.execs - self.initial_execs, now - self.started);
+        // Write to a temporary file first, so readers never see a partial file.
+        let tmp_file = self.stats_file.with_extension("tmp");
+        fs::write(&tmp_file, stats.to_fuzzer_stats(self.start_time, avg))?;
+        fs::rename(&tmp_file, &self.stats_file)
+    }
+}
+
+fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+fn rate(execs: u64, elapsed: Duration) -> f64 {
+    if elapsed.is_zero() {
+        0.0
+    } else {
+        execs as f64 / elapsed.as_secs_f64()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> CampaignStats {
+        CampaignStats {
+            unix_time: 1_700_000_000,
+            run_time: 60,
+            execs: 12_000,
+            execs_per_sec: 199.5,
+            corpus_size: 42,
+            edges: 1337,
+            objectives: ObjectiveCounts {
+                crashes: 2,
+                timeouts: 1,
+                ooms: 0,
+            },
+            max_instructions: 987_654,
+        }
+    }
+
+    #[test]
+    fn test_csv_row_matches_header() {
+        let row = sample().to_csv_row();
+        assert_eq!(row, "1700000000,60,12000,199.50,42,1337,2,1,0,987654");
+        assert_eq!(row.split(',').count(), CSV_HEADER.split(',').count());
+    }
+
+    #[test]
+    fn test_fuzzer_stats() {
+        let stats = sample().to_fuzzer_stats(1_699_999_940, 200.0);
+        let lines: Vec<&str> = stats.lines().collect();
+        assert_eq!(lines[0], "start_time       : 1699999940");
+        assert!(lines.contains(&"execs_per_sec    : 200.00"));
+        assert!(lines.contains(&"saved_hangs      : 1"));
+        assert!(lines.contains(&"max_instructions : 987654"));
+    }
+}
@@ -1,6 +1,7 @@
 use pocket_ic::{ErrorCode, RejectResponse};
 use std::{fs::File, io::Read};
 
+use crate::custom::feedback::crash_report::record_reject;
 use crate::{fuzzer::WasmPath, libafl::executors::ExitKind};
 
 pub fn read_canister_bytes(wasm_path: WasmPath) -> Vec<u8> {
@@ -14,12 +15,16 @@ pub fn read_canister_bytes(wasm_path: WasmPath) -> Vec<u8> {
     buffer
 }
 
+/// Maps the result of a canister call to the `ExitKind` of the execution.
+///
+/// Traps are crashes, memory errors are OOMs and exceeding the instruction limit is a timeout.
+/// The reject of a failing call is recorded for [crash triage](crate::custom::feedback::crash_triage).
 pub fn parse_canister_result_for_trap(result: Result<Vec<u8>, RejectResponse>) -> ExitKind {
     match result {
         Ok(_) => ExitKind::Ok,
         Err(e) => {
             // println!("{e:?}");
-            match e.error_code {
+            let exit_kind = match e.error_code {
                 ErrorCode::CanisterTrapped | ErrorCode::CanisterCalledTrap => ExitKind::Crash,
                 ErrorCode::CanisterMemoryAccessLimitExceeded
                 | ErrorCode::InsufficientMemoryAllocation
@@ -27,7 +32,11 @@ pub fn parse_canister_result_for_trap(result: Result<Vec<u8>, RejectResponse>) -
                 | ErrorCode::CanisterWasmMemoryLimitExceeded => ExitKind::Oom,
                 ErrorCode::CanisterInstructionLimitExceeded => ExitKind::Timeout,
                 _ => ExitKind::Ok, // How to handle other errors?
+            };
+            if exit_kind != ExitKind::Ok {
+                record_reject(&e);
             }
+            exit_kind
         }
     }
 }
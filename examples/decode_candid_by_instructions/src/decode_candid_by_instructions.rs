@@ -1,8 +1,8 @@
 //! Fuzzer that maximizes IC instructions consumed by Candid decoding.
 //!
 //! This example demonstrates **automated instruction counting** via wasm instrumentation.
-//! The target canister (`decode_candid`) is instrumented with
-//! `instrument_instruction_count: true`, which injects wrapper functions that read
+//! The target canister (`decode_candid`) is built with
+//! `CanisterBuilder::with_instruction_count()`, which injects wrapper functions that read
 //! `ic0.performance_counter` after each method call. No manual changes to the canister
 //! source code are needed.
 //!
@@ -13,14 +13,12 @@
 use candid::Principal;
 use canfuzz::define_fuzzer_state;
 use canfuzz::fuzzer::{CanisterBuilder, FuzzerBuilder};
-use canfuzz::instrumentation::{InstrumentationArgs, Seed, instrument_wasm_for_fuzzing};
 use canfuzz::orchestrator::FuzzerOrchestrator;
-use canfuzz::util::{parse_canister_result_for_trap, read_canister_bytes};
+use canfuzz::util::parse_canister_result_for_trap;
+use slog::Level;
 
 use canfuzz::libafl::executors::ExitKind;
 use canfuzz::libafl::inputs::BytesInput;
-use pocket_ic::PocketIcBuilder;
-use slog::Level;
 use std::path::PathBuf;
 use std::time::Duration;
 
@@ -29,12 +27,15 @@ define_fuzzer_state!(DecodeCandidFuzzer);
 fn main() {
     let canister = CanisterBuilder::new("decode_candid")
         .with_wasm_env("DECODE_CANDID_WASM_PATH")
+        .with_instruction_count()
         .as_coverage()
+        .with_cycles(u128::MAX / 2)
         .build();
 
     let state = FuzzerBuilder::new()
         .name("decode_candid_by_instructions")
         .with_canister(canister)
+        .with_log_level(Level::Critical)
         .build();
 
     let mut fuzzer_state = DecodeCandidFuzzer(state);
@@ -52,28 +53,6 @@ impl FuzzerOrchestrator for DecodeCandidFuzzer {
             .join("corpus")
     }
 
-    fn init(&mut self) {
-        let test = PocketIcBuilder::new()
-            .with_application_subnet()
-            .with_log_level(Level::Critical)
-            .build();
-        self.as_mut().init_state(test);
-        let test = self.get_state_machine();
-
-        for info in self.as_mut().get_iter_mut_canister_info() {
-            let canister_id = test.create_canister();
-            test.add_cycles(canister_id, u128::MAX / 2);
-            let module = instrument_wasm_for_fuzzing(InstrumentationArgs {
-                wasm_bytes: read_canister_bytes(info.wasm_path.clone()),
-                history_size: 8,
-                seed: Seed::Random,
-                instrument_instruction_count: true,
-            });
-            test.install_canister(canister_id, module, vec![], None);
-            info.id = Some(canister_id);
-        }
-    }
-
     fn execute(&self, input: BytesInput) -> ExitKind {
         let test = self.get_state_machine();
 
@@ -8,17 +8,14 @@ use k256::{
     Scalar, Secp256k1,
     ecdsa::{Signature, hazmat},
 };
-use pocket_ic::PocketIcBuilder;
 use sha2::{Digest, Sha256};
 use std::path::PathBuf;
 use std::time::Duration;
 
-use slog::Level;
-
 use canfuzz::fuzzer::{CanisterBuilder, FuzzerBuilder};
-use canfuzz::instrumentation::{InstrumentationArgs, Seed, instrument_wasm_for_fuzzing};
 use canfuzz::orchestrator::FuzzerOrchestrator;
-use canfuzz::util::{parse_canister_result_for_trap, read_canister_bytes};
+use canfuzz::util::parse_canister_result_for_trap;
+use slog::Level;
 
 define_fuzzer_state!(MotokoDiffFuzzer);
 
@@ -26,11 +23,13 @@ fn main() {
     let canister = CanisterBuilder::new("ecdsa_sign")
         .with_wasm_env("MOTOKO_CANISTER_WASM_PATH")
         .as_coverage()
+        .with_cycles(u128::MAX / 2)
         .build();
 
     let state = FuzzerBuilder::new()
         .name("motoko_diff")
         .with_canister(canister)
+        .with_log_level(Level::Critical)
         .build();
 
     let mut fuzzer_state = MotokoDiffFuzzer(state);
@@ -47,28 +46,6 @@ impl FuzzerOrchestrator for MotokoDiffFuzzer {
             .join("corpus")
     }
 
-    fn init(&mut self) {
-        let test = PocketIcBuilder::new()
-            .with_application_subnet()
-            .with_log_level(Level::Critical)
-            .build();
-        self.as_mut().init_state(test);
-        let test = self.get_state_machine();
-
-        for info in self.as_mut().get_iter_mut_canister_info() {
-            let canister_id = test.create_canister();
-            test.add_cycles(canister_id, u128::MAX / 2);
-            let module = instrument_wasm_for_fuzzing(InstrumentationArgs {
-                wasm_bytes: read_canister_bytes(info.wasm_path.clone()),
-                history_size: 8,
-                seed: Seed::Random,
-                instrument_instruction_count: false,
-            });
-            test.install_canister(canister_id, module, vec![], None);
-            info.id = Some(canister_id);
-        }
-    }
-
     fn execute(&self, input: ValueInput<Vec<u8>>) -> ExitKind {
         let test = self.get_state_machine();
 
@@ -3,29 +3,30 @@ use canfuzz::custom::mutator::candid::CandidTypeDefArgs;
 use canfuzz::define_fuzzer_state;
 use canfuzz::libafl::executors::ExitKind;
 use canfuzz::libafl::inputs::BytesInput;
-use once_cell::sync::OnceCell;
-use pocket_ic::PocketIcBuilder;
 use std::path::PathBuf;
 
-use slog::Level;
-
-use canfuzz::fuzzer::{CanisterBuilder, FuzzerBuilder};
-use canfuzz::instrumentation::{InstrumentationArgs, Seed, instrument_wasm_for_fuzzing};
+use canfuzz::fuzzer::{CanisterBuilder, FuzzerBuilder, ResetStrategy};
 use canfuzz::orchestrator::FuzzerOrchestrator;
-use canfuzz::util::{parse_canister_result_for_trap, read_canister_bytes};
+use canfuzz::util::parse_canister_result_for_trap;
+use slog::Level;
 
-static SNAPSHOT_ID: OnceCell<Vec<u8>> = OnceCell::new();
 define_fuzzer_state!(RusqliteFuzzer);
 
 fn main() {
     let canister = CanisterBuilder::new("rusqlite_db")
         .with_wasm_env("RUSQLITE_DB_WASM_PATH")
+        // SQLite has too many edges for the default map.
+        .with_map_size(1 << 20)
+        .with_instruction_count()
+        .with_reset(ResetStrategy::Snapshot)
         .as_coverage()
+        .with_cycles(u128::MAX / 2)
         .build();
 
     let state = FuzzerBuilder::new()
         .name("rusqlite_fuzz")
         .with_canister(canister)
+        .with_log_level(Level::Critical)
         .build();
 
     let mut fuzzer = RusqliteFuzzer(state);
@@ -58,43 +59,6 @@ impl FuzzerOrchestrator for RusqliteFuzzer {
             .join("corpus")
     }
 
-    fn init(&mut self) {
-        let test = PocketIcBuilder::new()
-            .with_application_subnet()
-            .with_log_level(Level::Critical)
-            .build();
-        self.as_mut().init_state(test);
-        let test = self.get_state_machine();
-
-        for info in self.as_mut().get_iter_mut_canister_info() {
-            let canister_id = test.create_canister();
-            test.add_cycles(canister_id, u128::MAX / 2);
-            let module = instrument_wasm_for_fuzzing(InstrumentationArgs {
-                wasm_bytes: read_canister_bytes(info.wasm_path.clone()),
-                history_size: 8,
-                seed: Seed::Random,
-                instrument_instruction_count: true,
-            });
-            test.install_canister(canister_id, module, vec![], None);
-            info.id = Some(canister_id);
-        }
-
-        let snapshot = test
-            .take_canister_snapshot(self.get_coverage_canister_id(), None, None)
-            .unwrap();
-        SNAPSHOT_ID.set(snapshot.id).unwrap();
-    }
-
-    fn setup(&self) {
-        let test = self.get_state_machine();
-        test.load_canister_snapshot(
-            self.get_coverage_canister_id(),
-            None,
-            SNAPSHOT_ID.get().unwrap().to_vec(),
-        )
-        .unwrap();
-    }
-
     fn execute(&self, input: BytesInput) -> ExitKind {
         let test = self.get_state_machine();
 
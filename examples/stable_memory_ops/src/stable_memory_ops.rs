@@ -3,24 +3,23 @@ use canfuzz::custom::mutator::candid::CandidTypeDefArgs;
 use canfuzz::define_fuzzer_state;
 use canfuzz::libafl::executors::ExitKind;
 use canfuzz::libafl::inputs::ValueInput;
-use once_cell::sync::OnceCell;
 use pocket_ic::PocketIcBuilder;
 use std::path::PathBuf;
 
 use slog::Level;
 
-use canfuzz::fuzzer::{CanisterBuilder, FuzzerBuilder};
-use canfuzz::instrumentation::{InstrumentationArgs, Seed, instrument_wasm_for_fuzzing};
+use canfuzz::fuzzer::{CanisterBuilder, FuzzerBuilder, ResetStrategy};
 use canfuzz::orchestrator::FuzzerOrchestrator;
-use canfuzz::util::{parse_canister_result_for_trap, read_canister_bytes};
+use canfuzz::util::parse_canister_result_for_trap;
 
-static SNAPSHOT_ID: OnceCell<Vec<u8>> = OnceCell::new();
 define_fuzzer_state!(StableMemoryFuzzer);
 
 fn main() {
     let canister = CanisterBuilder::new("stable_memory")
         .with_wasm_env("STABLE_MEMORY_WASM_PATH")
+        .with_reset(ResetStrategy::Snapshot)
         .as_coverage()
+        .with_cycles(u128::MAX / 2)
         .build();
 
     let state = FuzzerBuilder::new()
@@ -66,35 +65,7 @@ impl FuzzerOrchestrator for StableMemoryFuzzer {
             .with_auto_progress()
             .build();
         self.as_mut().init_state(test);
-        let test = self.get_state_machine();
-
-        for info in self.as_mut().get_iter_mut_canister_info() {
-            let canister_id = test.create_canister();
-            test.add_cycles(canister_id, u128::MAX / 2);
-            let module = instrument_wasm_for_fuzzing(InstrumentationArgs {
-                wasm_bytes: read_canister_bytes(info.wasm_path.clone()),
-                history_size: 8,
-                seed: Seed::Random,
-                instrument_instruction_count: false,
-            });
-            test.install_canister(canister_id, module, vec![], None);
-            info.id = Some(canister_id);
-        }
-
-        let snapshot = test
-            .take_canister_snapshot(self.get_coverage_canister_id(), None, None)
-            .unwrap();
-        SNAPSHOT_ID.set(snapshot.id).unwrap();
-    }
-
-    fn setup(&self) {
-        let test = self.get_state_machine();
-        test.load_canister_snapshot(
-            self.get_coverage_canister_id(),
-            None,
-            SNAPSHOT_ID.get().unwrap().to_vec(),
-        )
-        .unwrap();
+        self.as_mut().setup_canisters();
     }
 
     fn execute(&self, input: ValueInput<Vec<u8>>) -> ExitKind {
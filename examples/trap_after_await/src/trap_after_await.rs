@@ -5,41 +5,65 @@ use candid::{Decode, Encode, Principal};
 use canfuzz::define_fuzzer_state;
 use canfuzz::libafl::executors::ExitKind;
 use canfuzz::libafl::inputs::ValueInput;
-use once_cell::sync::OnceCell;
 use pocket_ic::PocketIcBuilder;
 use slog::Level;
 
 use canfuzz::custom::mutator::candid::CandidTypeDefArgs;
-use canfuzz::fuzzer::{CanisterBuilder, FuzzerBuilder};
-use canfuzz::instrumentation::{InstrumentationArgs, Seed, instrument_wasm_for_fuzzing};
+use canfuzz::fuzzer::{CanisterBuilder, FuzzerBuilder, FuzzerState, ResetStrategy};
+use canfuzz::instrumentation::{
+    InstrumentationArgs, Seed, extract_dictionary, instrument_wasm_for_fuzzing,
+};
 
 use canfuzz::orchestrator::FuzzerOrchestrator;
 use canfuzz::util::read_canister_bytes;
 
 const SYNCHRONOUS_EXECUTION: bool = false;
-static SNAPSHOT: OnceCell<(Vec<u8>, Vec<u8>)> = OnceCell::new();
 define_fuzzer_state!(TrapAfterAwaitFuzzer);
 
 fn main() {
+    let mut fuzzer_state = TrapAfterAwaitFuzzer(fuzzer_state());
+
+    fuzzer_state.run();
+}
+
+fn fuzzer_state() -> FuzzerState {
+    // Both canisters are instrumented, so new edges in the ledger also count as progress.
     let ledger = CanisterBuilder::new("ledger")
         .with_wasm_env("LEDGER_WASM_PATH")
-        .as_support()
+        .with_reset(ResetStrategy::Snapshot)
+        .as_coverage()
         .build();
 
     let transfer = CanisterBuilder::new("transfer")
         .with_wasm_env("TRANSFER_WASM_PATH")
+        .with_reset(ResetStrategy::Snapshot)
         .as_coverage()
         .build();
 
-    let state = FuzzerBuilder::new()
+    FuzzerBuilder::new()
         .name("trap_after_await")
-        .with_canister(ledger)
+        // The transfer canister is registered first, as it is the main target.
         .with_canister(transfer)
-        .build();
-
-    let mut fuzzer_state = TrapAfterAwaitFuzzer(state);
+        .with_canister(ledger)
+        .build()
+}
 
-    fuzzer_state.run();
+impl TrapAfterAwaitFuzzer {
+    /// Instruments the Wasm of the canister `name` and adds its magic values to the
+    /// dictionary, as `setup_canisters` would.
+    fn instrument(&mut self, name: &str) -> Vec<u8> {
+        let wasm_bytes =
+            read_canister_bytes(self.as_ref().get_canister_wasm_path_by_name(name).clone());
+        self.as_mut()
+            .extend_dictionary(&extract_dictionary(&wasm_bytes));
+        instrument_wasm_for_fuzzing(InstrumentationArgs {
+            wasm_bytes,
+            history_size: 8,
+            seed: Seed::Random,
+            instrument_instruction_count: false,
+            ..Default::default()
+        })
+    }
 }
 
 impl FuzzerOrchestrator for TrapAfterAwaitFuzzer {
@@ -78,25 +102,12 @@ impl FuzzerOrchestrator for TrapAfterAwaitFuzzer {
 
         let ledger_canister_id = test.create_canister();
         test.add_cycles(ledger_canister_id, u128::MAX / 2);
-        let module = read_canister_bytes(
-            self.as_ref()
-                .get_canister_wasm_path_by_name("ledger")
-                .clone(),
-        );
+        let module = self.instrument("ledger");
         test.install_canister(ledger_canister_id, module, vec![], None);
 
         let main_canister_id = test.create_canister();
         test.add_cycles(main_canister_id, u128::MAX / 2);
-        let module = instrument_wasm_for_fuzzing(InstrumentationArgs {
-            wasm_bytes: read_canister_bytes(
-                self.as_ref()
-                    .get_canister_wasm_path_by_name("transfer")
-                    .clone(),
-            ),
-            history_size: 8,
-            seed: Seed::Random,
-            instrument_instruction_count: false,
-        });
+        let module = self.instrument("transfer");
         test.install_canister(
             main_canister_id,
             module,
@@ -104,9 +115,12 @@ impl FuzzerOrchestrator for TrapAfterAwaitFuzzer {
             None,
         );
 
-        let canisters = [ledger_canister_id, main_canister_id];
-        for (info, id) in self.as_mut().get_iter_mut_canister_info().zip(canisters) {
-            info.id = Some(id)
+        for info in self.as_mut().get_iter_mut_canister_info() {
+            info.id = Some(match info.name.as_str() {
+                "ledger" => ledger_canister_id,
+                "transfer" => main_canister_id,
+                name => panic!("Unknown canister '{name}'"),
+            });
         }
 
         // Prepare the main canister
@@ -152,27 +166,6 @@ impl FuzzerOrchestrator for TrapAfterAwaitFuzzer {
 
         // should never fail
         assert_eq!(b1, b2);
-
-        let s1 = test
-            .take_canister_snapshot(main_canister_id, None, None)
-            .unwrap()
-            .id;
-        let s2 = test
-            .take_canister_snapshot(ledger_canister_id, None, None)
-            .unwrap()
-            .id;
-        SNAPSHOT.set((s1, s2)).unwrap();
-    }
-
-    fn setup(&self) {
-        let test = self.get_state_machine();
-        let main_canister_id = self.get_coverage_canister_id();
-        let ledger_canister_id = self.as_ref().get_canister_id_by_name("ledger");
-        let (s1, s2) = SNAPSHOT.get().unwrap();
-        test.load_canister_snapshot(main_canister_id, None, s1.to_vec())
-            .unwrap();
-        test.load_canister_snapshot(ledger_canister_id, None, s2.to_vec())
-            .unwrap();
     }
 
     fn execute(&self, input: ValueInput<Vec<u8>>) -> ExitKind {
@@ -247,3 +240,19 @@ impl FuzzerOrchestrator for TrapAfterAwaitFuzzer {
         ExitKind::Ok
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transfer_magic_values_reach_the_dictionary() {
+        let mut fuzzer = TrapAfterAwaitFuzzer(fuzzer_state());
+        fuzzer.instrument("transfer");
+
+        // `refund_balance` traps if its argument is 3278, i.e. its bytes are 206, 12 and zeros.
+        // The compiler folds the byte comparisons into a single comparison with 3278.
+        let tokens = fuzzer.as_ref().dictionary().tokens();
+        assert!(tokens.contains(&vec![206, 12]));
+    }
+}